@@ -0,0 +1,302 @@
+//! Opt-in HTTP management API for editor tasks and scripts, bound to
+//! 127.0.0.1 and protected by a bearer token.
+//!
+//! - `GET /services`, `GET /services/{name}`: service snapshots as JSON
+//! - `POST /services/{name}/start|stop|restart`: act on a service and return
+//!   its snapshot once it is ready (or stopped)
+//! - `GET /logs`: the terminal buffer followed by new entries, as server-sent
+//!   events whose data is a JSON log entry
+//! - `GET /metrics`: CPU and memory usage per service
+//!
+//! Clients send `Authorization: Bearer <token>`; `EventSource` clients, which
+//! cannot set headers, may pass `?token=<token>` instead.
+
+use std::io::Write;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Server};
+
+use crate::config::ApiConfig;
+use crate::logs::LogEntry;
+use crate::services::{runfiles, MetricsCollector, Service, ServiceInfo, ServiceRegistry, ServiceState};
+use crate::ui::Terminal;
+
+/// Comment sent on idle event streams so dead clients are noticed.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, PartialEq)]
+enum Route<'a> {
+    Services,
+    Service(&'a str),
+    Action(&'a str, Action),
+    Logs,
+    Metrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Start,
+    Stop,
+    Restart,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+#[derive(Clone)]
+struct Api {
+    registry: ServiceRegistry,
+    terminal: Terminal,
+    metrics: MetricsCollector,
+    token: String,
+}
+
+/// Starts serving on `127.0.0.1:port` in the background and returns the
+/// port actually bound (useful when `port` is 0).
+pub fn serve(registry: ServiceRegistry, terminal: Terminal, config: &ApiConfig, port: u16) -> Result<u16, String> {
+    let token = resolve_token(config)?;
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|e| format!("Failed to start the management API on 127.0.0.1:{port}: {e}"))?;
+    let port = server.server_addr().to_ip().map(|addr| addr.port()).unwrap_or(port);
+
+    let api = Api {
+        registry,
+        terminal,
+        metrics: MetricsCollector::start(),
+        token,
+    };
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let api = api.clone();
+            std::thread::spawn(move || api.handle(request));
+        }
+    });
+
+    Ok(port)
+}
+
+/// The configured token, or the one generated on an earlier run, or a new
+/// random one saved to `run/api-token` for scripts to read.
+fn resolve_token(config: &ApiConfig) -> Result<String, String> {
+    if let Some(token) = config.token.as_ref().filter(|token| !token.is_empty()) {
+        return Ok(token.clone());
+    }
+
+    let path = runfiles::api_token_path();
+    if let Some(token) = std::fs::read_to_string(&path).ok().map(|token| token.trim().to_string()) {
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    let token: String = rand::random::<[u8; 32]>().iter().map(|byte| format!("{byte:02x}")).collect();
+    runfiles::write_private(&path, &token)?;
+    Ok(token)
+}
+
+impl Api {
+    fn handle(&self, request: Request) {
+        let (path, query) = match request.url().split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (request.url().to_string(), None),
+        };
+
+        if !self.authorized(&request, query.as_deref()) {
+            return respond(request, 401, &ErrorBody { error: "missing or invalid token" });
+        }
+
+        let Some(route) = parse_route(&path) else {
+            return respond(request, 404, &ErrorBody { error: "not found" });
+        };
+
+        let method = request.method().clone();
+        match (method, route) {
+            (Method::Get, Route::Services) => {
+                let snapshots: Vec<_> = self.registry.iter().map(ServiceInfo::snapshot).collect();
+                respond(request, 200, &snapshots)
+            }
+            (Method::Get, Route::Service(name)) => match self.registry.get(&percent_decode(name)) {
+                Some(service) => respond(request, 200, &service.snapshot()),
+                None => respond(request, 404, &ErrorBody { error: "unknown service" }),
+            },
+            (Method::Post, Route::Action(name, action)) => match self.registry.get(&percent_decode(name)) {
+                Some(service) => match self.act(service, action) {
+                    Ok(()) => respond(request, 200, &service.snapshot()),
+                    Err(e) => respond(request, 500, &ErrorBody { error: &e }),
+                },
+                None => respond(request, 404, &ErrorBody { error: "unknown service" }),
+            },
+            (Method::Get, Route::Logs) => self.stream_logs(request),
+            (Method::Get, Route::Metrics) => match self.metrics.collect(&self.registry) {
+                Ok(metrics) => respond(request, 200, &metrics),
+                Err(e) => respond(request, 500, &ErrorBody { error: &e }),
+            },
+            _ => respond(request, 405, &ErrorBody { error: "method not allowed" }),
+        }
+    }
+
+    fn authorized(&self, request: &Request, query: Option<&str>) -> bool {
+        let header = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .and_then(|header| header.value.as_str().strip_prefix("Bearer "));
+        let query = query.and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+        });
+
+        header.or(query).is_some_and(|token| constant_time_eq(token.trim(), &self.token))
+    }
+
+    fn act(&self, service: &ServiceInfo, action: Action) -> Result<(), String> {
+        if matches!(action, Action::Stop | Action::Restart) && service.state() != ServiceState::Stopped {
+            service.stop();
+            if let ServiceState::Error(reason) = service.state() {
+                return Err(format!("Failed to stop {}: {reason}", service.name));
+            }
+        }
+
+        if matches!(action, Action::Start | Action::Restart) {
+            self.registry.start_with_dependencies(&service.name)?;
+            service.wait_until_ready(service.startup_timeout() + Duration::from_secs(5))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the response by hand so every event is flushed as it happens;
+    /// tiny_http would otherwise buffer the chunked body.
+    fn stream_logs(&self, request: Request) {
+        let (backlog, entries) = self.terminal.subscribe();
+        let mut writer = request.into_writer();
+
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+        if writer.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        for entry in backlog {
+            if send_entry(&mut writer, &entry).is_err() {
+                return;
+            }
+        }
+
+        loop {
+            let result = match entries.recv_timeout(KEEPALIVE) {
+                Ok(entry) => send_entry(&mut writer, &entry),
+                Err(RecvTimeoutError::Timeout) => writer
+                    .write_all(b": keepalive\n\n")
+                    .and_then(|_| writer.flush()),
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            if result.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn send_entry(writer: &mut impl Write, entry: &LogEntry) -> std::io::Result<()> {
+    send_event(writer, &serde_json::to_string(entry)?)
+}
+
+fn send_event(writer: &mut impl Write, line: &str) -> std::io::Result<()> {
+    let mut event = String::new();
+    for part in line.split('\n') {
+        event.push_str("data: ");
+        event.push_str(part.trim_end_matches('\r'));
+        event.push('\n');
+    }
+    event.push('\n');
+    writer.write_all(event.as_bytes())?;
+    writer.flush()
+}
+
+fn respond(request: Request, status: u16, body: &impl Serialize) {
+    let body = serde_json::to_string(body).unwrap_or_else(|_| "{}".to_string());
+    let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+    if let Ok(header) = Header::from_bytes("Content-Type", "application/json") {
+        response.add_header(header);
+    }
+    let _ = request.respond(response);
+}
+
+fn parse_route(path: &str) -> Option<Route<'_>> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["services"] => Some(Route::Services),
+        ["services", name] => Some(Route::Service(name)),
+        ["services", name, action] => {
+            let action = match *action {
+                "start" => Action::Start,
+                "stop" => Action::Stop,
+                "restart" => Action::Restart,
+                _ => return None,
+            };
+            Some(Route::Action(name, action))
+        }
+        ["logs"] => Some(Route::Logs),
+        ["metrics"] => Some(Route::Metrics),
+        _ => None,
+    }
+}
+
+/// Decodes `%XX` escapes, so names with spaces can be used in paths.
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| input.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(parse_route("/services"), Some(Route::Services));
+        assert_eq!(parse_route("/services/MariaDB/"), Some(Route::Service("MariaDB")));
+        assert_eq!(parse_route("/services/Nginx/restart"), Some(Route::Action("Nginx", Action::Restart)));
+        assert_eq!(parse_route("/services/Nginx/explode"), None);
+        assert_eq!(parse_route("/metrics"), Some(Route::Metrics));
+        assert_eq!(parse_route("/"), None);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("PHP%208.4"), "PHP 8.4");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn test_event_lines_are_prefixed() {
+        let mut out = Vec::new();
+        send_event(&mut out, "one\ntwo").unwrap();
+        assert_eq!(out, b"data: one\ndata: two\n\n");
+    }
+}
@@ -1,8 +1,8 @@
-use crate::services::{Service, ServiceInfo};
+use crate::config::XerveConfig;
+use crate::daemon::DaemonClient;
+use crate::services::{Service, ServiceRegistry};
 use eframe::egui;
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use std::process::{Command, Stdio};
 use crate::ui::theme;
 
 #[derive(Default, PartialEq)]
@@ -10,14 +10,27 @@ enum AppTab {
     #[default]
     Services,
     ResourceMonitoring,
+    Logs,
+    Sql,
+    Databases,
+    Sites,
+    Php,
 }
 
 pub struct XerveApp {
-    services: Vec<ServiceInfo>,
+    services: ServiceRegistry,
     terminal: crate::ui::Terminal,
-    _php_cgi_process: Option<std::process::Child>,
+    php: crate::php::PhpVersions,
+    /// The configured `[sites] htdocs`, opened by "Open htdocs".
+    htdocs: std::path::PathBuf,
     current_tab: AppTab,
     resource_monitoring: crate::ui::ResourceMonitoring,
+    log_history: crate::ui::LogHistory,
+    command_prompt: crate::ui::CommandPrompt,
+    sql_console: crate::ui::SqlConsole,
+    database_panel: crate::ui::DatabasePanel,
+    sites_panel: crate::ui::SitesPanel,
+    php_panel: crate::ui::PhpPanel,
 }
 
 impl XerveApp {
@@ -26,7 +39,7 @@ impl XerveApp {
         let mut running_services = 0;
         
         for service in &self.services {
-            if service.status() == "Running" {
+            if service.state().is_active() {
                 running_services += 1;
             }
         }
@@ -38,8 +51,8 @@ impl XerveApp {
         
         self.terminal.add_log(format!("Found {running_services} running services. Stopping all..."));
         
-        for service in &self.services {
-            if service.status() == "Running" {
+        for service in self.services.stop_order() {
+            if service.state().is_active() {
                 self.terminal.add_log(format!("Stopping {}...", service.name));
                 service.stop();
             }
@@ -54,7 +67,7 @@ impl XerveApp {
         loop {
             let mut all_stopped = true;
             for service in &self.services {
-                if service.status() == "Running" {
+                if service.state().is_active() {
                     all_stopped = false;
                     break;
                 }
@@ -80,15 +93,19 @@ impl XerveApp {
         self.terminal.clone()
     }
     
+    /// Puts the default PHP version on PATH.
     fn setup_php_path(&self) {
-        let php_dir = "./resource/php-8.4.11";
-        
-        if !std::path::Path::new(php_dir).exists() {
-            self.terminal.add_log(format!("PHP directory not found at {php_dir}. Please ensure PHP is installed in the resource directory."));
+        let Some(php) = self.php.default_version() else {
+            match &self.php.config().default_version {
+                Some(version) => self.terminal.add_log(format!("Default PHP version {version} not found in {}/php-*.", self.php.config().dir)),
+                None => self.terminal.add_log(format!("No PHP found in {}/php-*. Please ensure PHP is installed in the resource directory.", self.php.config().dir)),
+            }
             return;
-        }
+        };
+        self.terminal.add_log(format!("Using PHP {} as the default version", php.version));
+        let php_dir = php.dir.to_string_lossy();
         
-        let abs_php_dir = match crate::utils::env_path::get_absolute_path(php_dir) {
+        let abs_php_dir = match crate::utils::env_path::get_absolute_path(&php_dir) {
             Ok(path) => path,
             Err(e) => {
                 self.terminal.add_log(format!("Failed to get absolute path for PHP directory: {e}"));
@@ -127,45 +144,11 @@ impl XerveApp {
         #[cfg(not(windows))]
         match crate::utils::env_path::add_to_path(&abs_php_dir) {
             Ok(()) => {
-                self.terminal.add_log(format!("Successfully added {} to current session PATH", abs_php_dir));
+                self.terminal.add_log(format!("Successfully added {abs_php_dir} to current session PATH"));
                 self.terminal.add_log("Note: This PATH setting is only valid for the current session.".to_string());
             }
             Err(e) => {
-                self.terminal.add_log(format!("Failed to add {} to PATH: {}", abs_php_dir, e));
-            }
-        }
-    }
-    
-    fn start_php_cgi(&mut self) {
-        let php_cgi_path = "./resource/php-8.4.11/php-cgi.exe";
-        if !std::path::Path::new(php_cgi_path).exists() {
-            self.terminal.add_log(format!("PHP-CGI not found at {php_cgi_path}. Skipping PHP-CGI startup."));
-            return;
-        }
-        
-        let mut command = Command::new(php_cgi_path);
-        command
-            .arg("-b")
-            .arg("127.0.0.1:9000")
-            .arg("-c")
-            .arg("./resource/php-8.4.11/php.ini")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        
-        #[cfg(windows)]
-        {
-            use std::os::windows::process::CommandExt;
-            command.creation_flags(0x08000000); // CREATE_NO_WINDOW
-        }
-        
-        match command.spawn() {
-            Ok(child) => {
-                self._php_cgi_process = Some(child);
-                
-                self.terminal.add_log("PHP-CGI started in background on 127.0.0.1:9000".to_string());
-            }
-            Err(e) => {
-                self.terminal.add_log(format!("Failed to start PHP-CGI: {e}"));
+                self.terminal.add_log(format!("Failed to add {abs_php_dir} to PATH: {e}"));
             }
         }
     }
@@ -173,40 +156,64 @@ impl XerveApp {
 
 impl Default for XerveApp {
     fn default() -> Self {
-        let nginx_dir = std::path::Path::new("./resource/nginx");
-        let nginx_pid_file = nginx_dir.join("logs/nginx.pid");
-        
-        let nginx_status = if nginx_pid_file.exists() {
-            "Running"
-        } else {
-            "Stopped"
+        let terminal = crate::ui::Terminal::new();
+        crate::services::set_terminal(terminal.clone());
+
+        let config = match XerveConfig::load() {
+            Ok(config) => config,
+            Err(e) => {
+                terminal.add_log(format!("Failed to load service configuration: {e}"));
+                terminal.add_log("Falling back to the default Nginx and MariaDB services.".to_string());
+                XerveConfig::default_config()
+            }
         };
-        
-        let nginx_status = Arc::new(Mutex::new(nginx_status.to_string()));
-
-        let mariadb_status = Arc::new(Mutex::new("Stopped".to_string()));
-
-        let nginx_service = ServiceInfo::new(
-            "Nginx",
-            &nginx_status.lock().unwrap(),
-            "./resource/nginx/nginx.exe",
-        );
-        let mariadb_service = ServiceInfo::new(
-            "MariaDB",
-            &mariadb_status.lock().unwrap(),
-            "./resource/mariadb/bin/mariadbd.exe",
-        );
-
-        let mut app = XerveApp {
-            services: vec![nginx_service, mariadb_service],
-            terminal: crate::ui::Terminal::new(),
-            _php_cgi_process: None,
+        for deprecation in config.deprecations() {
+            terminal.add_log(format!("Warning: {deprecation}"));
+        }
+
+        crate::logs::store::configure(&config.logs);
+        terminal.set_capacity(config.logs.terminal_lines);
+        let mut services = ServiceRegistry::from_config(&config);
+        if let Ok(client) = DaemonClient::connect() {
+            terminal.add_log("Attached to the Xerve daemon; services keep running when this window closes.".to_string());
+            if let Err(e) = client.forward_logs(terminal.clone()) {
+                terminal.add_log(format!("Failed to subscribe to daemon logs: {e}"));
+            }
+            services.attach_daemon(client);
+        } else {
+            services.adopt_running();
+        }
+
+        if config.api.enabled && services.daemon().is_none() {
+            match crate::api::serve(services.clone(), terminal.clone(), &config.api, config.api.port) {
+                Ok(port) => terminal.add_log(format!("Management API listening on http://127.0.0.1:{port}")),
+                Err(e) => terminal.add_log(e),
+            }
+        }
+
+        // The daemon takes the nightly backups when it owns the services.
+        if services.daemon().is_none() {
+            crate::database::backup::schedule(services.clone(), config.backups.clone());
+        }
+
+        let php = crate::php::PhpVersions::discover(&config.php);
+        let command_prompt = crate::ui::CommandPrompt::new(&services, &php);
+        let app = XerveApp {
+            services,
+            terminal,
+            php: php.clone(),
+            htdocs: crate::config::absolute_path(&config.sites.htdocs),
             current_tab: AppTab::Services,
             resource_monitoring: crate::ui::ResourceMonitoring::new(),
+            log_history: crate::ui::LogHistory::new(),
+            command_prompt,
+            sql_console: crate::ui::SqlConsole::new(),
+            database_panel: crate::ui::DatabasePanel::new(config.backups.clone()),
+            sites_panel: crate::ui::SitesPanel::new(&config.sites, php.clone()),
+            php_panel: crate::ui::PhpPanel::new(php),
         };
         
         app.setup_php_path();
-        app.start_php_cgi();
         
         app
     }
@@ -214,7 +221,10 @@ impl Default for XerveApp {
 
 impl eframe::App for XerveApp {
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        self.cleanup_services();
+        // Services owned by the daemon outlive the window.
+        if self.services.daemon().is_none() {
+            self.cleanup_services();
+        }
     }
     
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
@@ -283,6 +293,91 @@ impl eframe::App for XerveApp {
                                     .corner_radius(8.0)
                                 );
 
+                                ui.add_space(8.0);
+
+                                let logs_btn = ui.add(
+                                    egui::Button::new(
+                                        egui::RichText::new("Logs")
+                                            .size(16.0)
+                                            .strong()
+                                    )
+                                    .selected(matches!(self.current_tab, AppTab::Logs))
+                                    .fill(if matches!(self.current_tab, AppTab::Logs) { 
+                                        theme::ACCENT 
+                                    } else { 
+                                        theme::BG_CARD 
+                                    })
+                                    .corner_radius(8.0)
+                                );
+
+                                ui.add_space(8.0);
+
+                                let sql_btn = ui.add(
+                                    egui::Button::new(
+                                        egui::RichText::new("SQL")
+                                            .size(16.0)
+                                            .strong()
+                                    )
+                                    .selected(matches!(self.current_tab, AppTab::Sql))
+                                    .fill(if matches!(self.current_tab, AppTab::Sql) { 
+                                        theme::ACCENT 
+                                    } else { 
+                                        theme::BG_CARD 
+                                    })
+                                    .corner_radius(8.0)
+                                );
+
+                                ui.add_space(8.0);
+
+                                let databases_btn = ui.add(
+                                    egui::Button::new(
+                                        egui::RichText::new("Databases")
+                                            .size(16.0)
+                                            .strong()
+                                    )
+                                    .selected(matches!(self.current_tab, AppTab::Databases))
+                                    .fill(if matches!(self.current_tab, AppTab::Databases) { 
+                                        theme::ACCENT 
+                                    } else { 
+                                        theme::BG_CARD 
+                                    })
+                                    .corner_radius(8.0)
+                                );
+
+                                ui.add_space(8.0);
+
+                                let sites_btn = ui.add(
+                                    egui::Button::new(
+                                        egui::RichText::new("Sites")
+                                            .size(16.0)
+                                            .strong()
+                                    )
+                                    .selected(matches!(self.current_tab, AppTab::Sites))
+                                    .fill(if matches!(self.current_tab, AppTab::Sites) { 
+                                        theme::ACCENT 
+                                    } else { 
+                                        theme::BG_CARD 
+                                    })
+                                    .corner_radius(8.0)
+                                );
+
+                                ui.add_space(8.0);
+
+                                let php_btn = ui.add(
+                                    egui::Button::new(
+                                        egui::RichText::new("PHP")
+                                            .size(16.0)
+                                            .strong()
+                                    )
+                                    .selected(matches!(self.current_tab, AppTab::Php))
+                                    .fill(if matches!(self.current_tab, AppTab::Php) { 
+                                        theme::ACCENT 
+                                    } else { 
+                                        theme::BG_CARD 
+                                    })
+                                    .corner_radius(8.0)
+                                );
+
                                 if services_btn.clicked() {
                                     self.current_tab = AppTab::Services;
                                 }
@@ -290,6 +385,26 @@ impl eframe::App for XerveApp {
                                 if monitoring_btn.clicked() {
                                     self.current_tab = AppTab::ResourceMonitoring;
                                 }
+
+                                if logs_btn.clicked() {
+                                    self.current_tab = AppTab::Logs;
+                                }
+
+                                if sql_btn.clicked() {
+                                    self.current_tab = AppTab::Sql;
+                                }
+
+                                if databases_btn.clicked() {
+                                    self.current_tab = AppTab::Databases;
+                                }
+
+                                if sites_btn.clicked() {
+                                    self.current_tab = AppTab::Sites;
+                                }
+
+                                if php_btn.clicked() {
+                                    self.current_tab = AppTab::Php;
+                                }
                             });
                         });
 
@@ -298,10 +413,15 @@ impl eframe::App for XerveApp {
                         match self.current_tab {
                             AppTab::Services => self.render_services_tab(ui),
                             AppTab::ResourceMonitoring => self.render_resource_monitoring_tab(ui),
+                            AppTab::Logs => self.log_history.render(ui, self.services.services()),
+                            AppTab::Sql => self.sql_console.render(ui, self.services.services()),
+                            AppTab::Databases => self.database_panel.render(ui, self.services.services()),
+                            AppTab::Sites => self.sites_panel.render(ui, &self.services),
+                            AppTab::Php => self.php_panel.render(ui, &self.services),
                         };
 
                         ui.add_space(16.0);
-                        self.terminal.render(ui);
+                        self.terminal.render(ui, &mut self.command_prompt);
 
                         ui.add_space(18.0);
                         ui.vertical_centered(|ui| {
@@ -325,13 +445,33 @@ impl XerveApp {
                     egui::RichText::new("Services").size(22.0).strong(),
                 );
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let btn = |text: &str, color: egui::Color32| {
+                        egui::Button::new(
+                            egui::RichText::new(text)
+                                .color(egui::Color32::WHITE)
+                                .size(13.0),
+                        )
+                        .fill(color)
+                        .corner_radius(8.0)
+                    };
+
+                    if ui.add(btn("Stop all", theme::RED)).on_hover_text("Stop every service, dependents first").clicked() {
+                        let registry = self.services.clone();
+                        std::thread::spawn(move || registry.stop_all());
+                    }
+
+                    if ui.add(btn("Start all", theme::GREEN)).on_hover_text("Start every service in dependency order").clicked() {
+                        let registry = self.services.clone();
+                        std::thread::spawn(move || registry.start_all());
+                    }
+
                     theme::subtle_label(ui, "Manage local daemons", 12.0);
                 });
             });
 
             ui.add_space(10.0);
 
-            let mut service_row = crate::ui::ServiceRow::new(ui);
+            let mut service_row = crate::ui::ServiceRow::new(ui, &self.services);
             for service in &self.services {
                 service_row.render(service);
             }
@@ -364,21 +504,22 @@ impl XerveApp {
                 };
 
                 if ui.add(btn("Open htdocs", theme::GREEN)).on_hover_text("Open the web root folder").clicked() {
-                    let htdocs_path = "resource\\nginx\\htdocs";
-                    if std::path::Path::new(htdocs_path).exists() {
-                        match open::that(htdocs_path) {
+                    if self.htdocs.exists() {
+                        match open::that(&self.htdocs) {
                             Ok(_) => self.terminal.add_log("Opening htdocs folder...".to_string()),
                             Err(e) => self.terminal.add_log(format!("Failed to open htdocs folder: {e}")),
                         }
                     } else {
-                        self.terminal.add_log("htdocs folder not found.".to_string());
+                        self.terminal.add_log(format!("htdocs folder {} not found.", self.htdocs.display()));
                     }
                 }
 
-                if ui.add(btn("Open phpMyAdmin", theme::BLUE)).on_hover_text("Open phpMyAdmin in your browser").clicked() {
-                    match open::that("http://localhost/phpmyadmin/") {
-                        Ok(_) => self.terminal.add_log("Opening phpMyAdmin in browser...".to_string()),
-                        Err(e) => self.terminal.add_log(format!("Failed to open phpMyAdmin: {e}")),
+                for site in self.sites_panel.sites() {
+                    if ui.add(btn(&format!("Open {}", site.name), theme::BLUE)).on_hover_text(site.url()).clicked() {
+                        match open::that(site.url()) {
+                            Ok(_) => self.terminal.add_log(format!("Opening {} in browser...", site.url())),
+                            Err(e) => self.terminal.add_log(format!("Failed to open {}: {e}", site.url())),
+                        }
                     }
                 }
             });
@@ -386,6 +527,6 @@ impl XerveApp {
     }
 
     fn render_resource_monitoring_tab(&mut self, ui: &mut egui::Ui) {
-        self.resource_monitoring.render(ui, &self.services);
+        self.resource_monitoring.render(ui, self.services.services());
     }
 }
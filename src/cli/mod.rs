@@ -0,0 +1,516 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::api;
+use crate::config::{ApiConfig, XerveConfig};
+use crate::daemon::{self, DaemonClient, Request, Response};
+use crate::database::backup;
+use crate::logs::{store, LogEntry};
+use crate::services::{self, runfiles, Health, Service, ServiceInfo, ServiceRegistry, ServiceSnapshot};
+use crate::ui::Terminal;
+
+/// Exit code for invalid arguments, such as an unknown service name.
+const EXIT_USAGE: u8 = 2;
+
+#[derive(Parser)]
+#[command(name = "xerve", version, about = "Elegant local development platform")]
+pub struct Cli {
+    /// Path to the service configuration (defaults to ./xerve.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Print service log messages to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the state of every service
+    Status {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Start services and wait until they are ready
+    Start(Selection),
+    /// Stop services
+    Stop(Selection),
+    /// Print a service's output
+    Logs {
+        service: String,
+        /// Keep printing new output as it arrives
+        #[arg(short, long)]
+        follow: bool,
+        /// Number of lines to print from the end of the log
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
+    /// Run the supervisor daemon that owns the services (Linux and macOS)
+    Daemon {
+        /// Start the daemon in the background and return once it is listening
+        #[arg(long)]
+        detach: bool,
+        /// Ask a running daemon to stop its services and exit
+        #[arg(long, conflicts_with = "detach")]
+        stop: bool,
+    },
+    /// Serve the HTTP management API in the foreground until Ctrl+C
+    Serve {
+        /// Port on 127.0.0.1 (defaults to [api].port in xerve.toml)
+        #[arg(long)]
+        port: Option<u16>,
+    },
+}
+
+#[derive(Args)]
+struct Selection {
+    /// Services to act on, by name
+    services: Vec<String>,
+    /// Act on every service, in dependency order
+    #[arg(long, conflicts_with = "services")]
+    all: bool,
+}
+
+impl Cli {
+    /// Whether a headless command was given; without one Xerve opens the GUI.
+    pub fn is_headless(&self) -> bool {
+        self.command.is_some()
+    }
+}
+
+pub fn run(cli: Cli) -> ExitCode {
+    services::echo_logs(cli.verbose);
+
+    let config = match &cli.config {
+        Some(path) => XerveConfig::load_from(path),
+        None => XerveConfig::load(),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for deprecation in config.deprecations() {
+        eprintln!("warning: {deprecation}");
+    }
+    store::configure(&config.logs);
+    let registry = ServiceRegistry::from_config(&config);
+
+    match cli.command {
+        Some(Command::Daemon { detach, stop }) => {
+            return daemon_command(registry, &config, cli.config, detach, stop)
+        }
+        Some(Command::Serve { port }) => return serve(registry, &config.api, port),
+        _ => {}
+    }
+
+    // With a daemon running, it owns the services and does the work.
+    let daemon = DaemonClient::connect().ok();
+    if daemon.is_none() {
+        services::detach_service_output();
+        registry.adopt_running();
+    }
+
+    match cli.command {
+        Some(Command::Status { json }) => status(&registry, daemon.as_ref(), json),
+        Some(Command::Start(selection)) => start(&registry, daemon.as_ref(), &selection),
+        Some(Command::Stop(selection)) => stop(&registry, daemon.as_ref(), &selection),
+        Some(Command::Logs { service, follow, lines }) => logs(&registry, &service, follow, lines),
+        Some(Command::Daemon { .. } | Command::Serve { .. }) | None => ExitCode::SUCCESS,
+    }
+}
+
+fn daemon_command(
+    registry: ServiceRegistry,
+    xerve_config: &XerveConfig,
+    config: Option<PathBuf>,
+    detach: bool,
+    stop: bool,
+) -> ExitCode {
+    if stop {
+        return match DaemonClient::connect().and_then(|client| client.request(&Request::Shutdown)) {
+            Ok(_) => {
+                println!("Daemon is stopping its services and exiting");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if detach {
+        return match spawn_daemon(config) {
+            Ok(()) => {
+                println!("Daemon listening on {}", runfiles::socket_path().display());
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    services::echo_logs(true);
+    let api_config = &xerve_config.api;
+    if api_config.enabled {
+        let terminal = capture_terminal();
+        match api::serve(registry.clone(), terminal, api_config, api_config.port) {
+            Ok(port) => services::log_message(format!("Management API listening on http://127.0.0.1:{port}")),
+            Err(e) => services::log_message(e),
+        }
+    }
+
+    backup::schedule(registry.clone(), xerve_config.backups.clone());
+    match daemon::run(registry) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Collects log messages and state events in a terminal buffer, as the GUI
+/// does, for the API's log stream.
+fn capture_terminal() -> Terminal {
+    let terminal = Terminal::new();
+    services::set_terminal(terminal.clone());
+
+    let events = services::subscribe();
+    let event_terminal = terminal.clone();
+    std::thread::spawn(move || {
+        for event in events {
+            event_terminal.add_entry(LogEntry::event(&event));
+        }
+    });
+    terminal
+}
+
+fn serve(mut registry: ServiceRegistry, api_config: &ApiConfig, port: Option<u16>) -> ExitCode {
+    let terminal = capture_terminal();
+    if let Ok(client) = DaemonClient::connect() {
+        if let Err(e) = client.forward_logs(terminal.clone()) {
+            eprintln!("warning: {e}");
+        }
+        registry.attach_daemon(client);
+    } else {
+        registry.adopt_running();
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_flag = Arc::clone(&running);
+    if let Err(e) = ctrlc::set_handler(move || handler_flag.store(false, Ordering::SeqCst)) {
+        eprintln!("error: failed to install Ctrl+C handler: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    match api::serve(registry.clone(), terminal, api_config, port.unwrap_or(api_config.port)) {
+        Ok(port) => println!("Management API listening on http://127.0.0.1:{port}"),
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    while running.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // Services started through this process stop with it; a daemon's keep running.
+    if registry.daemon().is_none() {
+        registry.stop_all();
+    }
+    ExitCode::SUCCESS
+}
+
+/// Re-runs this executable as `xerve daemon` in its own process group, with
+/// its output going to `logs/xerve-daemon/<date>.log`, and waits for the socket.
+#[cfg(unix)]
+fn spawn_daemon(config: Option<PathBuf>) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    if DaemonClient::connect().is_ok() {
+        return Err(format!("A daemon is already listening on {}", runfiles::socket_path().display()));
+    }
+
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate the xerve executable: {e}"))?;
+    let (log, log_path) = store::open_detached("xerve-daemon")?;
+    let log_err = log.try_clone().map_err(|e| format!("Failed to open the daemon log: {e}"))?;
+
+    let mut command = std::process::Command::new(exe);
+    command.arg("daemon");
+    if let Some(config) = config {
+        command.arg("--config").arg(config);
+    }
+    let daemon = command
+        .stdin(std::process::Stdio::null())
+        .stdout(log)
+        .stderr(log_err)
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Failed to start the daemon: {e}"))?;
+    if let Err(e) = store::hold_detached(&log_path, daemon.id()) {
+        eprintln!("warning: {e}");
+    }
+
+    for _ in 0..50 {
+        if DaemonClient::connect().is_ok() {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Err(format!("The daemon did not start listening; see {}", log_path.display()))
+}
+
+#[cfg(not(unix))]
+fn spawn_daemon(_config: Option<PathBuf>) -> Result<(), String> {
+    Err("The Xerve daemon is only available on Linux and macOS".to_string())
+}
+
+/// Sends `request` to the daemon, turning error responses into `Err`.
+fn ask(daemon: &DaemonClient, request: Request) -> Result<Vec<ServiceSnapshot>, String> {
+    match daemon.request(&request)? {
+        Response::Services { services } => Ok(services),
+        Response::Error { message } => Err(message),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn select<'a>(registry: &'a ServiceRegistry, selection: &Selection) -> Result<Vec<&'a ServiceInfo>, String> {
+    if selection.all {
+        return Ok(registry.start_order().collect());
+    }
+    if selection.services.is_empty() {
+        return Err("name at least one service, or pass --all".to_string());
+    }
+    selection
+        .services
+        .iter()
+        .map(|name| registry.get(name).ok_or_else(|| format!("unknown service '{name}'")))
+        .collect()
+}
+
+fn status(registry: &ServiceRegistry, daemon: Option<&DaemonClient>, json: bool) -> ExitCode {
+    let snapshots = match daemon {
+        Some(daemon) => match ask(daemon, Request::Status) {
+            Ok(snapshots) => snapshots,
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => registry.iter().map(ServiceInfo::snapshot).collect(),
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&snapshots) {
+            Ok(output) => println!("{output}"),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    println!("{:<16} {:<14} {:<8} HEALTH", "NAME", "STATE", "PID");
+    for snapshot in &snapshots {
+        let pid = snapshot.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string());
+        let health = match &snapshot.health {
+            Health::Unknown => "-".to_string(),
+            Health::Healthy => "healthy".to_string(),
+            Health::Unhealthy { last_error, .. } => format!("unhealthy ({last_error})"),
+        };
+        println!("{:<16} {:<14} {:<8} {health}", snapshot.name, snapshot.state.to_string(), pid);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints the state the daemon reported for `name` after acting on it.
+fn report(name: &str, result: Result<Vec<ServiceSnapshot>, String>, action: &str) -> bool {
+    match result {
+        Ok(snapshots) => {
+            if let Some(snapshot) = snapshots.iter().find(|snapshot| snapshot.name == name) {
+                println!("{name}: {}", snapshot.state);
+            }
+            true
+        }
+        Err(e) => {
+            eprintln!("{name}: failed to {action}: {e}");
+            false
+        }
+    }
+}
+
+fn start(registry: &ServiceRegistry, daemon: Option<&DaemonClient>, selection: &Selection) -> ExitCode {
+    if selection.all {
+        let result = match daemon {
+            Some(daemon) => ask(daemon, Request::Start { service: None }).map(|_| ()),
+            None => registry.start_all(),
+        };
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let services = match select(registry, selection) {
+        Ok(services) => services,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let mut failed = false;
+    for service in services {
+        if let Some(daemon) = daemon {
+            let result = ask(daemon, Request::Start { service: Some(service.name.clone()) });
+            failed |= !report(&service.name, result, "start");
+            continue;
+        }
+
+        let result = registry
+            .start_with_dependencies(&service.name)
+            .and_then(|_| service.wait_until_ready(service.startup_timeout() + Duration::from_secs(5)));
+        match result {
+            Ok(()) => println!("{}: {}", service.name, service.state()),
+            Err(e) => {
+                eprintln!("{}: failed to start: {e}", service.name);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn stop(registry: &ServiceRegistry, daemon: Option<&DaemonClient>, selection: &Selection) -> ExitCode {
+    let services = match select(registry, selection) {
+        Ok(services) => services,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    // Dependents go down before the services they depend on.
+    let mut failed = false;
+    for service in services.into_iter().rev() {
+        if let Some(daemon) = daemon {
+            let result = ask(daemon, Request::Stop { service: Some(service.name.clone()) });
+            failed |= !report(&service.name, result, "stop");
+            continue;
+        }
+
+        if !service.state().is_active() {
+            println!("{}: already stopped", service.name);
+            continue;
+        }
+        service.stop();
+        let state = service.state();
+        if state.is_active() || matches!(state, services::ServiceState::Error(_)) {
+            eprintln!("{}: failed to stop: {state}", service.name);
+            failed = true;
+        } else {
+            println!("{}: {state}", service.name);
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn logs(registry: &ServiceRegistry, name: &str, follow: bool, lines: usize) -> ExitCode {
+    let Some(service) = registry.get(name) else {
+        eprintln!("error: unknown service '{name}'");
+        return ExitCode::from(EXIT_USAGE);
+    };
+
+    let Some(mut path) = store::current_file(&service.name) else {
+        eprintln!("error: no log for {} in {}", service.name, store::service_dir(&service.name).display());
+        return ExitCode::FAILURE;
+    };
+    let mut file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("error: no log for {} at {}: {e}", service.name, path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Services may write bytes that aren't UTF-8, such as Windows console output.
+    let mut bytes = Vec::new();
+    if let Err(e) = file.read_to_end(&mut bytes) {
+        eprintln!("error: failed to read {}: {e}", path.display());
+        return ExitCode::FAILURE;
+    }
+    let contents = String::from_utf8_lossy(&bytes);
+    let all_lines: Vec<&str> = contents.lines().collect();
+    for line in &all_lines[all_lines.len().saturating_sub(lines)..] {
+        println!("{line}");
+    }
+
+    if !follow {
+        return ExitCode::SUCCESS;
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_flag = Arc::clone(&running);
+    if let Err(e) = ctrlc::set_handler(move || handler_flag.store(false, Ordering::SeqCst)) {
+        eprintln!("error: failed to install Ctrl+C handler: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut position = bytes.len() as u64;
+    let mut stdout = std::io::stdout();
+    let mut buffer = Vec::new();
+    while running.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(250));
+
+        // Start over if the file was truncated or rotated.
+        let latest = store::current_file(&service.name).unwrap_or_else(|| path.clone());
+        let length = std::fs::metadata(&latest).map(|m| m.len()).unwrap_or(0);
+        if latest != path || length < position {
+            path = latest;
+            position = 0;
+            match std::fs::File::open(&path) {
+                Ok(reopened) => file = reopened,
+                Err(_) => continue,
+            }
+        }
+
+        buffer.clear();
+        if file.seek(SeekFrom::Start(position)).is_ok() && file.read_to_end(&mut buffer).is_ok() {
+            position += buffer.len() as u64;
+            let _ = stdout.write_all(&buffer);
+            let _ = stdout.flush();
+        }
+    }
+
+    ExitCode::SUCCESS
+}
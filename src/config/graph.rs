@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use super::ServiceConfig;
+
+/// Orders services so that every service comes after the services it
+/// `depends_on`, keeping declaration order where there is no constraint.
+/// Returns indices into `services`.
+pub fn start_order(services: &[ServiceConfig]) -> Result<Vec<usize>, String> {
+    let index: HashMap<String, usize> = services
+        .iter()
+        .enumerate()
+        .map(|(i, service)| (service.name.to_lowercase(), i))
+        .collect();
+
+    let mut dependencies = Vec::with_capacity(services.len());
+    for service in services {
+        let mut resolved = Vec::new();
+        for dependency in &service.depends_on {
+            match index.get(&dependency.to_lowercase()) {
+                Some(&i) => resolved.push(i),
+                None => {
+                    return Err(format!(
+                        "Service '{}' depends on unknown service '{dependency}'",
+                        service.name
+                    ))
+                }
+            }
+        }
+        dependencies.push(resolved);
+    }
+
+    let mut order = Vec::with_capacity(services.len());
+    let mut marks = vec![Mark::New; services.len()];
+    let mut path = Vec::new();
+    for i in 0..services.len() {
+        visit(i, services, &dependencies, &mut marks, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    New,
+    InProgress,
+    Done,
+}
+
+fn visit(
+    i: usize,
+    services: &[ServiceConfig],
+    dependencies: &[Vec<usize>],
+    marks: &mut [Mark],
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), String> {
+    match marks[i] {
+        Mark::Done => return Ok(()),
+        Mark::InProgress => {
+            let start = path.iter().position(|&p| p == i).unwrap_or(0);
+            let cycle: Vec<&str> = path[start..]
+                .iter()
+                .chain(std::iter::once(&i))
+                .map(|&p| services[p].name.as_str())
+                .collect();
+            return Err(format!("Dependency cycle between services: {}", cycle.join(" -> ")));
+        }
+        Mark::New => {}
+    }
+
+    marks[i] = Mark::InProgress;
+    path.push(i);
+    for &dependency in &dependencies[i] {
+        visit(dependency, services, dependencies, marks, path, order)?;
+    }
+    path.pop();
+    marks[i] = Mark::Done;
+    order.push(i);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::XerveConfig;
+
+    fn names(config: &str) -> Result<Vec<String>, String> {
+        let config: XerveConfig = toml::from_str(config).unwrap();
+        let order = super::start_order(&config.services)?;
+        Ok(order.into_iter().map(|i| config.services[i].name.clone()).collect())
+    }
+
+    #[test]
+    fn test_dependencies_start_first() {
+        let order = names(
+            r#"
+            [[services]]
+            name = "Nginx"
+            executable = "nginx"
+            depends_on = ["PHP", "MariaDB"]
+
+            [[services]]
+            name = "MariaDB"
+            executable = "mariadbd"
+
+            [[services]]
+            name = "PHP"
+            executable = "php-cgi"
+            depends_on = ["mariadb"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(order, vec!["MariaDB", "PHP", "Nginx"]);
+    }
+
+    #[test]
+    fn test_cycle_rejected() {
+        let error = names(
+            r#"
+            [[services]]
+            name = "A"
+            executable = "a"
+            depends_on = ["B"]
+
+            [[services]]
+            name = "B"
+            executable = "b"
+            depends_on = ["A"]
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(error, "Dependency cycle between services: A -> B -> A");
+    }
+
+    #[test]
+    fn test_unknown_dependency_rejected() {
+        let error = names(
+            r#"
+            [[services]]
+            name = "A"
+            executable = "a"
+            depends_on = ["Redis"]
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.contains("unknown service 'Redis'"));
+    }
+}
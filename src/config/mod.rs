@@ -0,0 +1,619 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+mod graph;
+
+pub use graph::start_order;
+
+pub const CONFIG_FILE: &str = "xerve.toml";
+
+const DEFAULT_CONFIG: &str = include_str!("../../xerve.toml");
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct XerveConfig {
+    #[serde(default)]
+    pub services: Vec<ServiceConfig>,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub logs: LogsConfig,
+    #[serde(default)]
+    pub backups: BackupConfig,
+    #[serde(default)]
+    pub sites: SitesConfig,
+    #[serde(default)]
+    pub php: PhpConfig,
+}
+
+/// The opt-in HTTP management API, served on 127.0.0.1 only.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Bearer token clients must send. When unset, a random token is
+    /// generated and written to `run/api-token`.
+    pub token: Option<String>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            enabled: false,
+            port: 7878,
+            token: None,
+        }
+    }
+}
+
+/// Service output kept under `logs/<service>/`. A file is rotated once it
+/// grows past `max_size_mb`; rotated files are gzipped when `compress` is set
+/// and deleted after `max_age_days`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogsConfig {
+    pub max_size_mb: u64,
+    pub max_age_days: u64,
+    pub compress: bool,
+    /// Entries kept in the terminal panel.
+    pub terminal_lines: usize,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        LogsConfig {
+            max_size_mb: 10,
+            max_age_days: 14,
+            compress: true,
+            terminal_lines: 1000,
+        }
+    }
+}
+
+/// Database dumps kept under `dir/<service>/`, newest `keep` per service.
+/// With `nightly_at` set, every running database service is dumped at that
+/// time of day while Xerve is running.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub dir: String,
+    pub keep: usize,
+    /// `HH:MM`, local time.
+    pub nightly_at: Option<String>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            dir: "backups".to_string(),
+            keep: 7,
+            nightly_at: None,
+        }
+    }
+}
+
+impl BackupConfig {
+    pub fn nightly_at(&self) -> Option<chrono::NaiveTime> {
+        self.nightly_at.as_deref().and_then(crate::logs::parse_time_of_day)
+    }
+}
+
+/// Where the Sites tab writes Nginx server blocks: `conf_dir/sites/*.conf`,
+/// included from `conf_dir/nginx.conf`. `service` is reloaded after changes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SitesConfig {
+    pub service: String,
+    pub conf_dir: String,
+    /// Document roots of new sites default to `htdocs/<site>`.
+    pub htdocs: String,
+    /// The development CA and the certificates of HTTPS sites.
+    pub certs_dir: String,
+    /// Certificates expiring within this many days are flagged.
+    pub expiry_warning_days: u32,
+    /// Deprecated: PHP sites use the backends under `[php]`. Kept so that
+    /// older configs still load, with a warning.
+    pub php_fastcgi: Option<String>,
+}
+
+impl Default for SitesConfig {
+    fn default() -> Self {
+        SitesConfig {
+            service: "Nginx".to_string(),
+            conf_dir: "./resource/nginx/conf".to_string(),
+            htdocs: "./resource/nginx/htdocs".to_string(),
+            certs_dir: "./certs".to_string(),
+            expiry_warning_days: 30,
+            php_fastcgi: None,
+        }
+    }
+}
+
+/// PHP installs are the `php-*` directories under `dir`, e.g.
+/// `php-8.2.29`. Each version runs its own FastCGI backend: the default
+/// version on `base_port`, the others on `base_port + 10 * major + minor`,
+/// so PHP 8.2 listens on 9082. Versions such as 8.10 and 9.0 land on the same
+/// port; such a config is rejected when it is loaded.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PhpConfig {
+    pub dir: String,
+    pub host: String,
+    pub base_port: u16,
+    /// Version put on PATH and used by sites that don't pick one, such as
+    /// `8.4`. Defaults to the newest install.
+    pub default_version: Option<String>,
+    /// Worker processes per version (`PHP_FCGI_CHILDREN`, or
+    /// `pm.max_children` for PHP-FPM). Unix only for php-cgi: Windows builds
+    /// ignore `PHP_FCGI_CHILDREN`.
+    pub workers: u32,
+    /// Requests a worker serves before it is replaced
+    /// (`PHP_FCGI_MAX_REQUESTS`, or `pm.max_requests`).
+    pub max_requests: u32,
+}
+
+impl Default for PhpConfig {
+    fn default() -> Self {
+        PhpConfig {
+            dir: "./resource".to_string(),
+            host: "127.0.0.1".to_string(),
+            base_port: 9000,
+            default_version: None,
+            workers: 4,
+            max_requests: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceConfig {
+    pub name: String,
+    pub executable: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub pid_file: Option<String>,
+    #[serde(default)]
+    pub process_names: Vec<String>,
+    pub init: Option<InitCommand>,
+    pub stop: Option<CommandSpec>,
+    pub reload: Option<CommandSpec>,
+    /// Configuration test, such as `nginx -t`, run before a reload. A
+    /// failing test skips the reload.
+    pub check: Option<CommandSpec>,
+    #[serde(default)]
+    pub readiness: Readiness,
+    #[serde(default)]
+    pub health: HealthConfig,
+    /// Seconds to wait after SIGTERM before a process is killed.
+    #[serde(default = "default_stop_timeout")]
+    pub stop_timeout_secs: u64,
+    #[serde(default)]
+    pub restart: RestartConfig,
+    /// Services that must be ready before this one starts.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    pub database: Option<DatabaseConfig>,
+    /// Files written before every start, such as a generated PHP-FPM pool.
+    #[serde(skip)]
+    pub files: Vec<(PathBuf, String)>,
+}
+
+/// How the SQL console connects to a MariaDB/MySQL service.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DatabaseConfig {
+    #[serde(default = "default_probe_host")]
+    pub host: String,
+    /// Defaults to the port of a `mysql` readiness probe, else 3306.
+    pub port: Option<u16>,
+    #[serde(default = "default_database_user")]
+    pub user: String,
+    #[serde(default)]
+    pub password: String,
+    /// Schema selected after connecting.
+    pub schema: Option<String>,
+}
+
+fn default_database_user() -> String {
+    "root".to_string()
+}
+
+impl ServiceConfig {
+    /// Connection settings for the SQL console: the `database` table with its
+    /// port filled in, or defaults for services with a `mysql` probe.
+    pub fn database(&self) -> Option<DatabaseConfig> {
+        let probe = match &self.readiness {
+            Readiness::Mysql { host, port } => Some((host.clone(), *port)),
+            _ => None,
+        };
+
+        let mut database = match (&self.database, &probe) {
+            (Some(database), _) => database.clone(),
+            (None, Some((host, _))) => DatabaseConfig {
+                host: host.clone(),
+                port: None,
+                user: default_database_user(),
+                password: String::new(),
+                schema: None,
+            },
+            (None, None) => return None,
+        };
+        if database.port.is_none() {
+            database.port = Some(probe.map(|(_, port)| port).unwrap_or(3306));
+        }
+        Some(database)
+    }
+}
+
+pub(crate) fn default_stop_timeout() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+    /// For backends that exit cleanly by design after serving a number of
+    /// requests: clean exits restart at once without counting towards
+    /// `max_restarts`, failures are handled like `on-failure`.
+    Recycle,
+}
+
+/// What to do when a service exits without being asked to. Restarts back off
+/// exponentially from `backoff_ms` up to `max_backoff_ms`; more than
+/// `max_restarts` within `window_secs` puts the service into a crash loop.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RestartConfig {
+    pub policy: RestartPolicy,
+    pub max_restarts: usize,
+    pub window_secs: u64,
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        RestartConfig {
+            policy: RestartPolicy::Never,
+            max_restarts: 5,
+            window_secs: 60,
+            backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandSpec {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// One-off command that prepares a service before its first start, e.g.
+/// `mariadb-install-db`. It only runs while `creates` does not exist yet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InitCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub creates: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Readiness {
+    /// The service counts as running as soon as the process is spawned.
+    #[default]
+    Spawned,
+    /// Wait a fixed amount of time after spawning.
+    Delay { ms: u64 },
+    /// A TCP connection to the port succeeds.
+    Tcp {
+        #[serde(default = "default_probe_host")]
+        host: String,
+        port: u16,
+    },
+    /// An HTTP GET returns the expected status code.
+    Http {
+        url: String,
+        #[serde(default = "default_expect_status")]
+        expect_status: u16,
+    },
+    /// The server sends a MariaDB/MySQL protocol handshake.
+    Mysql {
+        #[serde(default = "default_probe_host")]
+        host: String,
+        port: u16,
+    },
+}
+
+fn default_probe_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_expect_status() -> u16 {
+    200
+}
+
+/// How long a service may take to pass its readiness probe, and how the probe
+/// keeps checking it afterwards.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    pub startup_timeout_secs: u64,
+    pub interval_secs: u64,
+    pub failure_threshold: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            startup_timeout_secs: 30,
+            interval_secs: 10,
+            failure_threshold: 3,
+        }
+    }
+}
+
+impl XerveConfig {
+    /// Loads `xerve.toml` from the working directory, falling back to the
+    /// bundled defaults when the file does not exist.
+    pub fn load() -> Result<Self, String> {
+        let path = Path::new(CONFIG_FILE);
+        if !path.exists() {
+            return Self::parse_installed(DEFAULT_CONFIG);
+        }
+        Self::load_from(path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        Self::parse_installed(&contents).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        Self::parse_with(contents, |_| Ok(()))
+    }
+
+    /// Parses `contents` and adds a service for every PHP install under the
+    /// configured directory, which takes a scan of that directory.
+    pub fn parse_installed(contents: &str) -> Result<Self, String> {
+        Self::parse_with(contents, |config| crate::php::PhpVersions::discover(&config.php).add_services(config))
+    }
+
+    /// Validation waits for `extend`, as declared services may depend on the
+    /// services it adds.
+    fn parse_with(
+        contents: &str,
+        extend: impl FnOnce(&mut XerveConfig) -> Result<(), String>,
+    ) -> Result<Self, String> {
+        let mut config: XerveConfig =
+            toml::from_str(contents).map_err(|e| format!("Invalid configuration: {e}"))?;
+        extend(&mut config)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Settings that are still read but have no effect, here or on this
+    /// platform.
+    pub fn deprecations(&self) -> Vec<String> {
+        let mut deprecations = Vec::new();
+        if let Some(address) = &self.sites.php_fastcgi {
+            deprecations.push(format!(
+                "sites.php_fastcgi = \"{address}\" is ignored; PHP sites use the default PHP version on [php] host and base_port ({}:{})",
+                self.php.host, self.php.base_port
+            ));
+        }
+        let runs_php_cgi = self
+            .services
+            .iter()
+            .any(|service| service.env.contains_key("PHP_FCGI_CHILDREN"));
+        if cfg!(windows) && self.php.workers > 1 && runs_php_cgi {
+            deprecations.push(format!(
+                "php-cgi on Windows serves from one process; workers = {} only applies to PHP-FPM and Unix builds",
+                self.php.workers
+            ));
+        }
+        deprecations
+    }
+
+    pub fn default_config() -> Self {
+        Self::parse_installed(DEFAULT_CONFIG).unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut seen = std::collections::HashSet::new();
+        for service in &self.services {
+            if service.name.trim().is_empty() {
+                return Err("Service name must not be empty".to_string());
+            }
+            if !seen.insert(service.name.to_lowercase()) {
+                return Err(format!("Service '{}' is defined more than once", service.name));
+            }
+        }
+        start_order(&self.services)?;
+        if let Some(time) = &self.backups.nightly_at {
+            if self.backups.nightly_at().is_none() {
+                return Err(format!("backups.nightly_at must look like 02:30, not '{time}'"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ServiceConfig {
+    pub fn working_dir(&self) -> Option<PathBuf> {
+        self.working_dir.as_ref().map(PathBuf::from)
+    }
+
+    /// Process names used to find this service's processes, defaulting to the
+    /// executable's file stem.
+    pub fn process_names(&self) -> Vec<String> {
+        if !self.process_names.is_empty() {
+            return self.process_names.clone();
+        }
+        Path::new(&self.executable)
+            .file_stem()
+            .map(|stem| vec![stem.to_string_lossy().to_string()])
+            .unwrap_or_default()
+    }
+
+    /// Whether Xerve signs in to this database service as `user`, either
+    /// through the `database` table or the stop command's `-u`/`--user`.
+    pub fn signs_in_as(&self, user: &str) -> bool {
+        self.database().is_some_and(|database| database.user == user) || self.stops_as(user)
+    }
+
+    /// Whether the stop command signs in as `user` with `-u`/`--user`.
+    pub fn stops_as(&self, user: &str) -> bool {
+        let args = self.stop.as_ref().map(|stop| stop.args.as_slice()).unwrap_or_default();
+        args.iter().enumerate().any(|(index, arg)| {
+            let value = match arg.as_str() {
+                "-u" | "--user" => args.get(index + 1).map(String::as_str),
+                arg => arg.strip_prefix("--user=").or_else(|| arg.strip_prefix("-u")),
+            };
+            value == Some(user)
+        })
+    }
+
+    /// The option file passed as `--defaults-file`, resolved against the
+    /// working directory like the server itself does.
+    pub fn defaults_file(&self) -> Option<PathBuf> {
+        let file = self.args.iter().find_map(|arg| arg.strip_prefix("--defaults-file="))?;
+        Some(match self.working_dir() {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        })
+    }
+}
+
+/// Resolves a configured path against Xerve's working directory so that it
+/// stays valid when a command runs with a different `current_dir`.
+pub fn absolute_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    match std::env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_parses() {
+        let config = XerveConfig::parse(DEFAULT_CONFIG).unwrap();
+        let names: Vec<&str> = config.services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Nginx", "MariaDB"]);
+        assert_eq!(
+            config.services[1].readiness,
+            Readiness::Mysql { host: "127.0.0.1".to_string(), port: 3306 }
+        );
+        assert_eq!(config.services[1].restart.policy, RestartPolicy::OnFailure);
+        assert_eq!(config.services[1].restart.max_restarts, 5);
+    }
+
+    #[test]
+    fn test_custom_service() {
+        let config = XerveConfig::parse(
+            r#"
+            [[services]]
+            name = "Redis"
+            executable = "./resource/redis/redis-server"
+            args = ["--port", "6379"]
+            env = { REDIS_LOG = "verbose" }
+            "#,
+        )
+        .unwrap();
+        let redis = &config.services[0];
+        assert_eq!(redis.args, vec!["--port", "6379"]);
+        assert_eq!(redis.env.get("REDIS_LOG").map(String::as_str), Some("verbose"));
+        assert_eq!(redis.process_names(), vec!["redis-server"]);
+        assert_eq!(redis.readiness, Readiness::Spawned);
+        assert_eq!(redis.health.failure_threshold, 3);
+    }
+
+    #[test]
+    fn test_database_settings() {
+        let config = XerveConfig::parse(
+            r#"
+            [[services]]
+            name = "MariaDB"
+            executable = "mariadbd"
+            args = ["--defaults-file=my.ini"]
+            working_dir = "./resource/mariadb"
+            readiness = { kind = "mysql", port = 3307 }
+
+            [[services]]
+            name = "MySQL"
+            executable = "mysqld"
+            database = { user = "app", password = "secret", schema = "shop" }
+            stop = { program = "mysqladmin", args = ["--user=root", "shutdown"] }
+
+            [[services]]
+            name = "Nginx"
+            executable = "nginx"
+            "#,
+        )
+        .unwrap();
+
+        let mariadb = config.services[0].database().unwrap();
+        assert_eq!((mariadb.port, mariadb.user.as_str(), mariadb.password.as_str()), (Some(3307), "root", ""));
+        let mysql = config.services[1].database().unwrap();
+        assert_eq!((mysql.port, mysql.user.as_str(), mysql.schema.as_deref()), (Some(3306), "app", Some("shop")));
+        assert_eq!(config.services[2].database(), None);
+        assert_eq!(config.services[0].defaults_file(), Some(PathBuf::from("./resource/mariadb/my.ini")));
+        assert_eq!(config.services[1].defaults_file(), None);
+        assert!(config.services[0].signs_in_as("root"));
+        assert!(config.services[1].signs_in_as("root") && config.services[1].signs_in_as("app"));
+        assert!(!config.services[1].signs_in_as("admin"));
+        assert!(config.services[1].stops_as("root") && !config.services[1].stops_as("app"));
+    }
+
+    #[test]
+    fn test_backup_schedule() {
+        let config = XerveConfig::parse("[backups]\nkeep = 3\nnightly_at = \"02:30\"\n").unwrap();
+        assert_eq!((config.backups.dir.as_str(), config.backups.keep), ("backups", 3));
+        assert_eq!(config.backups.nightly_at(), chrono::NaiveTime::from_hms_opt(2, 30, 0));
+        assert!(XerveConfig::parse("[backups]\nnightly_at = \"late\"\n").is_err());
+    }
+
+    #[test]
+    fn test_php_fastcgi_is_deprecated() {
+        let config = XerveConfig::parse("[sites]\nphp_fastcgi = \"127.0.0.1:9001\"\n").unwrap();
+        assert_eq!(
+            config.deprecations(),
+            ["sites.php_fastcgi = \"127.0.0.1:9001\" is ignored; PHP sites use the default PHP version on [php] host and base_port (127.0.0.1:9000)"]
+        );
+        assert!(XerveConfig::parse(DEFAULT_CONFIG).unwrap().deprecations().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_service_rejected() {
+        let result = XerveConfig::parse(
+            r#"
+            [[services]]
+            name = "Nginx"
+            executable = "nginx"
+
+            [[services]]
+            name = "nginx"
+            executable = "nginx"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Duration;
+
+use super::{Request, Response};
+#[cfg(unix)]
+use crate::logs::LogEntry;
+use crate::services::runfiles;
+use crate::ui::Terminal;
+
+/// How long to wait for quick answers such as `status`. Starting and stopping
+/// wait for the services themselves and have no limit.
+#[cfg(unix)]
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Talks to a running daemon. Each request uses its own connection.
+#[derive(Clone)]
+pub struct DaemonClient {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl DaemonClient {
+    /// Connects to the daemon serving the current directory, if one is running.
+    pub fn connect() -> Result<Self, String> {
+        let client = DaemonClient { path: runfiles::socket_path() };
+        client.open()?;
+        Ok(client)
+    }
+
+    fn open(&self) -> Result<UnixStream, String> {
+        UnixStream::connect(&self.path)
+            .map_err(|e| format!("Failed to connect to the daemon at {}: {e}", self.path.display()))
+    }
+
+    fn send(&self, request: &Request) -> Result<BufReader<UnixStream>, String> {
+        let mut stream = self.open()?;
+        let mut line = serde_json::to_string(request).map_err(|e| format!("Failed to encode request: {e}"))?;
+        line.push('\n');
+        stream
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to send request to the daemon: {e}"))?;
+        Ok(BufReader::new(stream))
+    }
+
+    pub fn request(&self, request: &Request) -> Result<Response, String> {
+        let mut reader = self.send(request)?;
+        if matches!(request, Request::Status | Request::Metrics) {
+            reader.get_ref().set_read_timeout(Some(QUERY_TIMEOUT)).ok();
+        }
+
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => Err("The daemon closed the connection without answering".to_string()),
+            Ok(_) => serde_json::from_str(&line).map_err(|e| format!("Invalid response from the daemon: {e}")),
+            Err(e) => Err(format!("Failed to read the daemon's response: {e}")),
+        }
+    }
+
+    /// Streams the daemon's log messages until it goes away.
+    fn subscribe_logs(&self) -> Result<impl Iterator<Item = LogEntry>, String> {
+        let reader = self.send(&Request::Logs)?;
+        Ok(reader
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| match serde_json::from_str(&line) {
+                Ok(Response::Log { entry }) => Some(entry),
+                _ => None,
+            }))
+    }
+
+    /// Copies the daemon's log messages into `terminal` on a background thread.
+    pub fn forward_logs(&self, terminal: Terminal) -> Result<(), String> {
+        let entries = self.subscribe_logs()?;
+        std::thread::spawn(move || entries.for_each(|entry| terminal.add_entry(entry)));
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+impl DaemonClient {
+    pub fn connect() -> Result<Self, String> {
+        Err(format!(
+            "The Xerve daemon is only available on Linux and macOS (no socket at {})",
+            runfiles::socket_path().display()
+        ))
+    }
+
+    pub fn request(&self, _request: &Request) -> Result<Response, String> {
+        Err(format!("No daemon at {}", self.path.display()))
+    }
+
+    pub fn forward_logs(&self, _terminal: Terminal) -> Result<(), String> {
+        Err(format!("No daemon at {}", self.path.display()))
+    }
+}
@@ -0,0 +1,102 @@
+//! The optional supervisor daemon. It owns the service processes and serves
+//! line-delimited JSON over a Unix domain socket, so the GUI and the CLI can
+//! both attach to the same services and closing the window leaves them
+//! running.
+//!
+//! Every request is one JSON object on its own line, tagged by `cmd`; the
+//! daemon answers with one JSON object per line, tagged by `type`. A `logs`
+//! request keeps the connection open and streams `log` responses, each
+//! carrying a structured log entry.
+
+use serde::{Deserialize, Serialize};
+
+use crate::logs::LogEntry;
+use crate::services::{ServiceMetrics, ServiceSnapshot};
+
+mod client;
+#[cfg(unix)]
+mod server;
+
+pub use client::DaemonClient;
+#[cfg(unix)]
+pub use server::run;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    /// Starts one service with its dependencies, or every service when
+    /// `service` is omitted, and waits until they are ready.
+    Start {
+        #[serde(default)]
+        service: Option<String>,
+    },
+    /// Stops one service, or every service when `service` is omitted.
+    Stop {
+        #[serde(default)]
+        service: Option<String>,
+    },
+    Reload { service: String },
+    /// Streams log messages until the client disconnects.
+    Logs,
+    Metrics,
+    /// Stops every service and exits the daemon.
+    Shutdown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Services { services: Vec<ServiceSnapshot> },
+    Metrics { metrics: Vec<ServiceMetrics> },
+    Log { entry: LogEntry },
+    Error { message: String },
+}
+
+#[cfg(not(unix))]
+pub fn run(_registry: crate::services::ServiceRegistry) -> Result<(), String> {
+    Err("The Xerve daemon is only available on Linux and macOS".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{ConfigError, Health, ServiceState};
+
+    #[test]
+    fn test_request_wire_format() {
+        let request: Request = serde_json::from_str(r#"{"cmd":"start","service":"Nginx"}"#).unwrap();
+        assert_eq!(request, Request::Start { service: Some("Nginx".to_string()) });
+
+        let request: Request = serde_json::from_str(r#"{"cmd":"stop"}"#).unwrap();
+        assert_eq!(request, Request::Stop { service: None });
+
+        assert_eq!(serde_json::to_string(&Request::Status).unwrap(), r#"{"cmd":"status"}"#);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let response = Response::Services {
+            services: vec![ServiceSnapshot {
+                name: "MariaDB".to_string(),
+                state: ServiceState::Error("port in use".to_string()),
+                pid: Some(42),
+                health: Health::Unknown,
+                depends_on: Vec::new(),
+                last_exit: None,
+                stderr_tail: vec!["bind failed".to_string()],
+                restarts_in_window: 2,
+                config_error: Some(ConfigError {
+                    file: Some("conf/nginx.conf".to_string()),
+                    line: Some(7),
+                    message: "unexpected \"}\"".to_string(),
+                }),
+            }],
+        };
+
+        let line = serde_json::to_string(&response).unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(serde_json::from_str::<Response>(&line).unwrap(), response);
+    }
+}
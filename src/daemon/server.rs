@@ -0,0 +1,188 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Request, Response};
+use crate::services::{self, runfiles, MetricsCollector, Service, ServiceInfo, ServiceRegistry, ServiceState};
+
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+/// Shared by every connection the daemon serves.
+#[derive(Clone)]
+struct Daemon {
+    registry: ServiceRegistry,
+    shutdown: Arc<AtomicBool>,
+    metrics: MetricsCollector,
+}
+
+/// Serves `registry` on the control socket until Ctrl+C, SIGTERM or a
+/// `shutdown` request, then stops every service.
+pub fn run(registry: ServiceRegistry) -> Result<(), String> {
+    let path = runfiles::socket_path();
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!("A Xerve daemon is already listening on {}", path.display()));
+        }
+        // Left behind by a daemon that did not shut down cleanly.
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to remove stale socket {}: {e}", path.display()))?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+    }
+
+    let listener = UnixListener::bind(&path).map_err(|e| format!("Failed to bind {}: {e}", path.display()))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to configure {}: {e}", path.display()))?;
+
+    registry.adopt_running();
+    let daemon = Daemon {
+        registry,
+        shutdown: Arc::new(AtomicBool::new(false)),
+        metrics: MetricsCollector::start(),
+    };
+
+    let shutdown = Arc::clone(&daemon.shutdown);
+    ctrlc::set_handler(move || shutdown.store(true, Ordering::SeqCst))
+        .map_err(|e| format!("Failed to install signal handler: {e}"))?;
+
+    let events = services::subscribe();
+    std::thread::spawn(move || {
+        for event in events {
+            eprintln!("{event}");
+        }
+    });
+
+    services::log_message(format!("Xerve daemon listening on {}", path.display()));
+    while !daemon.shutdown.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let daemon = daemon.clone();
+                std::thread::spawn(move || daemon.serve(stream));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(ACCEPT_POLL),
+            Err(e) => services::log_message(format!("Failed to accept daemon connection: {e}")),
+        }
+    }
+
+    services::log_message("Xerve daemon shutting down...".to_string());
+    daemon.registry.stop_all();
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+impl Daemon {
+    fn serve(&self, stream: UnixStream) {
+        if stream.set_nonblocking(false).is_err() {
+            return;
+        }
+        let Ok(reader) = stream.try_clone() else {
+            return;
+        };
+        let mut writer = stream;
+
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Logs) => {
+                    stream_logs(&mut writer);
+                    return;
+                }
+                Ok(request) => self.handle(request),
+                Err(e) => Response::Error { message: format!("Invalid request: {e}") },
+            };
+            if send(&mut writer, &response).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::Status => self.services(),
+            Request::Start { service: None } => match self.registry.start_all() {
+                Ok(()) => self.services(),
+                Err(message) => Response::Error { message },
+            },
+            Request::Start { service: Some(name) } => {
+                let result = self.find(&name).and_then(|service| {
+                    self.registry
+                        .start_with_dependencies(&service.name)
+                        .and_then(|_| service.wait_until_ready(service.startup_timeout() + Duration::from_secs(5)))
+                });
+                match result {
+                    Ok(()) => self.services(),
+                    Err(message) => Response::Error { message },
+                }
+            }
+            Request::Stop { service: None } => {
+                self.registry.stop_all();
+                self.services()
+            }
+            Request::Stop { service: Some(name) } => match self.find(&name) {
+                Ok(service) => {
+                    service.stop();
+                    match service.state() {
+                        ServiceState::Error(reason) => Response::Error {
+                            message: format!("Failed to stop {}: {reason}", service.name),
+                        },
+                        _ => self.services(),
+                    }
+                }
+                Err(message) => Response::Error { message },
+            },
+            Request::Reload { service } => match self.find(&service) {
+                Ok(service) => {
+                    service.reload();
+                    Response::Ok
+                }
+                Err(message) => Response::Error { message },
+            },
+            Request::Metrics => self.metrics(),
+            Request::Shutdown => {
+                self.shutdown.store(true, Ordering::SeqCst);
+                Response::Ok
+            }
+            Request::Logs => Response::Error { message: "Logs are streamed, not answered".to_string() },
+        }
+    }
+
+    fn find(&self, name: &str) -> Result<&ServiceInfo, String> {
+        self.registry.get(name).ok_or_else(|| format!("Unknown service '{name}'"))
+    }
+
+    fn services(&self) -> Response {
+        Response::Services {
+            services: self.registry.iter().map(ServiceInfo::snapshot).collect(),
+        }
+    }
+
+    fn metrics(&self) -> Response {
+        match self.metrics.collect(&self.registry) {
+            Ok(metrics) => Response::Metrics { metrics },
+            Err(message) => Response::Error { message },
+        }
+    }
+}
+
+fn send(writer: &mut UnixStream, response: &Response) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(response)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())
+}
+
+fn stream_logs(writer: &mut UnixStream) {
+    for entry in services::subscribe_logs() {
+        if send(writer, &Response::Log { entry }).is_err() {
+            return;
+        }
+    }
+}
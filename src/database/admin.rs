@@ -0,0 +1,357 @@
+//! Schema and account management for the Databases tab. Statements are built
+//! here with names quoted as identifiers and secrets as string literals, so
+//! that nothing typed into the UI is spliced into SQL unescaped.
+
+use mysql::prelude::Queryable;
+use mysql::{from_row_opt, Conn, Row};
+
+/// Schemas the server needs for itself; they can't be dropped from the UI.
+pub const SYSTEM_SCHEMAS: [&str; 4] = ["information_schema", "mysql", "performance_schema", "sys"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub name: String,
+    /// Data and index size of its tables in bytes.
+    pub size: u64,
+    pub tables: u64,
+}
+
+impl Schema {
+    pub fn is_system(&self) -> bool {
+        SYSTEM_SCHEMAS.contains(&self.name.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub user: String,
+    pub host: String,
+}
+
+impl Account {
+    /// `'user'@'host'`, as SQL expects it.
+    pub fn sql(&self) -> String {
+        format!("{}@{}", quote_string(&self.user), quote_string(&self.host))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collation {
+    pub charset: String,
+    pub name: String,
+    /// Whether this is the charset's default collation.
+    pub default: bool,
+}
+
+/// What the Databases tab shows.
+#[derive(Debug, Clone, Default)]
+pub struct Overview {
+    pub schemas: Vec<Schema>,
+    pub accounts: Vec<Account>,
+    pub collations: Vec<Collation>,
+}
+
+impl Overview {
+    pub fn charsets(&self) -> Vec<&str> {
+        let mut charsets: Vec<&str> = self.collations.iter().map(|collation| collation.charset.as_str()).collect();
+        charsets.dedup();
+        charsets
+    }
+
+    pub fn collations_of<'a>(&'a self, charset: &str) -> impl Iterator<Item = &'a Collation> {
+        let charset = charset.to_string();
+        self.collations.iter().filter(move |collation| collation.charset == charset)
+    }
+
+    pub fn default_collation(&self, charset: &str) -> Option<&str> {
+        self.collations_of(charset)
+            .find(|collation| collation.default)
+            .map(|collation| collation.name.as_str())
+    }
+}
+
+pub fn overview(conn: &mut Conn) -> Result<Overview, String> {
+    let schemas = select(
+        conn,
+        "SELECT s.SCHEMA_NAME, CAST(COALESCE(SUM(t.DATA_LENGTH + t.INDEX_LENGTH), 0) AS UNSIGNED), COUNT(t.TABLE_NAME) \
+         FROM information_schema.SCHEMATA s \
+         LEFT JOIN information_schema.TABLES t ON t.TABLE_SCHEMA = s.SCHEMA_NAME \
+         GROUP BY s.SCHEMA_NAME ORDER BY s.SCHEMA_NAME",
+    )?
+    .into_iter()
+    .map(|(name, size, tables)| Schema { name, size, tables })
+    .collect();
+
+    let accounts = select(conn, "SELECT User, Host FROM mysql.user ORDER BY User, Host")?
+        .into_iter()
+        .map(|(user, host)| Account { user, host })
+        .collect();
+
+    let collations = select(
+        conn,
+        "SELECT CHARACTER_SET_NAME, COLLATION_NAME, IS_DEFAULT FROM information_schema.COLLATIONS \
+         WHERE CHARACTER_SET_NAME IS NOT NULL ORDER BY CHARACTER_SET_NAME, COLLATION_NAME",
+    )?
+    .into_iter()
+    .map(|(charset, name, default): (String, String, String)| Collation {
+        charset,
+        name,
+        default: default.eq_ignore_ascii_case("yes"),
+    })
+    .collect();
+
+    Ok(Overview {
+        schemas,
+        accounts,
+        collations,
+    })
+}
+
+fn select<T: mysql::prelude::FromRow>(conn: &mut Conn, sql: &str) -> Result<Vec<T>, String> {
+    let rows: Vec<Row> = conn.query(sql).map_err(|e| e.to_string())?;
+    rows.into_iter()
+        .map(|row| from_row_opt(row).map_err(|e| format!("Unexpected row from the server: {e}")))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Privileges {
+    All,
+    ReadWrite,
+    ReadOnly,
+}
+
+impl Privileges {
+    pub const ALL: [Privileges; 3] = [Privileges::All, Privileges::ReadWrite, Privileges::ReadOnly];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Privileges::All => "All privileges",
+            Privileges::ReadWrite => "Read and write",
+            Privileges::ReadOnly => "Read only",
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            Privileges::All => "ALL PRIVILEGES",
+            Privileges::ReadWrite => "SELECT, INSERT, UPDATE, DELETE, CREATE TEMPORARY TABLES, LOCK TABLES, EXECUTE",
+            Privileges::ReadOnly => "SELECT, SHOW VIEW",
+        }
+    }
+}
+
+/// A change made from the Databases tab.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    CreateDatabase {
+        name: String,
+        charset: String,
+        collation: String,
+    },
+    DropDatabase(String),
+    CreateUser {
+        account: Account,
+        password: String,
+        privileges: Privileges,
+        /// `None` grants on every database.
+        database: Option<String>,
+    },
+    DropUser(Account),
+    /// Sets the password of every `root` account.
+    ResetRootPassword(String),
+}
+
+impl Action {
+    /// Whether the action throws data or access away and needs confirming.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, Action::DropDatabase(_) | Action::DropUser(_) | Action::ResetRootPassword(_))
+    }
+
+    /// What happened, once the action has succeeded.
+    pub fn done(&self) -> String {
+        match self {
+            Action::CreateDatabase { name, .. } => format!("Created database {name}"),
+            Action::DropDatabase(name) => format!("Dropped database {name}"),
+            Action::CreateUser { account, .. } => format!("Created user {}@{}", account.user, account.host),
+            Action::DropUser(account) => format!("Dropped user {}@{}", account.user, account.host),
+            Action::ResetRootPassword(_) => "Changed the root password".to_string(),
+        }
+    }
+
+    pub fn apply(&self, conn: &mut Conn) -> Result<(), String> {
+        let root_hosts = match self {
+            Action::ResetRootPassword(_) => select::<String>(conn, "SELECT Host FROM mysql.user WHERE User = 'root'")?,
+            _ => Vec::new(),
+        };
+        for (index, statement) in self.statements(&root_hosts)?.iter().enumerate() {
+            if let Err(e) = conn.query_drop(statement) {
+                return Err(match self.rollback().filter(|_| index > 0) {
+                    Some((undo, what)) => match conn.query_drop(&undo) {
+                        Ok(()) => format!("{e}; {what} was removed again"),
+                        Err(undo_error) => format!("{e}; removing {what} failed too: {undo_error}"),
+                    },
+                    None => e.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// A statement undoing the first one when a later one fails, with what
+    /// it removes, so a half-made change isn't left behind.
+    fn rollback(&self) -> Option<(String, String)> {
+        match self {
+            Action::CreateUser { account, .. } => Some((
+                format!("DROP USER {}", account.sql()),
+                format!("user {}@{}", account.user, account.host),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The statements that carry the action out. `root_hosts` are the hosts
+    /// of the `root` accounts, used when resetting their password.
+    pub fn statements(&self, root_hosts: &[String]) -> Result<Vec<String>, String> {
+        let statements = match self {
+            Action::CreateDatabase { name, charset, collation } => {
+                let mut statement = format!("CREATE DATABASE {}", quote_identifier(required("Database name", name)?));
+                if !charset.is_empty() {
+                    statement.push_str(&format!(" CHARACTER SET {}", quote_identifier(charset)));
+                }
+                if !collation.is_empty() {
+                    statement.push_str(&format!(" COLLATE {}", quote_identifier(collation)));
+                }
+                vec![statement]
+            }
+            Action::DropDatabase(name) => {
+                if SYSTEM_SCHEMAS.contains(&name.as_str()) {
+                    return Err(format!("{name} is a system database and can't be dropped"));
+                }
+                vec![format!("DROP DATABASE {}", quote_identifier(required("Database name", name)?))]
+            }
+            Action::CreateUser { account, password, privileges, database } => {
+                required("User name", &account.user)?;
+                required("Host", &account.host)?;
+                let scope = match database {
+                    Some(database) => format!("{}.*", quote_identifier(database)),
+                    None => "*.*".to_string(),
+                };
+                vec![
+                    format!("CREATE USER {} IDENTIFIED BY {}", account.sql(), quote_string(password)),
+                    format!("GRANT {} ON {scope} TO {}", privileges.sql(), account.sql()),
+                ]
+            }
+            Action::DropUser(account) => vec![format!("DROP USER {}", account.sql())],
+            Action::ResetRootPassword(password) => {
+                if root_hosts.is_empty() {
+                    return Err("There is no root account".to_string());
+                }
+                root_hosts
+                    .iter()
+                    .map(|host| {
+                        let account = Account { user: "root".to_string(), host: host.clone() };
+                        format!("ALTER USER {} IDENTIFIED BY {}", account.sql(), quote_string(password))
+                    })
+                    .collect()
+            }
+        };
+        Ok(statements)
+    }
+}
+
+fn required<'a>(what: &str, value: &'a str) -> Result<&'a str, String> {
+    if value.trim().is_empty() {
+        Err(format!("{what} must not be empty"))
+    } else {
+        Ok(value)
+    }
+}
+
+/// `name` as a backquoted identifier.
+pub fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// `value` as a single-quoted string literal.
+pub fn quote_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        match c {
+            '\'' => quoted.push_str("''"),
+            '\\' => quoted.push_str("\\\\"),
+            '\0' => quoted.push_str("\\0"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(user: &str, host: &str) -> Account {
+        Account { user: user.to_string(), host: host.to_string() }
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(quote_identifier("shop`; DROP DATABASE x; --"), "`shop``; DROP DATABASE x; --`");
+        assert_eq!(quote_string(r"it's a \ test"), r"'it''s a \\ test'");
+    }
+
+    #[test]
+    fn test_database_statements() {
+        let create = Action::CreateDatabase {
+            name: "shop".to_string(),
+            charset: "utf8mb4".to_string(),
+            collation: "utf8mb4_unicode_ci".to_string(),
+        };
+        assert_eq!(
+            create.statements(&[]).unwrap(),
+            ["CREATE DATABASE `shop` CHARACTER SET `utf8mb4` COLLATE `utf8mb4_unicode_ci`"]
+        );
+        assert!(!create.is_destructive());
+
+        assert_eq!(Action::DropDatabase("shop".to_string()).statements(&[]).unwrap(), ["DROP DATABASE `shop`"]);
+        assert!(Action::DropDatabase("mysql".to_string()).statements(&[]).is_err());
+        assert!(Action::DropDatabase(" ".to_string()).statements(&[]).is_err());
+    }
+
+    #[test]
+    fn test_user_statements() {
+        let create = Action::CreateUser {
+            account: account("app", "%"),
+            password: "s3cr'et".to_string(),
+            privileges: Privileges::ReadOnly,
+            database: Some("shop".to_string()),
+        };
+        assert_eq!(
+            create.statements(&[]).unwrap(),
+            [
+                "CREATE USER 'app'@'%' IDENTIFIED BY 's3cr''et'",
+                "GRANT SELECT, SHOW VIEW ON `shop`.* TO 'app'@'%'",
+            ]
+        );
+
+        assert_eq!(
+            create.rollback(),
+            Some(("DROP USER 'app'@'%'".to_string(), "user app@%".to_string()))
+        );
+        assert_eq!(Action::DropUser(account("app", "%")).rollback(), None);
+
+        let reset = Action::ResetRootPassword("new".to_string());
+        assert!(reset.is_destructive());
+        assert_eq!(
+            reset.statements(&["localhost".to_string(), "127.0.0.1".to_string()]).unwrap(),
+            [
+                "ALTER USER 'root'@'localhost' IDENTIFIED BY 'new'",
+                "ALTER USER 'root'@'127.0.0.1' IDENTIFIED BY 'new'",
+            ]
+        );
+        assert!(reset.statements(&[]).is_err());
+    }
+}
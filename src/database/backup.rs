@@ -0,0 +1,431 @@
+//! Dumps and restores database services with the `mariadb-dump` and `mariadb`
+//! clients that ship next to the server, falling back to `mysqldump` and
+//! `mysql`. Dumps are gzipped into `<dir>/<service>/` and only the newest
+//! `keep` are kept. Client messages go to the log like service output does.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Local, NaiveTime};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::config::{absolute_path, BackupConfig, DatabaseConfig};
+use crate::logs::LogEntry;
+use crate::services::{self, platform, runfiles, Service, ServiceInfo, ServiceRegistry, ServiceState};
+
+/// Name under which backups and restores appear in the log.
+pub const SOURCE: &str = "Backup";
+
+/// How often a running dump or restore reports its progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// One dump or restore at a time, so a scheduled backup never overlaps a
+/// restore started from the UI.
+static JOBS: Mutex<()> = Mutex::new(());
+
+/// A dump file, finished or copied in by hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupFile {
+    pub path: PathBuf,
+    pub modified: DateTime<Local>,
+    pub size: u64,
+}
+
+impl BackupFile {
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.path.extension().is_some_and(|ext| ext == "gz")
+    }
+}
+
+/// Backups of one database service.
+#[derive(Debug, Clone)]
+pub struct Backups {
+    service: String,
+    settings: DatabaseConfig,
+    /// Directory of the server executable, where the clients are looked for.
+    tool_dir: Option<PathBuf>,
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl Backups {
+    /// Backups of `service`, or `None` when it is not a database service.
+    pub fn for_service(service: &ServiceInfo, config: &BackupConfig) -> Option<Self> {
+        Some(Backups {
+            service: service.name.clone(),
+            settings: service.database()?,
+            tool_dir: service.executable_dir(),
+            dir: absolute_path(&config.dir).join(runfiles::slug(&service.name)),
+            keep: config.keep.max(1),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Dump files, most recent first.
+    pub fn files(&self) -> Vec<BackupFile> {
+        files_in(&self.dir)
+    }
+
+    /// Dumps `schema`, or every database, to a new timestamped file.
+    pub fn create(&self, schema: Option<&str>) -> Result<PathBuf, String> {
+        let _job = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+        let tool = self.tool(&["mariadb-dump", "mysqldump"])?;
+        std::fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create {}: {e}", self.dir.display()))?;
+
+        let path = self.dir.join(file_name(schema, Local::now()));
+        let partial = path.with_extension("gz.partial");
+        let mut command = self.command(&tool);
+        command.args(["--single-transaction", "--routines", "--events", "--triggers"]);
+        match schema {
+            Some(schema) => command.arg("--databases").arg(schema),
+            None => command.arg("--all-databases"),
+        };
+        command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let what = schema.map(|schema| format!("database {schema}")).unwrap_or_else(|| "all databases".to_string());
+        log(format!("Backing up {what} of {} to {}", self.service, path.display()));
+        let started = Instant::now();
+        let mut child = command.spawn().map_err(|e| format!("Failed to run {}: {e}", tool.display()))?;
+        let mut dump = child.stdout.take().ok_or("The dump has no output")?;
+        let readers = services::capture_output(&mut child, SOURCE, None, None);
+
+        let written = File::create(&partial).and_then(|file| {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            let written = copy(&mut dump, &mut encoder, |written| {
+                log(format!("Dumped {} so far", format_bytes(written)));
+            })?;
+            encoder.finish()?.sync_all()?;
+            Ok(written)
+        });
+        let status = child.wait();
+        for reader in readers {
+            let _ = reader.join();
+        }
+
+        let result = match (written, status) {
+            (Ok(written), Ok(status)) if status.success() => std::fs::rename(&partial, &path)
+                .map(|()| written)
+                .map_err(|e| format!("Failed to move the dump to {}: {e}", path.display())),
+            (Err(e), _) => Err(format!("Failed to write {}: {e}", partial.display())),
+            (_, Ok(status)) => Err(format!("{} exited with {status}", tool.display())),
+            (_, Err(e)) => Err(format!("Failed to wait for {}: {e}", tool.display())),
+        };
+        let written = match result {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+
+        let size = std::fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or_default();
+        log(format!(
+            "Backup finished in {:.1}s: {} of SQL, {} compressed",
+            started.elapsed().as_secs_f64(),
+            format_bytes(written),
+            format_bytes(size)
+        ));
+        self.prune();
+        Ok(path)
+    }
+
+    /// Feeds `file` to the server, replacing whatever it recreates.
+    pub fn restore(&self, file: &BackupFile) -> Result<(), String> {
+        let _job = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+        let tool = self.tool(&["mariadb", "mysql"])?;
+
+        let input = File::open(&file.path).map_err(|e| format!("Failed to open {}: {e}", file.path.display()))?;
+        let total = file.size.max(1);
+        let read = std::rc::Rc::new(std::cell::Cell::new(0u64));
+        let counted = Counted { inner: input, read: std::rc::Rc::clone(&read) };
+        let mut input: Box<dyn Read> = if file.is_compressed() {
+            Box::new(GzDecoder::new(counted))
+        } else {
+            Box::new(counted)
+        };
+
+        let mut command = self.command(&tool);
+        command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        log(format!("Restoring {} into {}", file.name(), self.service));
+        let started = Instant::now();
+        let mut child = command.spawn().map_err(|e| format!("Failed to run {}: {e}", tool.display()))?;
+        let mut stdin = child.stdin.take().ok_or("The client has no input")?;
+        let readers = services::capture_output(&mut child, SOURCE, None, None);
+
+        let copied = copy(&mut input, &mut stdin, |_| {
+            log(format!("Restored {}%", read.get() * 100 / total));
+        });
+        // Closing stdin tells the client the script is over.
+        drop(stdin);
+        let status = child.wait();
+        for reader in readers {
+            let _ = reader.join();
+        }
+
+        match (copied, status) {
+            (_, Ok(status)) if !status.success() => Err(format!("{} exited with {status}", tool.display())),
+            (Err(e), _) => Err(format!("Failed to send {} to the server: {e}", file.name())),
+            (Ok(_), Err(e)) => Err(format!("Failed to wait for {}: {e}", tool.display())),
+            (Ok(_), Ok(_)) => {
+                log(format!("Restore finished in {:.1}s", started.elapsed().as_secs_f64()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Deletes all but the newest `keep` dumps.
+    fn prune(&self) {
+        for file in self.files().iter().skip(self.keep) {
+            match std::fs::remove_file(&file.path) {
+                Ok(()) => log(format!("Removed old backup {}", file.name())),
+                Err(e) => log(format!("Failed to remove old backup {}: {e}", file.path.display())),
+            }
+        }
+    }
+
+    fn tool(&self, names: &[&str]) -> Result<PathBuf, String> {
+        for name in names {
+            let program = match &self.tool_dir {
+                Some(dir) => dir.join(name).to_string_lossy().into_owned(),
+                None => name.to_string(),
+            };
+            let path = platform::resolve_executable(&program);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+        Err(format!("{} was not found next to {} or on PATH", names.join(" or "), self.service))
+    }
+
+    fn command(&self, tool: &Path) -> Command {
+        let settings = &self.settings;
+        let mut command = Command::new(tool);
+        command
+            .arg("--protocol=TCP")
+            .arg(format!("--host={}", settings.host))
+            .arg(format!("--port={}", settings.port.unwrap_or(3306)))
+            .arg(format!("--user={}", settings.user));
+        // Keeps the password off the command line, where other users could
+        // see it.
+        if !settings.password.is_empty() {
+            command.env("MYSQL_PWD", &settings.password);
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        }
+        command
+    }
+}
+
+fn log(message: String) {
+    services::log_entry(LogEntry::service(SOURCE, message));
+}
+
+/// `shop-20240309-020000.sql.gz`, or `all-databases-...` for a full dump.
+fn file_name(schema: Option<&str>, time: DateTime<Local>) -> String {
+    let label = schema.map(runfiles::slug).unwrap_or_else(|| "all-databases".to_string());
+    format!("{label}-{}.sql.gz", time.format("%Y%m%d-%H%M%S"))
+}
+
+fn files_in(dir: &Path) -> Vec<BackupFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<BackupFile> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.ends_with(".sql") || name.ends_with(".sql.gz")
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(BackupFile {
+                path: entry.path(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH).into(),
+                size: metadata.len(),
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| b.path.cmp(&a.path)));
+    files
+}
+
+/// Copies `reader` into `writer`, calling `progress` with the bytes copied
+/// so far every `PROGRESS_INTERVAL`.
+fn copy(reader: &mut dyn Read, writer: &mut dyn Write, mut progress: impl FnMut(u64)) -> std::io::Result<u64> {
+    let mut buffer = vec![0; 64 * 1024];
+    let mut copied = 0;
+    let mut reported = Instant::now();
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            progress(copied);
+            reported = Instant::now();
+        }
+    }
+    writer.flush()?;
+    Ok(copied)
+}
+
+/// Counts the bytes read from a file, for restore progress.
+struct Counted<R> {
+    inner: R,
+    read: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.read.set(self.read.get() + read as u64);
+        Ok(read)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// Dumps every running database service at `nightly_at` each day, if set.
+pub fn schedule(registry: ServiceRegistry, config: BackupConfig) {
+    let Some(at) = config.nightly_at() else {
+        return;
+    };
+    log(format!("Nightly backups are scheduled for {}", at.format("%H:%M")));
+
+    std::thread::spawn(move || loop {
+        // Sleep in short steps so a suspended laptop catches up on waking.
+        let next = next_run(Local::now(), at);
+        while Local::now() < next {
+            let remaining = (next - Local::now()).to_std().unwrap_or_default();
+            std::thread::sleep(remaining.min(Duration::from_secs(60)));
+        }
+
+        for service in registry.iter() {
+            if service.state() != ServiceState::Running {
+                continue;
+            }
+            if let Some(backups) = Backups::for_service(service, &config) {
+                if let Err(e) = backups.create(None) {
+                    log(format!("Nightly backup of {} failed: {e}", service.name));
+                }
+            }
+        }
+    });
+}
+
+/// The first time after `now` that the clock reads `at`.
+fn next_run(now: DateTime<Local>, at: NaiveTime) -> DateTime<Local> {
+    let mut date = now.date_naive();
+    loop {
+        // A time skipped by a daylight saving change runs the next day.
+        if let Some(time) = date.and_time(at).and_local_timezone(Local).earliest() {
+            if time > now {
+                return time;
+            }
+        }
+        date = date.succ_opt().unwrap_or(date);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_file_names() {
+        let time = Local.with_ymd_and_hms(2024, 3, 9, 2, 0, 5).unwrap();
+        assert_eq!(file_name(Some("Shop DB"), time), "shop-db-20240309-020005.sql.gz");
+        assert_eq!(file_name(None, time), "all-databases-20240309-020005.sql.gz");
+    }
+
+    #[test]
+    fn test_next_run() {
+        let at = NaiveTime::from_hms_opt(2, 0, 0).unwrap();
+        let evening = Local.with_ymd_and_hms(2024, 6, 1, 21, 30, 0).unwrap();
+        assert_eq!(next_run(evening, at), Local.with_ymd_and_hms(2024, 6, 2, 2, 0, 0).unwrap());
+        let night = Local.with_ymd_and_hms(2024, 6, 2, 1, 0, 0).unwrap();
+        assert_eq!(next_run(night, at), Local.with_ymd_and_hms(2024, 6, 2, 2, 0, 0).unwrap());
+        let exactly = Local.with_ymd_and_hms(2024, 6, 2, 2, 0, 0).unwrap();
+        assert_eq!(next_run(exactly, at), Local.with_ymd_and_hms(2024, 6, 3, 2, 0, 0).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dump_restore_and_retention() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let scripts = [
+            ("mariadb-dump", "echo \"-- dump $*\"\necho 'CREATE TABLE t (id INT);'"),
+            ("mariadb", "cat > \"$(dirname \"$0\")/restored.sql\""),
+        ];
+        for (name, script) in scripts {
+            let tool = bin.join(name);
+            std::fs::write(&tool, format!("#!/bin/sh\n{script}\n")).unwrap();
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let backups = Backups {
+            service: "MariaDB".to_string(),
+            settings: DatabaseConfig {
+                host: "127.0.0.1".to_string(),
+                port: Some(3307),
+                user: "root".to_string(),
+                password: String::new(),
+                schema: None,
+            },
+            tool_dir: Some(bin.clone()),
+            dir: dir.path().join("backups").join("mariadb"),
+            keep: 2,
+        };
+
+        let path = backups.create(Some("shop")).unwrap();
+        let mut sql = String::new();
+        GzDecoder::new(File::open(&path).unwrap()).read_to_string(&mut sql).unwrap();
+        assert!(sql.contains("--port=3307 --user=root --single-transaction"), "{sql}");
+        assert!(sql.ends_with("--databases shop\nCREATE TABLE t (id INT);\n"), "{sql}");
+
+        backups.restore(&backups.files()[0]).unwrap();
+        assert_eq!(std::fs::read_to_string(bin.join("restored.sql")).unwrap(), sql);
+
+        for old in ["all-databases-20240101-020000.sql.gz", "all-databases-20240102-020000.sql.gz"] {
+            let old = backups.dir().join(old);
+            std::fs::write(&old, b"").unwrap();
+            let earlier = SystemTime::now() - Duration::from_secs(3600);
+            File::options().write(true).open(&old).unwrap().set_modified(earlier).unwrap();
+        }
+        backups.prune();
+        let names: Vec<String> = backups.files().iter().map(BackupFile::name).collect();
+        assert_eq!(names, [path.file_name().unwrap().to_string_lossy().into_owned(), "all-databases-20240102-020000.sql.gz".to_string()]);
+    }
+}
@@ -0,0 +1,449 @@
+//! Talks to a managed MariaDB/MySQL server over the MySQL protocol for the SQL
+//! console and the Databases tab. A script is split into statements that run
+//! one after another on a background thread; each produces a result set or
+//! an affected-row count.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mysql::prelude::Queryable;
+use mysql::{Conn, OptsBuilder, Value};
+
+use crate::config::DatabaseConfig;
+use crate::services::runfiles;
+
+pub mod admin;
+pub mod backup;
+pub mod server_config;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rows kept per result set; the rest are counted but not stored.
+pub const MAX_ROWS: usize = 10_000;
+
+const MAX_HISTORY: usize = 100;
+
+/// What one statement returned.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub statement: String,
+    /// Empty for statements without a result set, such as `UPDATE`.
+    pub columns: Vec<String>,
+    /// `None` is SQL `NULL`.
+    pub rows: Vec<Vec<Option<String>>>,
+    /// Rows the server sent, including those past `MAX_ROWS`.
+    pub total_rows: usize,
+    pub affected_rows: u64,
+    pub elapsed: Duration,
+}
+
+impl QueryResult {
+    pub fn has_rows(&self) -> bool {
+        !self.columns.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        let elapsed = self.elapsed.as_secs_f64();
+        if !self.has_rows() {
+            return format!("{} row(s) affected in {elapsed:.3}s", self.affected_rows);
+        }
+        if self.total_rows > self.rows.len() {
+            format!("{} row(s) in {elapsed:.3}s, first {} shown", self.total_rows, self.rows.len())
+        } else {
+            format!("{} row(s) in {elapsed:.3}s", self.total_rows)
+        }
+    }
+
+    /// The kept rows as CSV with a header line, quoting fields as RFC 4180
+    /// describes. `NULL` becomes an empty field.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let mut line = |fields: &mut dyn Iterator<Item = &str>| {
+            let fields: Vec<String> = fields.map(csv_field).collect();
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        };
+        line(&mut self.columns.iter().map(String::as_str));
+        for row in &self.rows {
+            line(&mut row.iter().map(|value| value.as_deref().unwrap_or("")));
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Everything a script produced. Execution stops at the first failing
+/// statement, whose error is kept alongside the results before it.
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    pub results: Vec<QueryResult>,
+    pub error: Option<String>,
+}
+
+/// A connection to one server, opened on first use and reopened after it
+/// breaks.
+#[derive(Clone)]
+pub struct Session {
+    settings: DatabaseConfig,
+    conn: Arc<Mutex<Option<Conn>>>,
+}
+
+impl Session {
+    pub fn new(settings: DatabaseConfig) -> Self {
+        Session {
+            settings,
+            conn: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn settings(&self) -> &DatabaseConfig {
+        &self.settings
+    }
+
+    /// `user@host:port`, for display.
+    pub fn address(&self) -> String {
+        let settings = &self.settings;
+        format!("{}@{}:{}", settings.user, settings.host, settings.port.unwrap_or(3306))
+    }
+
+    /// Runs `script` on a background thread and sends its output once every
+    /// statement has finished.
+    pub fn execute(&self, script: &str) -> Receiver<ScriptOutput> {
+        let statements = split_statements(script);
+        let session = self.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(session.run(&statements));
+        });
+        receiver
+    }
+
+    /// Runs `work` with the connection on a background thread.
+    pub fn call<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&mut Conn) -> Result<T, String> + Send + 'static,
+    ) -> Receiver<Result<T, String>> {
+        let session = self.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(session.with_conn(work));
+        });
+        receiver
+    }
+
+    fn run(&self, statements: &[String]) -> ScriptOutput {
+        let mut results = Vec::new();
+        let outcome = self.with_conn(|conn| {
+            for statement in statements {
+                let output = run_statement(conn, statement).map_err(|e| format!("{e}\n  in: {}", first_line(statement)))?;
+                results.extend(output);
+            }
+            Ok(())
+        });
+        ScriptOutput {
+            results,
+            error: outcome.err(),
+        }
+    }
+
+    fn with_conn<T>(&self, work: impl FnOnce(&mut Conn) -> Result<T, String>) -> Result<T, String> {
+        let mut guard = self.conn.lock().map_err(|_| "The database connection is unavailable".to_string())?;
+        if guard.is_none() {
+            *guard = Some(self.connect()?);
+        }
+        let conn = guard.as_mut().unwrap();
+
+        let result = work(conn);
+        // A dropped connection fails everything after it; start over on the
+        // next call instead.
+        if result.is_err() && conn.ping().is_err() {
+            *guard = None;
+        }
+        result
+    }
+
+    fn connect(&self) -> Result<Conn, String> {
+        let settings = &self.settings;
+        let opts = OptsBuilder::new()
+            .ip_or_hostname(Some(settings.host.clone()))
+            .tcp_port(settings.port.unwrap_or(3306))
+            .user(Some(settings.user.clone()))
+            .pass(Some(settings.password.clone()))
+            .db_name(settings.schema.clone())
+            .prefer_socket(false)
+            .tcp_connect_timeout(Some(CONNECT_TIMEOUT));
+        Conn::new(opts).map_err(|e| format!("Failed to connect to {}: {e}", self.address()))
+    }
+}
+
+fn run_statement(conn: &mut Conn, statement: &str) -> Result<Vec<QueryResult>, String> {
+    let started = Instant::now();
+    let mut query = conn.query_iter(statement).map_err(|e| e.to_string())?;
+
+    // A statement can return several result sets, e.g. a stored procedure.
+    let mut results = Vec::new();
+    loop {
+        let columns: Vec<String> = query.columns().as_ref().iter().map(|column| column.name_str().into_owned()).collect();
+        let affected_rows = query.affected_rows();
+        let Some(set) = query.iter() else {
+            break;
+        };
+
+        let mut result = QueryResult {
+            statement: statement.to_string(),
+            columns,
+            affected_rows,
+            ..QueryResult::default()
+        };
+        for row in set {
+            let row = row.map_err(|e| e.to_string())?;
+            result.total_rows += 1;
+            if result.rows.len() < MAX_ROWS {
+                result.rows.push(row.unwrap().into_iter().map(display_value).collect());
+            }
+        }
+        result.elapsed = started.elapsed();
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn display_value(value: Value) -> Option<String> {
+    let text = match value {
+        Value::NULL => return None,
+        Value::Bytes(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Value::Int(n) => n.to_string(),
+        Value::UInt(n) => n.to_string(),
+        Value::Float(n) => n.to_string(),
+        Value::Double(n) => n.to_string(),
+        Value::Date(year, month, day, hour, minute, second, micros) => {
+            let mut text = format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}");
+            if micros > 0 {
+                text.push_str(&format!(".{micros:06}"));
+            }
+            text
+        }
+        Value::Time(negative, days, hours, minutes, seconds, micros) => {
+            let hours = days * 24 + u32::from(hours);
+            let mut text = format!("{}{hours:02}:{minutes:02}:{seconds:02}", if negative { "-" } else { "" });
+            if micros > 0 {
+                text.push_str(&format!(".{micros:06}"));
+            }
+            text
+        }
+    };
+    Some(text)
+}
+
+fn first_line(statement: &str) -> &str {
+    statement.lines().next().unwrap_or_default()
+}
+
+/// Splits a script on `;`, leaving semicolons inside quotes, backquoted
+/// names and comments alone. Empty statements are dropped.
+pub fn split_statements(script: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut chars = script.chars().peekable();
+
+    let mut finish = |current: &mut String| {
+        let statement = current.trim();
+        if !is_blank(statement) {
+            statements.push(statement.to_string());
+        }
+        current.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => finish(&mut current),
+            '\'' | '"' | '`' => {
+                current.push(c);
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == '\\' && c != '`' {
+                        if let Some(escaped) = chars.next() {
+                            current.push(escaped);
+                        }
+                    } else if next == c {
+                        // A doubled quote stands for the quote itself.
+                        match chars.next_if_eq(&c) {
+                            Some(quote) => current.push(quote),
+                            None => break,
+                        }
+                    }
+                }
+            }
+            '#' => skip_line(&mut chars, &mut current, c),
+            '-' if is_dash_comment(&format!("-{}", chars.clone().take(2).collect::<String>())) => {
+                skip_line(&mut chars, &mut current, c)
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                current.push(c);
+                current.extend(chars.next());
+                let mut previous = '\0';
+                for next in chars.by_ref() {
+                    current.push(next);
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    finish(&mut current);
+    statements
+}
+
+fn skip_line(chars: &mut std::iter::Peekable<std::str::Chars>, current: &mut String, first: char) {
+    current.push(first);
+    for next in chars.by_ref() {
+        current.push(next);
+        if next == '\n' {
+            break;
+        }
+    }
+}
+
+/// Whether `text` starts with a `-- ` comment. MySQL wants whitespace or the
+/// end of the line after the dashes, so `5--1` is arithmetic.
+fn is_dash_comment(text: &str) -> bool {
+    text.strip_prefix("--")
+        .is_some_and(|rest| rest.chars().next().is_none_or(char::is_whitespace))
+}
+
+/// Whether `statement` holds nothing but whitespace and comments.
+fn is_blank(statement: &str) -> bool {
+    let mut rest = statement.trim_start();
+    loop {
+        if rest.is_empty() {
+            return true;
+        }
+        if rest.starts_with('#') || is_dash_comment(rest) {
+            rest = rest.split_once('\n').map(|(_, rest)| rest.trim_start()).unwrap_or("");
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            match comment.split_once("*/") {
+                Some((_, rest_of)) => rest = rest_of.trim_start(),
+                None => return true,
+            }
+        } else {
+            return false;
+        }
+    }
+}
+
+/// Scripts run in the SQL console, newest last, saved after every run.
+pub struct QueryHistory {
+    entries: Vec<String>,
+    path: PathBuf,
+}
+
+impl QueryHistory {
+    pub fn load() -> Self {
+        Self::load_from(runfiles::sql_history_path())
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        QueryHistory { entries, path }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Records `script`, moving it to the end if it was run before.
+    pub fn push(&mut self, script: &str) {
+        let script = script.trim();
+        self.entries.retain(|entry| entry != script);
+        self.entries.push(script.to_string());
+        let excess = self.entries.len().saturating_sub(MAX_HISTORY);
+        self.entries.drain(..excess);
+
+        if let Some(parent) = self.path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string_pretty(&self.entries) {
+            let _ = std::fs::write(&self.path, json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_statements() {
+        let script = "
+            -- set up; nothing to see
+            CREATE TABLE t (name VARCHAR(20)); # trailing; comment
+            INSERT INTO t VALUES ('a;b'), ('it''s'), (\"say \\\"hi\\\";\");
+            /* block; comment */ SELECT `odd;name` FROM t;
+            ;
+            -- only a comment;
+        ";
+        assert_eq!(
+            split_statements(script),
+            vec![
+                "-- set up; nothing to see\n            CREATE TABLE t (name VARCHAR(20))",
+                "# trailing; comment\n            INSERT INTO t VALUES ('a;b'), ('it''s'), (\"say \\\"hi\\\";\")",
+                "/* block; comment */ SELECT `odd;name` FROM t",
+            ]
+        );
+        assert_eq!(split_statements("SELECT 1"), vec!["SELECT 1"]);
+        assert_eq!(split_statements("SELECT 5--1; SELECT 2;\n--"), vec!["SELECT 5--1", "SELECT 2"]);
+        assert_eq!(split_statements("--1;"), vec!["--1"]);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let result = QueryResult {
+            columns: vec!["id".to_string(), "note".to_string()],
+            rows: vec![
+                vec![Some("1".to_string()), Some("plain".to_string())],
+                vec![Some("2".to_string()), Some("has, comma and \"quotes\"".to_string())],
+                vec![Some("3".to_string()), None],
+            ],
+            ..QueryResult::default()
+        };
+        assert_eq!(
+            result.to_csv(),
+            "id,note\r\n1,plain\r\n2,\"has, comma and \"\"quotes\"\"\"\r\n3,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_display_values() {
+        assert_eq!(display_value(Value::NULL), None);
+        assert_eq!(display_value(Value::Bytes(b"caf\xc3\xa9".to_vec())).as_deref(), Some("café"));
+        assert_eq!(display_value(Value::Date(2024, 3, 9, 14, 5, 0, 0)).as_deref(), Some("2024-03-09 14:05:00"));
+        assert_eq!(display_value(Value::Time(true, 1, 2, 3, 4, 5)).as_deref(), Some("-26:03:04.000005"));
+    }
+
+    #[test]
+    fn test_query_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run").join("sql-history.json");
+        let mut history = QueryHistory::load_from(path.clone());
+        history.push("SELECT 1;");
+        history.push("SHOW DATABASES;\n");
+        history.push("SELECT 1;");
+
+        assert_eq!(QueryHistory::load_from(path).entries(), ["SHOW DATABASES;", "SELECT 1;"]);
+    }
+}
@@ -0,0 +1,355 @@
+//! The server's option file (`my.ini` / `my.cnf`), as passed with
+//! `--defaults-file`. Lines are kept as they are, comments included; the
+//! editor only rewrites the settings that change.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use mysql::prelude::Queryable;
+use mysql::Conn;
+
+use super::admin::quote_string;
+
+/// Sections the server reads its own options from.
+const SERVER_SECTIONS: [&str; 5] = ["mysqld", "server", "mariadb", "mariadbd", "client-server"];
+
+/// What a setting's value has to look like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Port,
+    /// Host names or IP addresses, comma-separated.
+    Address,
+    /// Bytes, with an optional `K`, `M`, `G` or `T` suffix.
+    Size,
+    Count,
+    /// A character set or collation name.
+    Name,
+}
+
+impl Kind {
+    pub fn check(self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let valid = match self {
+            Kind::Port => value.parse::<u16>().is_ok_and(|port| port > 0),
+            Kind::Address => {
+                !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || ".:-_*,".contains(c))
+            }
+            Kind::Size => parse_size(value).is_some(),
+            Kind::Count => value.parse::<u32>().is_ok_and(|count| count > 0),
+            Kind::Name => !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        };
+        if valid {
+            return Ok(());
+        }
+        Err(match self {
+            Kind::Port => format!("'{value}' is not a port between 1 and 65535"),
+            Kind::Address => format!("'{value}' is not a host name or IP address"),
+            Kind::Size => format!("'{value}' is not a size such as 512M or 2G"),
+            Kind::Count => format!("'{value}' is not a positive number"),
+            Kind::Name => format!("'{value}' is not a character set or collation name"),
+        })
+    }
+}
+
+/// A setting the form edits.
+pub struct Setting {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: Kind,
+    /// Whether a running server can change it with `SET GLOBAL`; the others
+    /// take effect on the next start.
+    pub dynamic: bool,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting { key: "port", label: "Port", kind: Kind::Port, dynamic: false },
+    Setting { key: "bind-address", label: "Bind address", kind: Kind::Address, dynamic: false },
+    Setting { key: "innodb_buffer_pool_size", label: "InnoDB buffer pool", kind: Kind::Size, dynamic: true },
+    Setting { key: "max_connections", label: "Max connections", kind: Kind::Count, dynamic: true },
+    Setting { key: "character-set-server", label: "Character set", kind: Kind::Name, dynamic: true },
+    Setting { key: "collation-server", label: "Collation", kind: Kind::Name, dynamic: true },
+];
+
+/// The form setting for `key`, which may use `-` or `_`.
+pub fn setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| normalize(setting.key) == normalize(key))
+}
+
+#[derive(Clone)]
+pub struct OptionFile {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl OptionFile {
+    /// Reads `path`; a missing file is an empty one.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+        };
+        Ok(Self::parse(path.to_path_buf(), &contents))
+    }
+
+    pub fn parse(path: PathBuf, contents: &str) -> Self {
+        OptionFile {
+            path,
+            lines: contents.lines().map(str::to_string).collect(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contents(&self) -> String {
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+        contents
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        std::fs::write(&self.path, self.contents()).map_err(|e| format!("Failed to write {}: {e}", self.path.display()))
+    }
+
+    /// Server options by normalized name (`bind_address`); the last
+    /// assignment wins, and bare flags have an empty value.
+    pub fn server_options(&self) -> BTreeMap<String, String> {
+        self.server_lines()
+            .map(|(_, key, value)| (normalize(key), unquote(value).to_string()))
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.server_options().remove(&normalize(key))
+    }
+
+    /// Sets a server option, rewriting the value of its last assignment or
+    /// adding it to the `[mysqld]` section.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let name = normalize(key);
+        if let Some((index, _, _)) = self.server_lines().filter(|(_, key, _)| normalize(key) == name).last() {
+            self.lines[index] = with_value(&self.lines[index], value);
+            return;
+        }
+        let line = format!("{key}={value}");
+
+        let Some(header) = self.lines.iter().position(|line| section(line).is_some_and(|section| section == "mysqld")) else {
+            if self.lines.last().is_some_and(|last| !last.trim().is_empty()) {
+                self.lines.push(String::new());
+            }
+            self.lines.push("[mysqld]".to_string());
+            self.lines.push(line);
+            return;
+        };
+        // After the section's last option, ahead of trailing blank lines.
+        let mut index = header + 1;
+        for (offset, line) in self.lines[header + 1..].iter().enumerate() {
+            if section(line).is_some() {
+                break;
+            }
+            if option(line).is_some() {
+                index = header + 2 + offset;
+            }
+        }
+        self.lines.insert(index, line);
+    }
+
+    /// Removes every assignment of a server option, so the server falls back
+    /// to its default.
+    pub fn remove(&mut self, key: &str) {
+        let name = normalize(key);
+        let indexes: Vec<usize> = self
+            .server_lines()
+            .filter(|(_, key, _)| normalize(key) == name)
+            .map(|(index, _, _)| index)
+            .collect();
+        for index in indexes.into_iter().rev() {
+            self.lines.remove(index);
+        }
+    }
+
+    /// `(line index, key, value)` of each option in a server section.
+    fn server_lines(&self) -> impl Iterator<Item = (usize, &str, &str)> {
+        let mut in_server = false;
+        self.lines.iter().enumerate().filter_map(move |(index, line)| {
+            if let Some(section) = section(line) {
+                in_server = SERVER_SECTIONS.contains(&section.as_str());
+                return None;
+            }
+            let (key, value) = option(line).filter(|_| in_server)?;
+            Some((index, key, value))
+        })
+    }
+}
+
+/// Options whose value differs between `before` and `after`, by normalized name.
+pub fn changed(before: &OptionFile, after: &OptionFile) -> Vec<String> {
+    let (before, after) = (before.server_options(), after.server_options());
+    let mut keys: Vec<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Problems with the form settings in `file`, one message each.
+pub fn validate(file: &OptionFile) -> Vec<String> {
+    let options = file.server_options();
+    SETTINGS
+        .iter()
+        .filter_map(|setting| {
+            let value = options.get(&normalize(setting.key))?;
+            setting.kind.check(value).err().map(|e| format!("{}: {e}", setting.key))
+        })
+        .collect()
+}
+
+/// Applies a dynamic setting to the running server.
+pub fn apply(conn: &mut Conn, key: &str, value: &str) -> Result<(), String> {
+    let setting = setting(key).filter(|setting| setting.dynamic).ok_or_else(|| format!("{key} needs a restart"))?;
+    setting.kind.check(value).map_err(|e| format!("{key}: {e}"))?;
+    let value = match setting.kind {
+        Kind::Size => parse_size(value).unwrap_or_default().to_string(),
+        Kind::Name => quote_string(value.trim()),
+        _ => value.trim().to_string(),
+    };
+    conn.query_drop(format!("SET GLOBAL {} = {value}", normalize(setting.key)))
+        .map_err(|e| format!("Failed to set {key}: {e}"))
+}
+
+/// Bytes in a size such as `512M`.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last()?.to_ascii_uppercase() {
+        'K' => (&value[..value.len() - 1], 1u64 << 10),
+        'M' => (&value[..value.len() - 1], 1 << 20),
+        'G' => (&value[..value.len() - 1], 1 << 30),
+        'T' => (&value[..value.len() - 1], 1 << 40),
+        _ => (value, 1),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Option names treat `-` and `_` alike.
+fn normalize(key: &str) -> String {
+    key.trim().to_ascii_lowercase().replace('-', "_")
+}
+
+/// The name of a `[section]` header line.
+fn section(line: &str) -> Option<String> {
+    let line = line.trim();
+    let name = line.strip_prefix('[')?.strip_suffix(']')?;
+    Some(name.trim().to_ascii_lowercase())
+}
+
+/// `(key, value)` of an option line; bare flags have an empty value.
+fn option(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with(['#', ';', '[', '!']) {
+        return None;
+    }
+    let line = match line.find(" #") {
+        Some(comment) => line[..comment].trim_end(),
+        None => line,
+    };
+    Some(match line.split_once('=') {
+        Some((key, value)) => (key.trim(), value.trim()),
+        None => (line, ""),
+    })
+}
+
+/// An option line with its value replaced, keeping the spacing around `=`
+/// and any trailing comment.
+fn with_value(line: &str, value: &str) -> String {
+    let (body, comment) = line.split_at(line.find(" #").unwrap_or(line.len()));
+    let assignment = body.trim_end();
+    let padding = &body[assignment.len()..];
+    match assignment.split_once('=') {
+        Some((key, old)) => {
+            let spacing = &old[..old.len() - old.trim_start().len()];
+            format!("{key}={spacing}{value}{padding}{comment}")
+        }
+        None => format!("{assignment}={value}{padding}{comment}"),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MY_INI: &str = "# Xerve's MariaDB settings\n\
+        [client]\n\
+        port=3306\n\
+        \n\
+        [mysqld]\n\
+        datadir=./data\n\
+        port = 3306 # keep in line with xerve.toml\n\
+        bind_address=\"127.0.0.1\"\n\
+        skip-name-resolve\n\
+        \n\
+        [mysqldump]\n\
+        quick\n";
+
+    fn file(contents: &str) -> OptionFile {
+        OptionFile::parse(PathBuf::from("my.ini"), contents)
+    }
+
+    #[test]
+    fn test_read_and_edit_options() {
+        let mut ini = file(MY_INI);
+        assert_eq!(ini.get("port").as_deref(), Some("3306"));
+        assert_eq!(ini.get("bind-address").as_deref(), Some("127.0.0.1"));
+        assert_eq!(ini.get("skip_name_resolve").as_deref(), Some(""));
+        assert_eq!(ini.get("quick"), None);
+
+        let before = ini.clone();
+        ini.set("port", "3307");
+        ini.set("max_connections", "200");
+        assert!(ini.contents().contains("[client]\nport=3306\n"));
+        assert!(ini.contents().contains("datadir=./data\nport = 3307 # keep in line with xerve.toml\nbind_address=\"127.0.0.1\"\nskip-name-resolve\nmax_connections=200\n\n[mysqldump]"));
+        assert_eq!(changed(&before, &ini), vec!["max_connections", "port"]);
+
+        ini.remove("bind-address");
+        ini.remove("max_connections");
+        assert!(ini.contents().contains("# keep in line with xerve.toml\nskip-name-resolve\n\n[mysqldump]"));
+        assert_eq!(ini.get("bind_address"), None);
+
+        let mut empty = file("");
+        empty.set("port", "3308");
+        assert_eq!(empty.contents(), "[mysqld]\nport=3308\n");
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        let ini = file("[mysqld]\nport=70000\nbind-address=0.0.0.0\ninnodb_buffer_pool_size=512MB\nmax_connections=150\ncollation-server=utf8mb4 general\n");
+        assert_eq!(
+            validate(&ini),
+            vec![
+                "port: '70000' is not a port between 1 and 65535",
+                "innodb_buffer_pool_size: '512MB' is not a size such as 512M or 2G",
+                "collation-server: 'utf8mb4 general' is not a character set or collation name",
+            ]
+        );
+        assert_eq!(parse_size("512M"), Some(512 << 20));
+        assert_eq!(parse_size("2g"), Some(2 << 30));
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("M"), None);
+        assert!(setting("bind_address").is_some_and(|setting| !setting.dynamic));
+    }
+}
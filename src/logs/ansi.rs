@@ -0,0 +1,255 @@
+//! Turns terminal output into plain text plus colour spans. SGR sequences
+//! (`ESC [ ... m`) become styles; cursor movement, erase, OSC (titles and
+//! hyperlinks) and other control sequences are dropped. A carriage return in
+//! the middle of a line starts it over, as progress bars expect.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+const ESC: char = '\x1b';
+
+/// A colour as the program asked for it; the palette is up to the renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnsiColor {
+    /// 0-7 standard, 8-15 bright, 16-255 the xterm 256-colour cube and greys.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnsiStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<AnsiColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<AnsiColor>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dim: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub inverse: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Styled bytes of a parsed message. Text outside every span is unstyled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyledSpan {
+    pub range: Range<usize>,
+    pub style: AnsiStyle,
+}
+
+/// Splits `input` into its visible text and the styled parts of that text.
+pub fn parse(input: &str) -> (String, Vec<StyledSpan>) {
+    if !input.chars().any(|c| c.is_control() && c != '\t') {
+        return (input.to_string(), Vec::new());
+    }
+
+    let mut text = String::with_capacity(input.len());
+    let mut spans: Vec<StyledSpan> = Vec::new();
+    let mut style = AnsiStyle::default();
+    let mut restart_line = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ESC => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut command = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            command = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    if command == Some('m') {
+                        apply_sgr(&mut style, &params);
+                    }
+                }
+                Some(']') => {
+                    // Operating system command, ended by BEL or ESC \.
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                Some('(' | ')' | '*' | '+') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\r' => restart_line = true,
+            '\t' => push(&mut text, &mut spans, &mut restart_line, style, c),
+            c if c.is_control() => {}
+            c => push(&mut text, &mut spans, &mut restart_line, style, c),
+        }
+    }
+
+    (text, spans)
+}
+
+fn push(text: &mut String, spans: &mut Vec<StyledSpan>, restart_line: &mut bool, style: AnsiStyle, c: char) {
+    if std::mem::take(restart_line) {
+        text.clear();
+        spans.clear();
+    }
+
+    let start = text.len();
+    text.push(c);
+    if style == AnsiStyle::default() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.style == style && last.range.end == start => last.range.end = text.len(),
+        _ => spans.push(StyledSpan { range: start..text.len(), style }),
+    }
+}
+
+fn apply_sgr(style: &mut AnsiStyle, params: &str) {
+    let codes: Vec<u16> = params
+        .split([';', ':'])
+        .map(|code| code.parse().unwrap_or(0))
+        .collect();
+
+    let mut codes = codes.into_iter();
+    while let Some(code) = codes.next() {
+        match code {
+            0 => *style = AnsiStyle::default(),
+            1 => style.bold = true,
+            2 => style.dim = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            7 => style.inverse = true,
+            21 | 22 => {
+                style.bold = false;
+                style.dim = false;
+            }
+            23 => style.italic = false,
+            24 => style.underline = false,
+            27 => style.inverse = false,
+            30..=37 => style.fg = Some(AnsiColor::Indexed((code - 30) as u8)),
+            38 => style.fg = extended_color(&mut codes),
+            39 => style.fg = None,
+            40..=47 => style.bg = Some(AnsiColor::Indexed((code - 40) as u8)),
+            48 => style.bg = extended_color(&mut codes),
+            49 => style.bg = None,
+            90..=97 => style.fg = Some(AnsiColor::Indexed((code - 90 + 8) as u8)),
+            100..=107 => style.bg = Some(AnsiColor::Indexed((code - 100 + 8) as u8)),
+            _ => {}
+        }
+    }
+}
+
+/// The rest of a `38;5;n` or `38;2;r;g;b` sequence.
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<AnsiColor> {
+    let mut next = || codes.next().map(|code| code.min(255) as u8);
+    match next()? {
+        5 => next().map(AnsiColor::Indexed),
+        2 => Some(AnsiColor::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(input: &str) -> Vec<(String, AnsiStyle)> {
+        let (text, spans) = parse(input);
+        spans.into_iter().map(|span| (text[span.range].to_string(), span.style)).collect()
+    }
+
+    fn fg(color: u8) -> AnsiStyle {
+        AnsiStyle { fg: Some(AnsiColor::Indexed(color)), ..AnsiStyle::default() }
+    }
+
+    #[test]
+    fn test_plain_text_is_untouched() {
+        assert_eq!(parse("ready for connections.\tport: 3306"), ("ready for connections.\tport: 3306".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_composer_install() {
+        let line = "\x1b[32mInstalling dependencies from lock file (including require-dev)\x1b[39m";
+        assert_eq!(parse(line).0, "Installing dependencies from lock file (including require-dev)");
+        assert_eq!(styled(line), vec![("Installing dependencies from lock file (including require-dev)".to_string(), fg(2))]);
+    }
+
+    #[test]
+    fn test_artisan_serve_badge() {
+        let line = "\x1b[37;44m INFO \x1b[39;49m Server running on [\x1b[1mhttp://127.0.0.1:8000\x1b[22m].";
+        let (text, _) = parse(line);
+        assert_eq!(text, " INFO  Server running on [http://127.0.0.1:8000].");
+        assert_eq!(
+            styled(line),
+            vec![
+                (" INFO ".to_string(), AnsiStyle { bg: Some(AnsiColor::Indexed(4)), ..fg(7) }),
+                ("http://127.0.0.1:8000".to_string(), AnsiStyle { bold: true, ..AnsiStyle::default() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vite_banner_keeps_styles_separate() {
+        let line = "  \x1b[32m\x1b[1mVITE\x1b[22m v5.0.12\x1b[39m  ready in \x1b[1m312\x1b[22m ms";
+        assert_eq!(parse(line).0, "  VITE v5.0.12  ready in 312 ms");
+        assert_eq!(
+            styled(line),
+            vec![
+                ("VITE".to_string(), AnsiStyle { bold: true, ..fg(2) }),
+                (" v5.0.12".to_string(), fg(2)),
+                ("312".to_string(), AnsiStyle { bold: true, ..AnsiStyle::default() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_npm_progress_is_overwritten() {
+        let line = "\x1b[?25l\x1b[1G\x1b[0K⠙\x1b[1G\x1b[0K⠹\r\x1b[2Kadded 312 packages in 4s\x1b[?25h";
+        assert_eq!(parse(line), ("added 312 packages in 4s".to_string(), vec![]));
+        assert_eq!(parse("PHP Warning: x\r").0, "PHP Warning: x");
+    }
+
+    #[test]
+    fn test_extended_colours_and_reset() {
+        let line = "\x1b[38;5;208mwarn\x1b[0m \x1b[38;2;255;0;128;48;5;236mpink\x1b[m done";
+        assert_eq!(parse(line).0, "warn pink done");
+        assert_eq!(
+            styled(line),
+            vec![
+                ("warn".to_string(), fg(208)),
+                (
+                    "pink".to_string(),
+                    AnsiStyle {
+                        fg: Some(AnsiColor::Rgb(255, 0, 128)),
+                        bg: Some(AnsiColor::Indexed(236)),
+                        ..AnsiStyle::default()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hyperlinks_and_titles_are_dropped() {
+        let line = "\x1b]0;npm run dev\x07See \x1b]8;;https://laravel.com/docs\x1b\\the docs\x1b]8;;\x1b\\ for help\x1b(B";
+        assert_eq!(parse(line), ("See the docs for help".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_bright_colours_and_truncated_sequences() {
+        assert_eq!(styled("\x1b[91mFAIL\x1b[39m"), vec![("FAIL".to_string(), fg(9))]);
+        assert_eq!(parse("cut off \x1b[3").0, "cut off ");
+    }
+}
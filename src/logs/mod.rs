@@ -0,0 +1,222 @@
+pub mod ansi;
+mod search;
+pub mod store;
+
+use std::fmt;
+
+use chrono::{DateTime, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::services::ServiceEvent;
+use ansi::StyledSpan;
+
+pub use search::{Matcher, SearchOptions};
+
+/// Where a log entry came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Messages from Xerve itself, e.g. about starting or stopping a service.
+    System,
+}
+
+impl LogStream {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::System => "xerve",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Guesses the level of a line from the markers Nginx, MariaDB, PHP and
+    /// Xerve itself put in their messages.
+    pub fn detect(message: &str) -> Self {
+        const ERROR_MARKERS: &[&str] = &[
+            "[error]", "[emerg]", "[alert]", "[crit]", "error:", "fatal", "failed", "panicked",
+        ];
+        const WARN_MARKERS: &[&str] = &["[warn]", "[warning]", "warning:", "deprecated:"];
+
+        let lower = message.to_lowercase();
+        if ERROR_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            LogLevel::Error
+        } else if WARN_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            LogLevel::Warn
+        } else {
+            LogLevel::Info
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Local>,
+    /// The service the entry is about, or `None` for Xerve-wide messages.
+    pub service: Option<String>,
+    pub stream: LogStream,
+    pub level: LogLevel,
+    /// The text without ANSI escape sequences; see `spans` for its colours.
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<StyledSpan>,
+}
+
+impl LogEntry {
+    /// A Xerve message not tied to any service.
+    pub fn system(message: impl Into<String>) -> Self {
+        Self::new(None, LogStream::System, message.into())
+    }
+
+    /// A Xerve message about `service`.
+    pub fn service(service: &str, message: impl Into<String>) -> Self {
+        Self::new(Some(service.to_string()), LogStream::System, message.into())
+    }
+
+    /// A line a service process wrote to stdout or stderr.
+    pub fn output(service: &str, stream: LogStream, line: impl Into<String>) -> Self {
+        Self::new(Some(service.to_string()), stream, line.into())
+    }
+
+    pub fn event(event: &ServiceEvent) -> Self {
+        LogEntry {
+            timestamp: DateTime::<Local>::from(event.at),
+            service: Some(event.service.clone()),
+            stream: LogStream::System,
+            level: LogLevel::Info,
+            message: format!("{} -> {}", event.from, event.to),
+            spans: Vec::new(),
+        }
+    }
+
+    fn new(service: Option<String>, stream: LogStream, message: String) -> Self {
+        let (message, spans) = ansi::parse(&message);
+        LogEntry {
+            timestamp: Local::now(),
+            level: LogLevel::detect(&message),
+            service,
+            stream,
+            message,
+            spans,
+        }
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.timestamp.format("%H:%M:%S"))?;
+        if let Some(service) = &self.service {
+            write!(f, "[{service}] ")?;
+        }
+        if self.stream == LogStream::Stderr {
+            write!(f, "STDERR: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Filters log entries by text and time of day.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogQuery {
+    /// Matched case-insensitively against the message; empty matches all.
+    pub text: String,
+    pub from: Option<NaiveTime>,
+    pub to: Option<NaiveTime>,
+}
+
+impl LogQuery {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        let time = entry.timestamp.time();
+        self.from.is_none_or(|from| time >= from)
+            && self.to.is_none_or(|to| time <= to)
+            && (self.text.is_empty() || entry.message.to_lowercase().contains(&self.text.to_lowercase()))
+    }
+}
+
+/// Parses `HH:MM` or `HH:MM:SS`.
+pub fn parse_time_of_day(input: &str) -> Option<NaiveTime> {
+    let input = input.trim();
+    NaiveTime::parse_from_str(input, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(input, "%H:%M"))
+        .ok()
+}
+
+/// Age of `timestamp` relative to `now`, e.g. `5s ago` or `3h ago`.
+pub fn format_relative(timestamp: DateTime<Local>, now: DateTime<Local>) -> String {
+    let seconds = (now - timestamp).num_seconds().max(0);
+    match seconds {
+        0..60 => format!("{seconds}s ago"),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_level() {
+        assert_eq!(
+            LogLevel::detect("2025-08-12 10:00:00 0 [ERROR] Can't start server: Bind on TCP/IP port"),
+            LogLevel::Error
+        );
+        assert_eq!(LogLevel::detect("2025/08/12 [emerg] 1234#0: bind() failed"), LogLevel::Error);
+        assert_eq!(LogLevel::detect("2025-08-12 0 [Warning] Aborted connection"), LogLevel::Warn);
+        assert_eq!(LogLevel::detect("PHP Deprecated:  Creation of dynamic property"), LogLevel::Warn);
+        assert_eq!(LogLevel::detect("ready for connections."), LogLevel::Info);
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = Local::now();
+        assert_eq!(format_relative(now - chrono::Duration::seconds(5), now), "5s ago");
+        assert_eq!(format_relative(now - chrono::Duration::seconds(150), now), "2m ago");
+        assert_eq!(format_relative(now - chrono::Duration::hours(3), now), "3h ago");
+        assert_eq!(format_relative(now + chrono::Duration::seconds(2), now), "0s ago");
+    }
+
+    #[test]
+    fn test_display() {
+        let entry = LogEntry::output("MariaDB", LogStream::Stderr, "InnoDB: Starting shutdown...");
+        assert!(entry.to_string().ends_with("] [MariaDB] STDERR: InnoDB: Starting shutdown..."));
+        assert!(LogEntry::system("Starting all services...").to_string().ends_with("] Starting all services..."));
+    }
+
+    #[test]
+    fn test_query() {
+        let mut entry = LogEntry::output("MariaDB", LogStream::Stderr, "InnoDB: Database was not shut down normally!");
+        entry.timestamp = Local::now().with_time(NaiveTime::from_hms_opt(12, 12, 0).unwrap()).unwrap();
+
+        let query = LogQuery {
+            text: "innodb".to_string(),
+            from: parse_time_of_day("12:00"),
+            to: parse_time_of_day("12:30:00"),
+        };
+        assert!(query.matches(&entry));
+        assert!(!LogQuery { text: "nginx".to_string(), ..query.clone() }.matches(&entry));
+        assert!(!LogQuery { to: parse_time_of_day("12:10"), ..query }.matches(&entry));
+        assert_eq!(parse_time_of_day("25:00"), None);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let entry = LogEntry::service("Nginx", "Failed to reload Nginx");
+        assert_eq!(entry.level, LogLevel::Error);
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(json.contains(r#""stream":"system""#));
+        assert_eq!(serde_json::from_str::<LogEntry>(&json).unwrap(), entry);
+    }
+}
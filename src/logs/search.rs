@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+/// What the user typed into a search bar and how to interpret it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    pub query: String,
+    /// Treat `query` as a regular expression instead of plain text.
+    pub regex: bool,
+    pub case_sensitive: bool,
+}
+
+/// A compiled search. Plain text is escaped and run through the same regex
+/// engine, so both kinds report byte ranges on character boundaries.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    regex: Regex,
+}
+
+impl Matcher {
+    /// `Ok(None)` for an empty query, `Err` for an invalid regex.
+    pub fn new(options: &SearchOptions) -> Result<Option<Self>, String> {
+        if options.query.is_empty() {
+            return Ok(None);
+        }
+
+        let pattern = if options.regex {
+            options.query.clone()
+        } else {
+            regex::escape(&options.query)
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .size_limit(1 << 20)
+            .build()
+            .map(|regex| Some(Matcher { regex }))
+            .map_err(|e| match e {
+                regex::Error::Syntax(message) => message.lines().last().unwrap_or("invalid regex").trim().to_string(),
+                other => other.to_string(),
+            })
+    }
+
+    /// Byte ranges of every non-empty match in `text`.
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        self.regex
+            .find_iter(text)
+            .filter(|found| !found.is_empty())
+            .map(|found| found.range())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str, regex: bool, case_sensitive: bool) -> Result<Option<Matcher>, String> {
+        Matcher::new(&SearchOptions {
+            query: query.to_string(),
+            regex,
+            case_sensitive,
+        })
+    }
+
+    #[test]
+    fn test_plain_text_is_not_a_pattern() {
+        let matcher = search("[error]", false, false).unwrap().unwrap();
+        assert_eq!(matcher.find_all("2025/08/12 [ERROR] 12#0 and [error] again"), vec![11..18, 28..35]);
+        assert!(matcher.find_all("error").is_empty());
+    }
+
+    #[test]
+    fn test_case_sensitivity() {
+        let matcher = search("InnoDB", false, true).unwrap().unwrap();
+        assert_eq!(matcher.find_all("innodb InnoDB"), vec![7..13]);
+    }
+
+    #[test]
+    fn test_regex() {
+        let matcher = search(r"port \d+", true, false).unwrap().unwrap();
+        assert_eq!(matcher.find_all("Bind on TCP/IP Port 3306"), vec![15..24]);
+        assert!(search("a*", true, false).unwrap().unwrap().find_all("bbb").is_empty());
+    }
+
+    #[test]
+    fn test_empty_and_invalid_queries() {
+        assert!(search("", true, false).unwrap().is_none());
+        assert!(search("(unclosed", true, false).is_err());
+        assert!(search("(unclosed", false, false).unwrap().is_some());
+    }
+}
@@ -0,0 +1,467 @@
+//! Service output on disk, one directory per service:
+//!
+//! - `logs/<service>/<date>.log`: today's output, one entry per line
+//! - `logs/<service>/<date>.<n>.log.gz`: parts rotated out for size
+//! - `logs/<service>/<date>.log.gz`: earlier days
+//!
+//! Each line is `<RFC 3339 timestamp> <stream> <message>`. Output of detached
+//! services is written by the process itself and has no prefix. Such a
+//! process keeps the file of the day it started until it exits; a
+//! `<date>.log.pid` next to the file names it, so maintenance leaves the file
+//! in place and rotates it for size by copying and truncating it.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local, NaiveDate};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use once_cell::sync::OnceCell;
+
+use super::{ansi, LogEntry, LogLevel, LogStream};
+use crate::config::LogsConfig;
+use crate::services::platform;
+use crate::services::runfiles::slug;
+
+const LOG_DIR: &str = "logs";
+
+static SETTINGS: OnceCell<LogsConfig> = OnceCell::new();
+
+/// Serializes compression and cleanup so two writers never gzip the same file.
+static MAINTENANCE: Mutex<()> = Mutex::new(());
+
+/// Sets rotation limits for the rest of the process. Only the first call
+/// has an effect.
+pub fn configure(settings: &LogsConfig) {
+    let _ = SETTINGS.set(settings.clone());
+}
+
+fn settings() -> LogsConfig {
+    SETTINGS.get().cloned().unwrap_or_default()
+}
+
+pub fn service_dir(service: &str) -> PathBuf {
+    PathBuf::from(LOG_DIR).join(slug(service))
+}
+
+fn file_name(date: NaiveDate) -> String {
+    format!("{}.log", date.format("%Y-%m-%d"))
+}
+
+/// Opens today's file for a process that writes its output itself.
+pub fn open_detached(service: &str) -> Result<(File, PathBuf), String> {
+    let dir = service_dir(service);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let path = dir.join(file_name(Local::now().date_naive()));
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map(|file| (file, path.clone()))
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))
+}
+
+/// Records that `pid` writes to the detached log at `path`, so the file is
+/// not compressed or deleted while the process is alive.
+pub fn hold_detached(path: &Path, pid: u32) -> Result<(), String> {
+    let holder = holder_path(path);
+    std::fs::write(&holder, pid.to_string()).map_err(|e| format!("Failed to write {}: {e}", holder.display()))
+}
+
+fn holder_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".pid");
+    PathBuf::from(name)
+}
+
+/// Whether a live detached process writes to `path`. Records of processes
+/// that have exited are removed.
+fn is_held(path: &Path) -> bool {
+    let holder = holder_path(path);
+    let Ok(pid) = std::fs::read_to_string(&holder) else {
+        return false;
+    };
+    if pid.trim().parse().is_ok_and(platform::is_alive) {
+        return true;
+    }
+    let _ = std::fs::remove_file(&holder);
+    false
+}
+
+/// Moves `current` aside as the next numbered part of `day` and returns the
+/// part. A file held by a detached process is copied and truncated instead,
+/// since the process keeps writing to it.
+fn rotate_file(dir: &Path, current: &Path, day: &str) -> Option<PathBuf> {
+    let part = (1..)
+        .map(|n| dir.join(format!("{day}.{n}.log")))
+        .find(|path| !path.exists() && !gz_path(path).exists())?;
+    if !is_held(current) {
+        return std::fs::rename(current, &part).ok().map(|_| part);
+    }
+
+    std::fs::copy(current, &part).ok()?;
+    OpenOptions::new().write(true).open(current).and_then(|file| file.set_len(0)).ok()?;
+    Some(part)
+}
+
+/// Appends entries to a service's current log file, rotating it when the
+/// day changes or it grows too large.
+pub struct LogWriter {
+    dir: PathBuf,
+    settings: LogsConfig,
+    file: Option<File>,
+    date: Option<NaiveDate>,
+    size: u64,
+}
+
+impl LogWriter {
+    pub fn open(service: &str) -> Self {
+        Self::in_dir(service_dir(service), settings())
+    }
+
+    fn in_dir(dir: PathBuf, settings: LogsConfig) -> Self {
+        let writer = LogWriter {
+            dir,
+            settings,
+            file: None,
+            date: None,
+            size: 0,
+        };
+        writer.maintain_in_background(None);
+        writer
+    }
+
+    pub fn write(&mut self, entry: &LogEntry) {
+        let line = format_line(entry);
+        let date = entry.timestamp.date_naive();
+
+        if self.date != Some(date) {
+            if self.date.is_some() {
+                self.file = None;
+                self.maintain_in_background(None);
+            }
+            self.date = Some(date);
+        }
+
+        let max_size = self.settings.max_size_mb.saturating_mul(1024 * 1024);
+        if max_size > 0 && self.size > 0 && self.size + line.len() as u64 > max_size {
+            self.rotate(date);
+        }
+
+        if self.file.is_none() {
+            self.file = self.open_file(date);
+        }
+        if let Some(file) = &mut self.file {
+            match file.write_all(line.as_bytes()) {
+                Ok(()) => self.size += line.len() as u64,
+                Err(_) => self.file = None,
+            }
+        }
+    }
+
+    fn open_file(&mut self, date: NaiveDate) -> Option<File> {
+        std::fs::create_dir_all(&self.dir).ok()?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(file_name(date)))
+            .ok()?;
+        self.size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Some(file)
+    }
+
+    /// Moves the current file aside as the next numbered part of `date`.
+    fn rotate(&mut self, date: NaiveDate) {
+        self.file = None;
+        self.size = 0;
+
+        let current = self.dir.join(file_name(date));
+        let day = date.format("%Y-%m-%d").to_string();
+        if let Some(part) = rotate_file(&self.dir, &current, &day) {
+            self.maintain_in_background(Some(part));
+        }
+    }
+
+    fn maintain_in_background(&self, rotated: Option<PathBuf>) {
+        let dir = self.dir.clone();
+        let settings = self.settings.clone();
+        std::thread::spawn(move || maintain(&dir, &settings, rotated.as_deref(), Local::now().date_naive()));
+    }
+}
+
+/// Compresses `rotated` and files from before `today`, and deletes files
+/// older than the configured age. Files of live detached processes are only
+/// rotated for size.
+fn maintain(dir: &Path, settings: &LogsConfig, rotated: Option<&Path>, today: NaiveDate) {
+    let _guard = MAINTENANCE.lock();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let max_age = Duration::from_secs(settings.max_age_days.saturating_mul(24 * 60 * 60));
+    let max_size = settings.max_size_mb.saturating_mul(1024 * 1024);
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(log) = name.strip_suffix(".pid") {
+            // Drops the record once its process has exited.
+            is_held(&dir.join(log));
+            continue;
+        }
+        if name.ends_with(".log") && is_held(&path) {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            if max_size > 0 && size > max_size {
+                if let Some(part) = name.get(..10).and_then(|day| rotate_file(dir, &path, day)) {
+                    if settings.compress {
+                        let _ = compress(&part);
+                    }
+                }
+            }
+            continue;
+        }
+
+        let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok();
+        let age = modified.and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if settings.max_age_days > 0 && age.is_some_and(|age| age > max_age) {
+            let _ = std::fs::remove_file(&path);
+            continue;
+        }
+
+        let earlier_day = name
+            .get(..10)
+            .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+            .is_some_and(|day| day < today);
+        let finished = rotated == Some(path.as_path()) || earlier_day;
+        if settings.compress && name.ends_with(".log") && finished {
+            let _ = compress(&path);
+        }
+    }
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// Replaces `path` with a gzipped copy, keeping its modification time so
+/// age-based cleanup still sees when it was last written.
+fn compress(path: &Path) -> std::io::Result<()> {
+    let target = gz_path(path);
+    let partial = target.with_extension("gz.partial");
+    let modified = std::fs::metadata(path)?.modified()?;
+
+    let mut encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    let file = encoder.finish()?;
+    file.set_modified(modified)?;
+    drop(file);
+
+    std::fs::rename(&partial, &target)?;
+    std::fs::remove_file(path)
+}
+
+fn format_line(entry: &LogEntry) -> String {
+    let message = entry.message.replace('\n', " ");
+    format!(
+        "{} {} {message}\n",
+        entry.timestamp.format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+        entry.stream.label()
+    )
+}
+
+/// Parses a line written by `LogWriter`. Lines without a timestamp, such as
+/// those of detached services, are stdout at `fallback`. Detached services
+/// also write raw escape sequences, which are parsed here.
+fn parse_line(service: &str, line: &str, fallback: DateTime<Local>) -> LogEntry {
+    let parsed = line.split_once(' ').and_then(|(timestamp, rest)| {
+        let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?.with_timezone(&Local);
+        let (stream, message) = rest.split_once(' ').unwrap_or((rest, ""));
+        let stream = [LogStream::Stdout, LogStream::Stderr, LogStream::System]
+            .into_iter()
+            .find(|candidate| candidate.label() == stream)?;
+        Some((timestamp, stream, message))
+    });
+    let (timestamp, stream, message) = parsed.unwrap_or((fallback, LogStream::Stdout, line));
+    let (message, spans) = ansi::parse(message);
+
+    LogEntry {
+        timestamp,
+        service: Some(service.to_string()),
+        stream,
+        level: LogLevel::detect(&message),
+        message,
+        spans,
+    }
+}
+
+/// A log file of a service, current or rotated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub modified: DateTime<Local>,
+    pub size: u64,
+}
+
+impl LogFile {
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.path.extension().is_some_and(|ext| ext == "gz")
+    }
+}
+
+/// The service's log files, most recently written first.
+pub fn files(service: &str) -> Vec<LogFile> {
+    files_in(&service_dir(service))
+}
+
+fn files_in(dir: &Path) -> Vec<LogFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<LogFile> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.ends_with(".log") || name.ends_with(".log.gz")
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(LogFile {
+                path: entry.path(),
+                modified: DateTime::<Local>::from(metadata.modified().ok()?),
+                size: metadata.len(),
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| b.path.cmp(&a.path)));
+    files
+}
+
+/// The file a running service is writing to, if it has one. Numbered parts
+/// that were just rotated out do not count.
+pub fn current_file(service: &str) -> Option<PathBuf> {
+    files(service)
+        .into_iter()
+        .find(|file| {
+            let name = file.name();
+            name.strip_suffix(".log")
+                .is_some_and(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").is_ok())
+        })
+        .map(|file| file.path)
+}
+
+/// Reads every entry of a log file, decompressing it if needed.
+pub fn read(service: &str, file: &LogFile) -> Result<Vec<LogEntry>, String> {
+    let handle = File::open(&file.path).map_err(|e| format!("Failed to open {}: {e}", file.path.display()))?;
+    let reader: Box<dyn Read> = if file.is_compressed() {
+        Box::new(GzDecoder::new(handle))
+    } else {
+        Box::new(handle)
+    };
+
+    let mut entries: Vec<LogEntry> = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line.map_err(|e| format!("Failed to read {}: {e}", file.path.display()))?;
+        let fallback = entries.last().map(|entry| entry.timestamp).unwrap_or(file.modified);
+        entries.push(parse_line(service, &line, fallback));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(max_size_mb: u64) -> LogsConfig {
+        LogsConfig { max_size_mb, ..LogsConfig::default() }
+    }
+
+    #[test]
+    fn test_line_round_trip() {
+        let entry = LogEntry::output("MariaDB", LogStream::Stderr, "[ERROR] Aborted");
+        let line = format_line(&entry);
+        let parsed = parse_line("MariaDB", line.trim_end(), Local::now());
+        assert_eq!(parsed.stream, LogStream::Stderr);
+        assert_eq!(parsed.level, LogLevel::Error);
+        assert_eq!(parsed.message, "[ERROR] Aborted");
+        assert_eq!(parsed.timestamp.timestamp_millis(), entry.timestamp.timestamp_millis());
+    }
+
+    #[test]
+    fn test_unprefixed_lines_use_fallback_time() {
+        let fallback = Local::now();
+        let parsed = parse_line("Nginx", "2025/08/12 10:00:00 [notice] start worker", fallback);
+        assert_eq!(parsed.timestamp, fallback);
+        assert_eq!(parsed.stream, LogStream::Stdout);
+        assert_eq!(parsed.message, "2025/08/12 10:00:00 [notice] start worker");
+    }
+
+    #[test]
+    fn test_size_rotation_compresses_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = LogWriter::in_dir(dir.path().to_path_buf(), settings(1));
+        let line = "x".repeat(1000);
+        for _ in 0..1500 {
+            writer.write(&LogEntry::output("Fake", LogStream::Stdout, line.as_str()));
+        }
+        drop(writer);
+
+        let today = Local::now().date_naive();
+        let day = today.format("%Y-%m-%d");
+        maintain(dir.path(), &settings(1), Some(&dir.path().join(format!("{day}.1.log"))), today);
+
+        let files = files_in(dir.path());
+        let names: Vec<String> = files.iter().map(LogFile::name).collect();
+        assert!(names.contains(&format!("{day}.log")), "{names:?}");
+        assert!(names.contains(&format!("{day}.1.log.gz")), "{names:?}");
+
+        let total: usize = files.iter().map(|file| read("Fake", file).unwrap().len()).sum();
+        assert_eq!(total, 1500);
+    }
+
+    #[test]
+    fn test_earlier_days_are_compressed_and_expired_files_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let today = Local::now().date_naive();
+        std::fs::write(dir.path().join(file_name(today)), "today\n").unwrap();
+        std::fs::write(dir.path().join("2020-01-01.log"), "old\n").unwrap();
+        let expired = dir.path().join("2019-01-01.log.gz");
+        File::create(&expired)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60))
+            .unwrap();
+
+        maintain(dir.path(), &settings(10), None, today);
+
+        assert!(dir.path().join(file_name(today)).exists());
+        assert!(dir.path().join("2020-01-01.log.gz").exists());
+        assert!(!dir.path().join("2020-01-01.log").exists());
+        assert!(!expired.exists());
+    }
+
+    #[test]
+    fn test_files_of_live_detached_processes_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let today = Local::now().date_naive();
+        let held = dir.path().join("2020-01-01.log");
+        std::fs::write(&held, "x".repeat(2 * 1024 * 1024)).unwrap();
+        hold_detached(&held, std::process::id()).unwrap();
+
+        maintain(dir.path(), &settings(1), None, today);
+
+        // Rotated for size by copying, but the file itself stays in place.
+        assert_eq!(std::fs::metadata(&held).unwrap().len(), 0);
+        assert!(dir.path().join("2020-01-01.1.log.gz").exists());
+        assert!(holder_path(&held).exists());
+        assert_eq!(files_in(dir.path()).len(), 2);
+    }
+}
@@ -1,16 +1,57 @@
 #![windows_subsystem = "windows"]
 
+use std::process::ExitCode;
 use std::sync::Arc;
+use clap::Parser;
 use eframe::egui;
 
 use crate::utils::load_icon::load_icon_from_file;
 
+mod api;
 mod app;
+mod cli;
+mod config;
+mod daemon;
+mod database;
+mod logs;
+mod php;
 mod services;
+mod shell;
+mod sites;
 mod ui;
 mod utils;
 
-fn main() -> eframe::Result {
+fn main() -> ExitCode {
+    attach_parent_console();
+
+    let cli = cli::Cli::parse();
+    if cli.is_headless() {
+        return cli::run(cli);
+    }
+
+    match run_gui() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to start Xerve: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The release binary uses the Windows GUI subsystem, so it has no console of
+/// its own; borrow the launching terminal's so CLI output is visible.
+#[cfg(windows)]
+fn attach_parent_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_parent_console() {}
+
+fn run_gui() -> eframe::Result {
     env_logger::init();
     let icon = load_icon_from_file("docs/logo.png").map(Arc::new);
     let mut viewport = egui::ViewportBuilder::default()
@@ -31,7 +72,17 @@ fn main() -> eframe::Result {
             cc.egui_ctx.set_visuals(egui::Visuals::dark());
             egui_extras::install_image_loaders(&cc.egui_ctx);
             let app = app::XerveApp::default();
-            services::set_terminal(app.get_terminal());
+            let terminal = app.get_terminal();
+
+            // Record state transitions and repaint, since they happen on background threads.
+            let ctx = cc.egui_ctx.clone();
+            let events = services::subscribe();
+            std::thread::spawn(move || {
+                for event in events {
+                    terminal.add_entry(logs::LogEntry::event(&event));
+                    ctx.request_repaint();
+                }
+            });
             Ok(Box::new(app))
         }),
     )
@@ -0,0 +1,290 @@
+//! Editing `php.ini` in place. Lines are kept as they are, comments
+//! included; only the lines of the settings that change are rewritten.
+
+use std::path::{Path, PathBuf};
+
+/// Extensions loaded with `zend_extension` rather than `extension`.
+const ZEND_EXTENSIONS: &[&str] = &["opcache", "xdebug"];
+
+pub struct PhpIni {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl PhpIni {
+    /// Reads `php.ini` from `dir`, starting from `php.ini-development` when
+    /// the install has no `php.ini` yet. Saving writes `php.ini` either way.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join("php.ini");
+        let source = [path.clone(), dir.join("php.ini-development"), dir.join("php.ini-production")]
+            .into_iter()
+            .find(|candidate| candidate.is_file());
+        let contents = match source {
+            Some(source) => std::fs::read_to_string(&source).map_err(|e| format!("Failed to read {}: {e}", source.display()))?,
+            None => String::new(),
+        };
+        Ok(Self::parse(path, &contents))
+    }
+
+    pub fn parse(path: PathBuf, contents: &str) -> Self {
+        PhpIni {
+            path,
+            lines: contents.lines().map(str::to_string).collect(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contents(&self) -> String {
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+        contents
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        std::fs::write(&self.path, self.contents()).map_err(|e| format!("Failed to write {}: {e}", self.path.display()))
+    }
+
+    /// The value of `key`; PHP uses the last assignment.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lines
+            .iter()
+            .rev()
+            .filter_map(|line| assignment(line))
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| unquote(value).to_string())
+    }
+
+    /// Sets `key`, rewriting its last assignment, else uncommenting its
+    /// commented-out default, else appending it.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{key} = {value}");
+        if let Some(index) = self.position(|name, _| name.eq_ignore_ascii_case(key)) {
+            self.lines[index] = line;
+        } else if let Some(index) = self.commented_position(|name, _| name.eq_ignore_ascii_case(key)) {
+            self.lines[index] = line;
+        } else {
+            let index = self.php_section_end();
+            self.lines.insert(index, line);
+        }
+    }
+
+    /// Whether extension `name` (`curl`, not `php_curl.dll`) is loaded.
+    pub fn extension_enabled(&self, name: &str) -> bool {
+        self.position(|key, value| is_extension_key(key) && extension_name(value) == name).is_some()
+    }
+
+    pub fn set_extension(&mut self, name: &str, enabled: bool) {
+        let matches = |key: &str, value: &str| is_extension_key(key) && extension_name(value) == name;
+        if !enabled {
+            for line in &mut self.lines {
+                if assignment(line).is_some_and(|(key, value)| matches(key, value)) {
+                    *line = format!(";{line}");
+                }
+            }
+            return;
+        }
+        if self.extension_enabled(name) {
+            return;
+        }
+
+        let directive = if ZEND_EXTENSIONS.contains(&name.split('-').next().unwrap_or(name)) {
+            "zend_extension"
+        } else {
+            "extension"
+        };
+        let line = format!("{directive}={name}");
+        match self.commented_position(matches) {
+            Some(index) => self.lines[index] = line,
+            None => {
+                // Next to the other extensions, where there are any.
+                let index = self
+                    .lines
+                    .iter()
+                    .rposition(|line| assignment(line).is_some_and(|(key, _)| is_extension_key(key)))
+                    .map_or(self.lines.len(), |index| index + 1);
+                self.lines.insert(index, line);
+            }
+        }
+    }
+
+    /// Where a new setting goes: after the last line of the `[PHP]` section,
+    /// or ahead of the first section in a file without one, so that it isn't
+    /// taken for an extension's setting such as `[opcache]`.
+    fn php_section_end(&self) -> usize {
+        let start = self
+            .lines
+            .iter()
+            .position(|line| line.trim().eq_ignore_ascii_case("[PHP]"))
+            .map_or(0, |index| index + 1);
+        let end = self.lines[start..]
+            .iter()
+            .position(|line| line.trim_start().starts_with('['))
+            .map_or(self.lines.len(), |offset| start + offset);
+        self.lines[start..end]
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .map_or(start, |offset| start + offset + 1)
+    }
+
+    fn position(&self, matches: impl Fn(&str, &str) -> bool) -> Option<usize> {
+        self.lines
+            .iter()
+            .rposition(|line| assignment(line).is_some_and(|(key, value)| matches(key, value)))
+    }
+
+    /// A `;key = value` line, skipping prose comments that merely mention it.
+    fn commented_position(&self, matches: impl Fn(&str, &str) -> bool) -> Option<usize> {
+        self.lines.iter().position(|line| {
+            let Some(commented) = line.trim_start().strip_prefix(';') else {
+                return false;
+            };
+            assignment(commented).is_some_and(|(key, value)| !key.contains(' ') && matches(key, value))
+        })
+    }
+}
+
+/// `(key, value)` for an active `key = value` line.
+fn assignment(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with([';', '#', '[']) {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    let value = match value.find(" ;") {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    Some((key.trim(), value.trim()))
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn is_extension_key(key: &str) -> bool {
+    key == "extension" || key == "zend_extension"
+}
+
+/// `curl` for `php_curl.dll`, `curl.so`, `"curl"` or a full path to one of them.
+pub fn extension_name(value: &str) -> String {
+    let file = unquote(value.trim()).rsplit(['/', '\\']).next().unwrap_or_default();
+    let stem = file.strip_suffix(".dll").or_else(|| file.strip_suffix(".so")).unwrap_or(file);
+    stem.strip_prefix("php_").unwrap_or(stem).to_string()
+}
+
+/// Extensions shipped in the install's `ext/` directory, sorted by name.
+pub fn available_extensions(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir.join("ext"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| name.ends_with(".dll") || name.ends_with(".so"))
+                .map(|name| extension_name(&name))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Whether `value` is a PHP size such as `128M`, `2G`, `512` or `-1`.
+pub fn is_size(value: &str) -> bool {
+    let value = value.trim();
+    if value == "-1" {
+        return true;
+    }
+    let digits = value.strip_suffix(['K', 'M', 'G', 'k', 'm', 'g']).unwrap_or(value);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// PHP's reading of a boolean setting such as `display_errors`.
+pub fn is_on(value: &str) -> bool {
+    matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "on" | "yes" | "true" | "stdout" | "stderr")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INI: &str = "[PHP]\n\
+        ; Maximum amount of memory a script may consume\n\
+        memory_limit = 128M\n\
+        display_errors = Off ; overridden below\n\
+        display_errors = On\n\
+        ;upload_max_filesize = 2M\n\
+        \n\
+        ; Notes: extension=mysqli loads mysqli\n\
+        extension=curl\n\
+        ;extension=php_mbstring.dll\n\
+        extension=\"C:\\php\\ext\\php_openssl.dll\"\n\
+        \n\
+        [opcache]\n\
+        ;zend_extension=opcache\n";
+
+    fn ini() -> PhpIni {
+        PhpIni::parse(PathBuf::from("php.ini"), INI)
+    }
+
+    #[test]
+    fn test_get_and_set_keep_comments() {
+        let mut ini = ini();
+        assert_eq!(ini.get("memory_limit").as_deref(), Some("128M"));
+        assert_eq!(ini.get("display_errors").as_deref(), Some("On"));
+        assert_eq!(ini.get("upload_max_filesize"), None);
+
+        ini.set("memory_limit", "512M");
+        ini.set("display_errors", "Off");
+        ini.set("upload_max_filesize", "64M");
+        ini.set("date.timezone", "UTC");
+
+        let contents = ini.contents();
+        assert!(contents.contains("; Maximum amount of memory a script may consume\nmemory_limit = 512M\n"));
+        assert!(contents.contains("display_errors = Off ; overridden below\ndisplay_errors = Off\n"));
+        assert!(contents.contains("\nupload_max_filesize = 64M\n"));
+        assert!(contents.contains("php_openssl.dll\"\ndate.timezone = UTC\n\n[opcache]\n"));
+        assert_eq!(ini.get("upload_max_filesize").as_deref(), Some("64M"));
+
+        let mut sections = PhpIni::parse(PathBuf::from("php.ini"), "; Xerve\n[opcache]\nopcache.enable=1\n");
+        sections.set("memory_limit", "256M");
+        assert_eq!(sections.contents(), "; Xerve\nmemory_limit = 256M\n[opcache]\nopcache.enable=1\n");
+    }
+
+    #[test]
+    fn test_toggle_extensions() {
+        let mut ini = ini();
+        assert!(ini.extension_enabled("curl"));
+        assert!(ini.extension_enabled("openssl"));
+        assert!(!ini.extension_enabled("mbstring"));
+        assert!(!ini.extension_enabled("mysqli"));
+
+        ini.set_extension("mbstring", true);
+        ini.set_extension("curl", false);
+        ini.set_extension("mysqli", true);
+        ini.set_extension("xdebug", true);
+        ini.set_extension("opcache", true);
+
+        let contents = ini.contents();
+        assert!(contents.contains("; Notes: extension=mysqli loads mysqli\n;extension=curl\nextension=mbstring\n"));
+        assert!(contents.contains("php_openssl.dll\"\nextension=mysqli\nzend_extension=xdebug\n"));
+        assert!(contents.contains("[opcache]\nzend_extension=opcache\n"));
+        assert!(!ini.extension_enabled("curl"));
+        assert!(ini.extension_enabled("xdebug"));
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(extension_name("php_xdebug-3.3.2-8.4-vs17-x86_64.dll"), "xdebug-3.3.2-8.4-vs17-x86_64");
+        assert_eq!(extension_name("/usr/lib/php/pdo_mysql.so"), "pdo_mysql");
+        assert!(is_size("512M") && is_size("-1") && is_size("1024") && is_size("2g"));
+        assert!(!is_size("512MB") && !is_size("M") && !is_size("lots"));
+        assert!(is_on("On") && is_on("1") && !is_on("Off") && !is_on(""));
+    }
+}
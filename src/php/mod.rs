@@ -0,0 +1,438 @@
+//! PHP installs side by side. Every `php-<version>` directory under the
+//! configured `dir` is one install with its own FastCGI port, served by a
+//! `PHP <major.minor>` service running PHP-FPM when the install has it and
+//! php-cgi otherwise.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{
+    absolute_path, default_stop_timeout, HealthConfig, PhpConfig, Readiness, RestartConfig, RestartPolicy,
+    ServiceConfig, XerveConfig,
+};
+use crate::services::runfiles;
+
+pub mod ini;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhpVersion {
+    /// Full version from the directory name, e.g. `8.4.11`.
+    pub version: String,
+    pub dir: PathBuf,
+    numbers: Vec<u32>,
+}
+
+impl PhpVersion {
+    /// Parses a directory called `php-<version>`.
+    fn from_dir(dir: &Path) -> Option<PhpVersion> {
+        let version = dir.file_name()?.to_str()?.strip_prefix("php-")?.to_string();
+        let numbers = version.split('.').map(str::parse).collect::<Result<Vec<u32>, _>>().ok()?;
+        if numbers.len() < 2 {
+            return None;
+        }
+        Some(PhpVersion {
+            version,
+            dir: dir.to_path_buf(),
+            numbers,
+        })
+    }
+
+    /// `8.4` for PHP 8.4.11; sites and the default version refer to this.
+    pub fn short(&self) -> String {
+        format!("{}.{}", self.numbers[0], self.numbers[1])
+    }
+
+    /// Whether `version`, short or full, names this install.
+    pub fn matches(&self, version: &str) -> bool {
+        let version = version.trim();
+        version == self.version || version == self.short()
+    }
+
+    /// The install's own `php-cgi`; unlike service executables it is never
+    /// looked up on PATH, which could hold another version.
+    pub fn cgi(&self) -> PathBuf {
+        self.dir.join(format!("php-cgi{}", std::env::consts::EXE_SUFFIX))
+    }
+
+    /// PHP-FPM, where the install ships it.
+    pub fn fpm(&self) -> Option<PathBuf> {
+        let name = format!("php-fpm{}", std::env::consts::EXE_SUFFIX);
+        [self.dir.join("sbin").join(&name), self.dir.join(&name)]
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+
+    pub fn ini(&self) -> PathBuf {
+        self.dir.join("php.ini")
+    }
+
+    /// Name of the service that runs this version.
+    pub fn service_name(&self) -> String {
+        format!("PHP {}", self.short())
+    }
+}
+
+/// The discovered installs, oldest first, and the default among them.
+#[derive(Debug, Clone, Default)]
+pub struct PhpVersions {
+    config: PhpConfig,
+    versions: Vec<PhpVersion>,
+}
+
+impl PhpVersions {
+    pub fn discover(config: &PhpConfig) -> Self {
+        let mut versions: Vec<PhpVersion> = std::fs::read_dir(absolute_path(&config.dir))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .filter_map(|path| PhpVersion::from_dir(&path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        versions.sort_by(|a, b| a.numbers.cmp(&b.numbers));
+        // One backend per minor version: the newest patch release wins.
+        versions.dedup_by(|newer, older| {
+            let same = newer.short() == older.short();
+            if same {
+                std::mem::swap(newer, older);
+            }
+            same
+        });
+        PhpVersions {
+            config: config.clone(),
+            versions,
+        }
+    }
+
+    pub fn config(&self) -> &PhpConfig {
+        &self.config
+    }
+
+    pub fn all(&self) -> &[PhpVersion] {
+        &self.versions
+    }
+
+    pub fn find(&self, version: &str) -> Option<&PhpVersion> {
+        self.versions.iter().find(|php| php.matches(version))
+    }
+
+    /// The configured default version, else the newest install.
+    pub fn default_version(&self) -> Option<&PhpVersion> {
+        match &self.config.default_version {
+            Some(version) => self.find(version),
+            None => self.versions.last(),
+        }
+    }
+
+    /// The default version listens on `base_port`, where the main
+    /// `nginx.conf` expects PHP; the others on `base_port + 10 * major +
+    /// minor`. Loading a config checks these with [`Self::check_ports`].
+    pub fn port(&self, php: &PhpVersion) -> u16 {
+        self.checked_port(php).unwrap_or(u16::MAX)
+    }
+
+    /// `None` when the port would be past 65535.
+    fn checked_port(&self, php: &PhpVersion) -> Option<u16> {
+        if self.default_version() == Some(php) {
+            return Some(self.config.base_port);
+        }
+        let offset = php.numbers[0].checked_mul(10)?.checked_add(php.numbers[1])?;
+        self.config.base_port.checked_add(u16::try_from(offset).ok()?)
+    }
+
+    /// Makes sure every install gets a port of its own that no service in
+    /// `declared` probes, so no two backends try to bind the same one.
+    pub fn check_ports(&self, declared: &[ServiceConfig]) -> Result<(), String> {
+        let mut taken: Vec<(u16, String)> = declared
+            .iter()
+            .filter(|service| !self.versions.iter().any(|php| service.name.eq_ignore_ascii_case(&php.service_name())))
+            .filter_map(|service| match &service.readiness {
+                Readiness::Tcp { port, .. } | Readiness::Mysql { port, .. } => Some((*port, service.name.clone())),
+                _ => None,
+            })
+            .collect();
+
+        for php in &self.versions {
+            let port = self.checked_port(php).ok_or_else(|| {
+                format!(
+                    "{} would listen past port 65535; lower [php] base_port ({})",
+                    php.service_name(),
+                    self.config.base_port
+                )
+            })?;
+            if let Some((_, other)) = taken.iter().find(|(used, _)| *used == port) {
+                return Err(format!(
+                    "{} and {other} would both use port {port}; set [php] base_port or default_version so they differ",
+                    php.service_name()
+                ));
+            }
+            taken.push((port, php.service_name()));
+        }
+        Ok(())
+    }
+
+    /// Address `php`'s FastCGI backend listens on.
+    pub fn address(&self, php: &PhpVersion) -> String {
+        format!("{}:{}", self.config.host, self.port(php))
+    }
+
+    /// Adds a service for every install to `config`, unless one is declared
+    /// by hand, and has the web server serving the sites start them first.
+    /// Fails when two installs, or an install and a declared service, would
+    /// share a port.
+    pub fn add_services(&self, config: &mut XerveConfig) -> Result<(), String> {
+        self.check_ports(&config.services)?;
+        let generated = self.services(&config.services);
+        config.services.extend(generated);
+
+        let names: Vec<String> = self
+            .versions
+            .iter()
+            .map(PhpVersion::service_name)
+            .filter_map(|name| config.services.iter().find(|service| service.name.eq_ignore_ascii_case(&name)))
+            .map(|service| service.name.clone())
+            .collect();
+        let Some(web) = config.services.iter_mut().find(|service| service.name.eq_ignore_ascii_case(&config.sites.service)) else {
+            return Ok(());
+        };
+        for name in names {
+            if !web.depends_on.iter().any(|dependency| dependency.eq_ignore_ascii_case(&name)) {
+                web.depends_on.push(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// A service for every install that has php-cgi or PHP-FPM and isn't
+    /// among the `declared` services.
+    pub fn services(&self, declared: &[ServiceConfig]) -> Vec<ServiceConfig> {
+        self.services_in(declared, &runfiles::php_fpm_config_path)
+    }
+
+    fn services_in(&self, declared: &[ServiceConfig], fpm_config: &dyn Fn(&str) -> PathBuf) -> Vec<ServiceConfig> {
+        self.versions
+            .iter()
+            .filter(|php| !declared.iter().any(|service| service.name.eq_ignore_ascii_case(&php.service_name())))
+            .filter_map(|php| self.service(php, &fpm_config(&php.service_name())))
+            .collect()
+    }
+
+    fn service(&self, php: &PhpVersion, fpm_config: &Path) -> Option<ServiceConfig> {
+        let address = self.address(php);
+        let ini = php.ini().to_string_lossy().into_owned();
+        let mut env = BTreeMap::new();
+
+        let mut files = Vec::new();
+
+        let (executable, args, process_name) = match php.fpm() {
+            Some(fpm) => {
+                files.push((fpm_config.to_path_buf(), self.fpm_pool(php)));
+                (
+                    fpm,
+                    vec![
+                        "--nodaemonize".to_string(),
+                        "--allow-to-run-as-root".to_string(),
+                        "--fpm-config".to_string(),
+                        absolute_path(&fpm_config.to_string_lossy()).to_string_lossy().into_owned(),
+                        "-c".to_string(),
+                        ini,
+                    ],
+                    "php-fpm",
+                )
+            }
+            None if php.cgi().is_file() => {
+                // php-cgi forks and recycles its own workers; Windows builds
+                // ignore PHP_FCGI_CHILDREN and serve from a single process,
+                // which `XerveConfig::deprecations` warns about.
+                env.insert("PHP_FCGI_CHILDREN".to_string(), self.config.workers.to_string());
+                env.insert("PHP_FCGI_MAX_REQUESTS".to_string(), self.config.max_requests.to_string());
+                (php.cgi(), vec!["-b".to_string(), address, "-c".to_string(), ini], "php-cgi")
+            }
+            None => return None,
+        };
+
+        Some(ServiceConfig {
+            name: php.service_name(),
+            executable: executable.to_string_lossy().into_owned(),
+            args,
+            working_dir: Some(php.dir.to_string_lossy().into_owned()),
+            env,
+            pid_file: None,
+            process_names: vec![process_name.to_string()],
+            init: None,
+            stop: None,
+            reload: None,
+            check: None,
+            readiness: Readiness::Tcp {
+                host: self.config.host.clone(),
+                port: self.port(php),
+            },
+            health: HealthConfig::default(),
+            stop_timeout_secs: default_stop_timeout(),
+            // A single php-cgi exits after PHP_FCGI_MAX_REQUESTS; that clean
+            // exit is planned and restarts at once, without backoff.
+            restart: RestartConfig {
+                policy: RestartPolicy::Recycle,
+                ..RestartConfig::default()
+            },
+            depends_on: Vec::new(),
+            database: None,
+            files,
+        })
+    }
+
+    /// PHP-FPM pool for `php`, written each time the service starts.
+    fn fpm_pool(&self, php: &PhpVersion) -> String {
+        format!(
+            "; Generated by Xerve for {}; changes are overwritten.\n\
+             [global]\n\
+             error_log = /dev/stderr\n\
+             daemonize = no\n\
+             \n\
+             [xerve]\n\
+             listen = {}\n\
+             pm = static\n\
+             pm.max_children = {}\n\
+             pm.max_requests = {}\n\
+             catch_workers_output = yes\n",
+            php.service_name(),
+            self.address(php),
+            self.config.workers.max(1),
+            self.config.max_requests
+        )
+    }
+
+    /// FastCGI address for `version`, or for the default version.
+    pub fn fastcgi(&self, version: Option<&str>) -> Result<String, String> {
+        let php = match version {
+            Some(version) => self.find(version).ok_or_else(|| format!("PHP {version} is not installed"))?,
+            None => self.default_version().ok_or_else(|| match &self.config.default_version {
+                Some(version) => format!("The default PHP version {version} is not installed"),
+                None => format!("No PHP installs found in {}/php-*", self.config.dir),
+            })?,
+        };
+        Ok(self.address(php))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(dir: &Path, names: &[&str]) -> PhpConfig {
+        for name in names {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        std::fs::write(dir.join("php-7.4.zip"), "").unwrap();
+        PhpConfig {
+            dir: dir.to_string_lossy().into_owned(),
+            ..PhpConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_discover_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = install(dir.path(), &["php-8.4.11", "php-8.1.2", "php-8.4.9", "php-8.2.29", "php-next", "nginx"]);
+        let versions = PhpVersions::discover(&config);
+
+        let found: Vec<&str> = versions.all().iter().map(|php| php.version.as_str()).collect();
+        assert_eq!(found, ["8.1.2", "8.2.29", "8.4.11"]);
+        assert_eq!(versions.default_version().map(PhpVersion::short).as_deref(), Some("8.4"));
+        assert_eq!(versions.fastcgi(Some("8.2")).unwrap(), "127.0.0.1:9082");
+        assert_eq!(versions.fastcgi(Some("8.1.2")).unwrap(), "127.0.0.1:9081");
+        assert_eq!(versions.fastcgi(Some("8.4")).unwrap(), "127.0.0.1:9000");
+        assert_eq!(versions.fastcgi(None).unwrap(), "127.0.0.1:9000");
+        assert!(versions.fastcgi(Some("7.4")).is_err());
+
+        let pinned = PhpVersions::discover(&PhpConfig { default_version: Some("8.2".to_string()), ..config.clone() });
+        assert_eq!(pinned.default_version().map(|php| php.version.as_str()), Some("8.2.29"));
+        assert_eq!(pinned.fastcgi(Some("8.2")).unwrap(), "127.0.0.1:9000");
+        assert_eq!(pinned.fastcgi(Some("8.4")).unwrap(), "127.0.0.1:9084");
+        let missing = PhpVersions::discover(&PhpConfig { default_version: Some("5.6".to_string()), ..config });
+        assert_eq!(missing.fastcgi(None).unwrap_err(), "The default PHP version 5.6 is not installed");
+    }
+
+    #[test]
+    fn test_port_collisions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = install(dir.path(), &["php-8.10.0", "php-9.0.1", "php-9.1.0"]);
+        let versions = PhpVersions::discover(&config);
+        assert_eq!(
+            versions.check_ports(&[]).unwrap_err(),
+            "PHP 9.0 and PHP 8.10 would both use port 9090; set [php] base_port or default_version so they differ"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = install(dir.path(), &["php-8.2.0", "php-8.4.0"]);
+        let versions = PhpVersions::discover(&config);
+        assert!(versions.check_ports(&[]).is_ok());
+        let mariadb: ServiceConfig =
+            toml::from_str("name = \"MariaDB\"\nexecutable = \"mariadbd\"\nreadiness = { kind = \"mysql\", port = 9082 }").unwrap();
+        assert!(versions.check_ports(&[mariadb]).unwrap_err().starts_with("PHP 8.2 and MariaDB"));
+
+        let high = PhpVersions::discover(&PhpConfig { base_port: 65500, ..config });
+        assert!(high.check_ports(&[]).unwrap_err().contains("past port 65535"));
+    }
+
+    #[test]
+    fn test_installs_join_the_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let php = install(dir.path(), &["php-8.2.29", "php-8.4.11"]);
+        for version in ["php-8.2.29", "php-8.4.11"] {
+            std::fs::write(dir.path().join(version).join(format!("php-cgi{}", std::env::consts::EXE_SUFFIX)), "").unwrap();
+        }
+        let contents = format!(
+            "[php]\ndir = {:?}\n\n[[services]]\nname = \"nginx\"\nexecutable = \"nginx\"\ndepends_on = [\"php 8.4\"]\n\n[[services]]\nname = \"PHP 8.2\"\nexecutable = \"php-cgi\"\n",
+            php.dir
+        );
+        // Plain parsing leaves the installs out, so the dependency is unknown.
+        assert!(XerveConfig::parse(&contents).unwrap_err().contains("php 8.4"));
+
+        let config = XerveConfig::parse_installed(&contents).unwrap();
+        let names: Vec<&str> = config.services.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(names, ["nginx", "PHP 8.2", "PHP 8.4"]);
+        // The declared PHP 8.2 is kept as written, and both start before Nginx.
+        assert_eq!(config.services[1].executable, "php-cgi");
+        assert_eq!(config.services[0].depends_on, ["php 8.4", "PHP 8.2"]);
+    }
+
+    #[test]
+    fn test_services_for_installs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = install(dir.path(), &["php-8.1.2", "php-8.2.29", "php-8.3.0", "php-8.4.11/sbin"]);
+        let exe = std::env::consts::EXE_SUFFIX;
+        std::fs::write(dir.path().join("php-8.2.29").join(format!("php-cgi{exe}")), "").unwrap();
+        std::fs::write(dir.path().join("php-8.3.0").join(format!("php-cgi{exe}")), "").unwrap();
+        std::fs::write(dir.path().join("php-8.4.11").join("sbin").join(format!("php-fpm{exe}")), "").unwrap();
+
+        let versions = PhpVersions::discover(&config);
+        let declared: [ServiceConfig; 1] = [toml::from_str("name = \"php 8.3\"\nexecutable = \"php-cgi\"").unwrap()];
+        let pools = dir.path().join("run");
+        let services = versions.services_in(&declared, &|name| pools.join(format!("{name}.conf")));
+
+        // 8.1 has neither php-cgi nor PHP-FPM, 8.3 is declared by hand.
+        let names: Vec<&str> = services.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(names, ["PHP 8.2", "PHP 8.4"]);
+
+        let cgi = &services[0];
+        assert_eq!(cgi.args[..2], ["-b", "127.0.0.1:9082"]);
+        assert_eq!(cgi.env.get("PHP_FCGI_CHILDREN").map(String::as_str), Some("4"));
+        assert_eq!(cgi.env.get("PHP_FCGI_MAX_REQUESTS").map(String::as_str), Some("500"));
+        assert_eq!(cgi.readiness, Readiness::Tcp { host: "127.0.0.1".to_string(), port: 9082 });
+        assert_eq!(cgi.restart.policy, RestartPolicy::Recycle);
+
+        let fpm = &services[1];
+        assert!(fpm.executable.ends_with(&format!("php-fpm{exe}")));
+        assert_eq!(fpm.process_names, ["php-fpm"]);
+        assert!(fpm.env.is_empty());
+        // The pool is only written when the service starts.
+        assert!(!pools.exists());
+        let (path, pool) = &fpm.files[0];
+        assert_eq!(*path, pools.join("PHP 8.4.conf"));
+        assert!(pool.contains("listen = 127.0.0.1:9000\n"));
+        assert!(pool.contains("pm.max_children = 4\npm.max_requests = 500\n"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Configuration tests run before a reload, such as `nginx -t`, and the
+//! error they report.
+
+use serde::{Deserialize, Serialize};
+
+/// Why a service's configuration test failed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigError {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl ConfigError {
+    /// Picks the error out of a failed test's output. Nginx style
+    /// (`[emerg] message in file:line`) and Apache style (`Syntax error on
+    /// line N of file:` followed by the message) are understood; otherwise
+    /// the first line of output is the message.
+    pub fn parse(output: &str) -> ConfigError {
+        let lines: Vec<&str> = output.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+
+        for line in &lines {
+            if let Some(error) = Self::parse_nginx(line) {
+                return error;
+            }
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            if let Some(error) = Self::parse_apache(line, lines.get(index + 1).copied()) {
+                return error;
+            }
+        }
+
+        ConfigError {
+            file: None,
+            line: None,
+            message: lines.first().map(|line| line.to_string()).unwrap_or_else(|| "Configuration test failed".to_string()),
+        }
+    }
+
+    fn parse_nginx(line: &str) -> Option<ConfigError> {
+        let rest = ["[emerg]", "[alert]", "[crit]", "[error]"]
+            .iter()
+            .find_map(|level| line.split_once(level).map(|(_, rest)| rest.trim()))?;
+
+        if let Some((message, location)) = rest.rsplit_once(" in ") {
+            if let Some((file, number)) = location.rsplit_once(':') {
+                if let Ok(number) = number.parse() {
+                    return Some(ConfigError {
+                        file: Some(file.to_string()),
+                        line: Some(number),
+                        message: message.to_string(),
+                    });
+                }
+            }
+        }
+        Some(ConfigError {
+            file: None,
+            line: None,
+            message: rest.to_string(),
+        })
+    }
+
+    fn parse_apache(line: &str, next: Option<&str>) -> Option<ConfigError> {
+        let (_, rest) = line.split_once("Syntax error on line ")?;
+        let (number, file) = rest.split_once(" of ")?;
+        Some(ConfigError {
+            file: Some(file.trim_end_matches(':').to_string()),
+            line: number.parse().ok(),
+            message: next.unwrap_or("Syntax error").to_string(),
+        })
+    }
+
+    /// `file:line` where known.
+    pub fn location(&self) -> Option<String> {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => Some(format!("{file}:{line}")),
+            (Some(file), None) => Some(file.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{} ({location})", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nginx_error() {
+        let output = "nginx: [emerg] unknown directive \"lisen\" in /srv/xerve/conf/sites/blog.conf:3\n\
+                      nginx: configuration file /srv/xerve/conf/nginx.conf test failed\n";
+        let error = ConfigError::parse(output);
+        assert_eq!(error.file.as_deref(), Some("/srv/xerve/conf/sites/blog.conf"));
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.message, "unknown directive \"lisen\"");
+        assert_eq!(error.to_string(), "unknown directive \"lisen\" (/srv/xerve/conf/sites/blog.conf:3)");
+
+        let error = ConfigError::parse("nginx: [emerg] no \"events\" section in configuration\n");
+        assert_eq!(error.location(), None);
+        assert_eq!(error.message, "no \"events\" section in configuration");
+    }
+
+    #[test]
+    fn test_parse_other_errors() {
+        let output = "AH00526: Syntax error on line 12 of C:/xerve/conf/httpd.conf:\nInvalid command 'Lisen'\n";
+        let error = ConfigError::parse(output);
+        assert_eq!(error.location().as_deref(), Some("C:/xerve/conf/httpd.conf:12"));
+        assert_eq!(error.message, "Invalid command 'Lisen'");
+
+        assert_eq!(ConfigError::parse("\n  bad config\nmore\n").message, "bad config");
+        assert_eq!(ConfigError::parse("").message, "Configuration test failed");
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::System;
+
+use super::{platform, ServiceRegistry};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceMetrics {
+    pub name: String,
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+}
+
+/// Per-service CPU and memory usage for the daemon and the HTTP API. A
+/// background thread keeps process statistics fresh, since CPU usage is
+/// measured between two refreshes.
+#[derive(Clone)]
+pub struct MetricsCollector {
+    sys: Arc<Mutex<System>>,
+}
+
+impl MetricsCollector {
+    pub fn start() -> Self {
+        let collector = MetricsCollector {
+            sys: Arc::new(Mutex::new(System::new())),
+        };
+
+        let sys = Arc::clone(&collector.sys);
+        std::thread::spawn(move || loop {
+            if let Ok(mut sys) = sys.lock() {
+                sys.refresh_processes();
+            }
+            std::thread::sleep(REFRESH_INTERVAL);
+        });
+
+        collector
+    }
+
+    pub fn collect(&self, registry: &ServiceRegistry) -> Result<Vec<ServiceMetrics>, String> {
+        let sys = self
+            .sys
+            .lock()
+            .map_err(|e| format!("Failed to read process statistics: {e}"))?;
+
+        Ok(registry
+            .iter()
+            .map(|service| {
+                // By process tree where the PID is known, so services sharing
+                // an executable, like several PHP versions, are told apart.
+                let (cpu_percent, memory_bytes) = service
+                    .process_id()
+                    .and_then(|pid| platform::process_tree_usage(&sys, pid))
+                    .or_else(|| {
+                        service
+                            .owns_process_names()
+                            .then(|| platform::process_usage(&sys, &service.process_names()))
+                    })
+                    .unwrap_or_default();
+                ServiceMetrics {
+                    name: service.name.clone(),
+                    cpu_percent,
+                    memory_bytes,
+                }
+            })
+            .collect())
+    }
+}
@@ -1,10 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::time::{Duration, Instant};
 use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{CommandSpec, DatabaseConfig, InitCommand, Readiness, ServiceConfig};
+use crate::daemon::{DaemonClient, Request, Response};
+use crate::logs::store::LogWriter;
+use crate::logs::{LogEntry, LogStream};
+
+mod config_check;
+mod metrics;
+pub mod platform;
+mod probe;
+mod registry;
+pub mod runfiles;
+mod state;
+mod supervisor;
+
+pub use config_check::ConfigError;
+pub use metrics::{MetricsCollector, ServiceMetrics};
+pub use probe::Health;
+pub use registry::ServiceRegistry;
+pub use state::{subscribe, ServiceEvent, ServiceState, StateMachine};
+pub use supervisor::{ExitInfo, RestartDecision, Supervisor};
 
 static TERMINAL: OnceCell<crate::ui::Terminal> = OnceCell::new();
+static ECHO_LOGS: AtomicBool = AtomicBool::new(false);
+static DETACHED_OUTPUT: AtomicBool = AtomicBool::new(false);
+static LOG_SUBSCRIBERS: Mutex<Vec<Sender<LogEntry>>> = Mutex::new(Vec::new());
 
 pub fn set_terminal(terminal: crate::ui::Terminal) {
     TERMINAL.set(terminal).ok();
@@ -14,40 +42,371 @@ fn get_terminal() -> Option<&'static crate::ui::Terminal> {
     TERMINAL.get()
 }
 
-fn log_message(message: String) {
+/// Prints log messages to stderr as well, for headless use.
+pub fn echo_logs(enabled: bool) {
+    ECHO_LOGS.store(enabled, Ordering::Relaxed);
+}
+
+/// Sends service output straight to `logs/<service>/<date>.log` instead of
+/// piping it through Xerve, so services keep running after a headless command
+/// exits.
+pub fn detach_service_output() {
+    DETACHED_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+/// Returns a receiver that gets every log entry, e.g. to forward them to
+/// daemon clients.
+pub fn subscribe_logs() -> Receiver<LogEntry> {
+    let (sender, receiver) = mpsc::channel();
+    if let Ok(mut subscribers) = LOG_SUBSCRIBERS.lock() {
+        subscribers.push(sender);
+    }
+    receiver
+}
+
+pub(crate) fn log_message(message: String) {
+    log_entry(LogEntry::system(message));
+}
+
+pub(crate) fn log_entry(entry: LogEntry) {
+    if ECHO_LOGS.load(Ordering::Relaxed) {
+        eprintln!("{entry}");
+    }
+    if let Ok(mut subscribers) = LOG_SUBSCRIBERS.lock() {
+        subscribers.retain(|subscriber| subscriber.send(entry.clone()).is_ok());
+    }
     if let Some(terminal) = get_terminal() {
-        terminal.add_log(message);
+        terminal.add_entry(entry);
+    }
+}
+
+/// Streams `child`'s stdout and stderr into the log as output of `source`,
+/// also writing it to `log_file` and feeding stderr to `supervisor` when
+/// given. The returned threads finish once both streams are closed.
+pub(crate) fn capture_output(
+    child: &mut std::process::Child,
+    source: &str,
+    log_file: Option<Arc<Mutex<LogWriter>>>,
+    supervisor: Option<Arc<Mutex<Supervisor>>>,
+) -> Vec<std::thread::JoinHandle<()>> {
+    let mut readers = Vec::new();
+
+    if let Some(stdout) = child.stdout.take() {
+        let source = source.to_string();
+        let log_file = log_file.clone();
+        readers.push(std::thread::spawn(move || {
+            let reader = BufReader::new(stdout);
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => {
+                        let entry = LogEntry::output(&source, LogStream::Stdout, line);
+                        if let Some(Ok(mut log_file)) = log_file.as_ref().map(|w| w.lock()) {
+                            log_file.write(&entry);
+                        }
+                        log_entry(entry);
+                    }
+                    Err(e) => {
+                        log_entry(LogEntry::service(&source, format!("Error reading stdout: {e}")));
+                    }
+                }
+            }
+        }));
+    }
+
+    if let Some(stderr) = child.stderr.take() {
+        let source = source.to_string();
+        readers.push(std::thread::spawn(move || {
+            let reader = BufReader::new(stderr);
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => {
+                        if let Some(Ok(mut supervisor)) = supervisor.as_ref().map(|s| s.lock()) {
+                            supervisor.record_stderr(line.clone());
+                        }
+                        let entry = LogEntry::output(&source, LogStream::Stderr, line);
+                        if let Some(Ok(mut log_file)) = log_file.as_ref().map(|w| w.lock()) {
+                            log_file.write(&entry);
+                        }
+                        log_entry(entry);
+                    }
+                    Err(e) => {
+                        log_entry(LogEntry::service(&source, format!("Error reading stderr: {e}")));
+                    }
+                }
+            }
+        }));
     }
+
+    readers
+}
+
+/// Connection to the daemon that owns a service, plus its latest snapshot.
+struct RemoteService {
+    client: DaemonClient,
+    snapshot: Mutex<Option<ServiceSnapshot>>,
 }
 
 pub trait Service {
     fn start(&self);
     fn stop(&self);
-    fn status(&self) -> String;
+    fn state(&self) -> ServiceState;
+}
+
+/// Point-in-time view of a service, as reported by `xerve status --json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceSnapshot {
+    pub name: String,
+    #[serde(flatten)]
+    pub state: ServiceState,
+    pub pid: Option<u32>,
+    pub health: Health,
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub last_exit: Option<ExitInfo>,
+    #[serde(default)]
+    pub stderr_tail: Vec<String>,
+    #[serde(default)]
+    pub restarts_in_window: usize,
+    #[serde(default)]
+    pub config_error: Option<ConfigError>,
 }
 
+#[derive(Clone)]
 pub struct ServiceInfo {
     pub name: String,
-    status: Arc<Mutex<String>>,
-    file_path: String,
+    state: Arc<StateMachine>,
+    config: ServiceConfig,
     process_id: Arc<Mutex<Option<u32>>>,
+    supervisor: Arc<Mutex<Supervisor>>,
+    health: Arc<Mutex<Health>>,
+    /// Why the last configuration test failed, until one passes.
+    config_error: Arc<Mutex<Option<ConfigError>>>,
+    /// Settings changed on disk that the running process has not read.
+    pending_restart: Arc<Mutex<Option<PendingRestart>>>,
+    /// Set when another service runs an executable of the same name, like
+    /// php-cgi for each PHP version, so a name says nothing about ownership.
+    shared_process_names: bool,
+    remote: Option<Arc<RemoteService>>,
+}
+
+/// Settings waiting for the process `pid` to be replaced.
+struct PendingRestart {
+    pid: u32,
+    settings: Vec<String>,
 }
 
 impl ServiceInfo {
-    pub fn new(name: &str, status: &str, file_path: &str) -> Self {
+    fn log(&self, message: String) {
+        log_entry(LogEntry::service(&self.name, message));
+    }
+
+    /// Directory of the service's executable, if it exists.
+    pub fn executable_dir(&self) -> Option<PathBuf> {
+        let executable = platform::resolve_executable(&self.config.executable);
+        executable.is_file().then(|| executable.parent().map(Path::to_path_buf)).flatten()
+    }
+
+    /// Where the SQL console connects, for database services. A root
+    /// password reset from the Databases tab replaces the configured one.
+    pub fn database(&self) -> Option<DatabaseConfig> {
+        let mut database = self.config.database()?;
+        if database.user == "root" {
+            if let Some(password) = self.root_password() {
+                database.password = password;
+            }
+        }
+        Some(database)
+    }
+
+    fn root_password(&self) -> Option<String> {
+        std::fs::read_to_string(runfiles::root_password_path(&self.name)).ok()
+    }
+
+    /// Keeps the root password just set on the server, so Xerve's
+    /// connections and stop command keep working.
+    pub fn remember_root_password(&self, password: &str) -> Result<(), String> {
+        runfiles::write_private(&runfiles::root_password_path(&self.name), password)
+    }
+
+    /// Whether Xerve's own connection or stop command uses `user`.
+    pub fn signs_in_as(&self, user: &str) -> bool {
+        self.config.signs_in_as(user)
+    }
+
+    /// The option file the server reads, for services started with
+    /// `--defaults-file`.
+    pub fn defaults_file(&self) -> Option<PathBuf> {
+        self.config.defaults_file()
+    }
+
+    pub fn from_config(config: ServiceConfig) -> Self {
         ServiceInfo {
-            name: name.to_string(),
-            status: Arc::new(Mutex::new(status.to_string())),
-            file_path: file_path.to_string(),
+            name: config.name.clone(),
+            state: Arc::new(StateMachine::new(&config.name, ServiceState::Stopped)),
+            config,
             process_id: Arc::new(Mutex::new(None)),
+            supervisor: Arc::new(Mutex::new(Supervisor::default())),
+            health: Arc::new(Mutex::new(Health::Unknown)),
+            config_error: Arc::new(Mutex::new(None)),
+            pending_restart: Arc::new(Mutex::new(None)),
+            shared_process_names: false,
+            remote: None,
+        }
+    }
+
+    /// Looks for a process left running by an earlier session, via Xerve's
+    /// own PID record and the service's `pid_file`. A live process with an
+    /// expected name is adopted and watched; stale or mismatched PID files
+    /// are reported and removed.
+    pub fn adopt_running(&self) {
+        let mut sources = vec![runfiles::pid_path(&self.name)];
+        if let Some(pid_file) = &self.config.pid_file {
+            sources.push(PathBuf::from(pid_file));
+        }
+
+        let names = self.process_names();
+        let mut adopted = None;
+        for path in sources {
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(pid) = contents.trim().parse::<u32>() else {
+                self.log(format!("{}: ignoring unreadable PID file {}", self.name, path.display()));
+                continue;
+            };
+            if adopted == Some(pid) {
+                continue;
+            }
+
+            match platform::process_name(pid) {
+                Some(actual) if platform::name_matches(&actual, &names) => {
+                    if adopted.is_none() {
+                        adopted = Some(pid);
+                    }
+                }
+                Some(actual) => {
+                    self.log(format!(
+                        "{}: PID {pid} from {} belongs to '{actual}', not {}; removing the stale PID file",
+                        self.name,
+                        path.display(),
+                        names.join("/")
+                    ));
+                    let _ = std::fs::remove_file(&path);
+                }
+                None => {
+                    self.log(format!(
+                        "{}: PID {pid} from {} is not running; removing the stale PID file",
+                        self.name,
+                        path.display()
+                    ));
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+
+        let Some(pid) = adopted else {
+            if !self.owns_process_names() {
+                return;
+            }
+            let strays = platform::find_pids_by_name(&names);
+            if !strays.is_empty() {
+                let pids: Vec<String> = strays.iter().map(u32::to_string).collect();
+                self.log(format!(
+                    "{}: {} is running as PID {} but has no PID file Xerve knows about; it may belong to another installation",
+                    self.name,
+                    names.join("/"),
+                    pids.join(", ")
+                ));
+            }
+            return;
+        };
+
+        self.log(format!("Adopted running {} process (PID {pid})", self.name));
+        self.set_process_id(Some(pid));
+        self.state.observe(ServiceState::Running);
+
+        let service = self.clone();
+        std::thread::spawn(move || service.watch_adopted(pid));
+
+        if probe::is_active_probe(&self.config.readiness) {
+            let service = self.clone();
+            std::thread::spawn(move || service.monitor_health(pid));
         }
     }
-    
-    pub fn update_status_static(status_arc: Arc<Mutex<String>>, new_status: &str) {
-        let mut status_guard = status_arc.lock().unwrap();
-        *status_guard = new_status.to_string();
+
+    /// Hands control of this service to a running daemon: lifecycle commands
+    /// are forwarded to it and state comes from [`ServiceInfo::apply_snapshot`].
+    pub fn attach_daemon(&mut self, client: DaemonClient) {
+        self.remote = Some(Arc::new(RemoteService {
+            client,
+            snapshot: Mutex::new(None),
+        }));
     }
-    
+
+    pub fn is_remote(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Mirrors the daemon's view of this service.
+    pub fn apply_snapshot(&self, snapshot: ServiceSnapshot) {
+        let Some(remote) = &self.remote else {
+            return;
+        };
+        self.state.observe(snapshot.state.clone());
+        if let Ok(mut process_id) = self.process_id.lock() {
+            *process_id = snapshot.pid;
+        }
+        self.set_health(snapshot.health.clone());
+        if let Ok(mut mirrored) = remote.snapshot.lock() {
+            *mirrored = Some(snapshot);
+        }
+    }
+
+    /// Sends a request about this service to the daemon, logging failures.
+    fn forward(&self, remote: &RemoteService, request: Request) {
+        match remote.client.request(&request) {
+            Ok(Response::Error { message }) => self.log(format!("{}: {message}", self.name)),
+            Ok(_) => {}
+            Err(e) => self.log(format!("{}: {e}", self.name)),
+        }
+    }
+
+    pub fn snapshot(&self) -> ServiceSnapshot {
+        ServiceSnapshot {
+            name: self.name.clone(),
+            state: self.state(),
+            pid: self.process_id(),
+            health: self.health(),
+            depends_on: self.config.depends_on.clone(),
+            last_exit: self.last_exit(),
+            stderr_tail: self.stderr_tail(),
+            restarts_in_window: self.restarts_in_window(),
+            config_error: self.config_error(),
+        }
+    }
+
+    pub fn process_id(&self) -> Option<u32> {
+        self.process_id.lock().ok().and_then(|guard| *guard)
+    }
+
+    pub fn process_names(&self) -> Vec<String> {
+        self.config.process_names()
+    }
+
+    /// Whether a process found by name can be taken for this service when
+    /// its PID is unknown.
+    pub fn owns_process_names(&self) -> bool {
+        !self.shared_process_names
+    }
+
+    pub fn can_reload(&self) -> bool {
+        self.config.reload.is_some()
+    }
+
+    pub fn can_check(&self) -> bool {
+        self.config.check.is_some()
+    }
+
     #[cfg(windows)]
     fn hide_window(&self, cmd: &mut Command) {
         use std::os::windows::process::CommandExt;
@@ -60,76 +419,76 @@ impl ServiceInfo {
         // No special handling needed for non-Windows platforms
     }
 
+    /// Builds a command for `program` that runs in the service's working
+    /// directory with its configured environment.
+    fn build_command(&self, program: &str, args: &[String]) -> Command {
+        let mut command = Command::new(platform::resolve_executable(program));
+        command.args(args).envs(&self.config.env);
+        if let Some(working_dir) = self.config.working_dir() {
+            command.current_dir(working_dir);
+        }
+        command
+    }
+
     fn run_command_with_output_capture(
         &self,
         mut command: Command,
         operation: &str,
     ) -> Result<Option<std::process::Child>, String> {
-        log_message(format!("[{}] Running: {:?}", self.name, command));
-        
+        self.log(format!("Running: {:?}", command));
+
         self.hide_window(&mut command);
 
-        match command
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
+        let is_service_process = operation == "start";
+        let mut detached_log = None;
+        if is_service_process && DETACHED_OUTPUT.load(Ordering::Relaxed) {
+            let (log, path) = crate::logs::store::open_detached(&self.name)?;
+            let log_err = log
+                .try_clone()
+                .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+            command.stdin(Stdio::null()).stdout(log).stderr(log_err);
+            detached_log = Some(path);
+        } else {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        match command.spawn() {
             Ok(mut child) => {
-                if let Some(stdout) = child.stdout.take() {
-                    let service_name = self.name.clone();
-                    std::thread::spawn(move || {
-                        let reader = BufReader::new(stdout);
-                        for line in reader.lines() {
-                            match line {
-                                Ok(line) => {
-                                    log_message(format!("[{service_name}] {line}"));
-                                }
-                                Err(e) => {
-                                    log_message(format!("[{service_name}] Error reading stdout: {e}"));
-                                }
-                            }
-                        }
-                    });
-                }
-                
-                if let Some(stderr) = child.stderr.take() {
-                    let service_name = self.name.clone();
-                    std::thread::spawn(move || {
-                        let reader = BufReader::new(stderr);
-                        for line in reader.lines() {
-                            match line {
-                                Ok(line) => {
-                                    log_message(format!("[{service_name}] STDERR: {line}"));
-                                }
-                                Err(e) => {
-                                    log_message(format!("[{service_name}] Error reading stderr: {e}"));
-                                }
-                            }
-                        }
-                    });
+                if let Some(path) = &detached_log {
+                    if let Err(e) = crate::logs::store::hold_detached(path, child.id()) {
+                        self.log(e);
+                    }
                 }
-                
-                if operation == "start" && (self.name == "MariaDB" || self.name == "Nginx") {
+                // Both streams of the service process share one writer so they
+                // rotate together.
+                let log_file = is_service_process.then(|| Arc::new(Mutex::new(LogWriter::open(&self.name))));
+                let supervisor = is_service_process.then(|| Arc::clone(&self.supervisor));
+                capture_output(&mut child, &self.name, log_file, supervisor);
+
+                if is_service_process {
                     Ok(Some(child))
                 } else {
                     let start_time = Instant::now();
                     let timeout = Duration::from_secs(30);
-                    
+
                     loop {
                         if start_time.elapsed() >= timeout {
                             return Err("Process wait timeout exceeded".to_string());
                         }
-                        
+
                         match child.try_wait() {
                             Ok(Some(status)) => {
-                                log_message(format!("[{}] Process finished with status: {}", self.name, status));
-                                return Ok(None);
+                                self.log(format!("Process finished with status: {}", status));
+                                if status.success() {
+                                    return Ok(None);
+                                }
+                                return Err(format!("Process exited with status: {status}"));
                             }
                             Ok(None) => {
                                 std::thread::sleep(Duration::from_millis(100));
                             }
                             Err(e) => {
-                                log_message(format!("[{}] Error waiting for process: {}", self.name, e));
+                                self.log(format!("Error waiting for process: {}", e));
                                 return Err(e.to_string());
                             }
                         }
@@ -137,348 +496,695 @@ impl ServiceInfo {
                 }
             }
             Err(e) => {
-                let error_msg = format!("[{}] Failed to execute command: {}", self.name, e);
-                log_message(error_msg.clone());
+                let error_msg = format!("Failed to execute command: {e}");
+                self.log(error_msg.clone());
                 Err(error_msg)
             }
         }
     }
 
-    fn update_status(&self, new_status: &str) {
-        match self.status.lock() {
-            Ok(mut status_guard) => {
-                *status_guard = new_status.to_string();
-            }
+    fn transition(&self, next: ServiceState) -> bool {
+        match self.state.transition(next) {
+            Ok(()) => true,
             Err(e) => {
-                log_message(format!("Failed to acquire status lock: {e}"));
+                self.log(e);
+                false
             }
         }
     }
-    
-    fn is_running(&self) -> bool {
-        match self.status.lock() {
-            Ok(status_guard) => {
-                *status_guard == "Running"
+
+    pub fn state_since(&self) -> Option<Instant> {
+        self.state.since()
+    }
+
+    /// Health as reported by the readiness probe while the service runs.
+    pub fn health(&self) -> Health {
+        if self.state() != ServiceState::Running {
+            return Health::Unknown;
+        }
+        self.health
+            .lock()
+            .map(|health| health.clone())
+            .unwrap_or(Health::Unknown)
+    }
+
+    fn set_health(&self, health: Health) {
+        match self.health.lock() {
+            Ok(mut health_guard) => {
+                *health_guard = health;
             }
             Err(e) => {
-                log_message(format!("Failed to acquire status lock: {e}"));
-                false 
+                self.log(format!("Failed to acquire health lock: {e}"));
             }
         }
     }
-    
-    fn is_stopped(&self) -> bool {
-        match self.status.lock() {
-            Ok(status_guard) => {
-                *status_guard == "Stopped"
+
+    pub fn startup_timeout(&self) -> Duration {
+        let delay = match self.config.readiness {
+            Readiness::Delay { ms } => Duration::from_millis(ms),
+            _ => Duration::ZERO,
+        };
+        Duration::from_secs(self.config.health.startup_timeout_secs) + delay
+    }
+
+    /// Blocks until the service is Running, failing as soon as it ends up in
+    /// a state it will not leave on its own.
+    pub fn wait_until_ready(&self, timeout: Duration) -> Result<(), String> {
+        let start_time = Instant::now();
+        loop {
+            match self.state() {
+                ServiceState::Running => return Ok(()),
+                ServiceState::Starting => {}
+                state => return Err(state.to_string()),
             }
-            Err(e) => {
-                log_message(format!("Failed to acquire status lock: {e}"));
-                true 
+
+            if start_time.elapsed() >= timeout {
+                return Err(format!("timed out after {}s", timeout.as_secs()));
             }
+            std::thread::sleep(Duration::from_millis(200));
         }
     }
-}
 
-impl Service for ServiceInfo {
-    fn start(&self) {
-        log_message(format!("Starting {} service...", self.name));
+    pub fn depends_on(&self) -> &[String] {
+        &self.config.depends_on
+    }
+
+    fn is_current_process(&self, pid: u32) -> bool {
+        self.process_id() == Some(pid)
+    }
+
+    /// Runs the readiness probe until it passes, marking the service
+    /// Running, then keeps probing it for as long as it runs.
+    fn watch_readiness(&self, pid: u32) {
+        self.set_health(Health::Unknown);
+
+        if let Readiness::Delay { ms } = self.config.readiness {
+            std::thread::sleep(Duration::from_millis(ms));
+        }
+
+        let timeout = Duration::from_secs(self.config.health.startup_timeout_secs);
+        let start_time = Instant::now();
+        loop {
+            if self.state() != ServiceState::Starting || !self.is_current_process(pid) {
+                return;
+            }
 
-        if self.is_running() {
-            log_message(format!("{} is already running", self.name));
+            match probe::check(&self.config.readiness) {
+                Ok(()) => break,
+                Err(e) if start_time.elapsed() >= timeout => {
+                    self.log(format!(
+                        "{} did not become ready within {}s: {e}",
+                        self.name,
+                        timeout.as_secs()
+                    ));
+                    self.transition(ServiceState::Error(format!("readiness check failed: {e}")));
+                    // The process may still hold its ports. Once it is gone the
+                    // exit counts as a failure and the restart policy applies.
+                    if let Err(e) = self.shut_down() {
+                        self.log(format!("Failed to stop {}: {e}", self.name));
+                    }
+                    return;
+                }
+                Err(_) => std::thread::sleep(Duration::from_millis(500)),
+            }
+        }
+
+        if self.state() != ServiceState::Starting {
             return;
         }
+        self.log(format!("{} started successfully with PID: {pid}", self.name));
+        self.transition(ServiceState::Running);
 
-        if self.name == "Nginx" {
-            let nginx_dir = std::path::Path::new("./resource/nginx");
+        if probe::is_active_probe(&self.config.readiness) {
+            self.monitor_health(pid);
+        }
+    }
 
-            let mut command = Command::new(&self.file_path);
-            command
-                .current_dir(nginx_dir)
-                .arg("-c")
-                .arg("conf/nginx.conf");
+    /// Keeps probing a running service, marking it unhealthy after
+    /// `failure_threshold` consecutive failures.
+    fn monitor_health(&self, pid: u32) {
+        self.set_health(Health::Healthy);
 
-            match self.run_command_with_output_capture(command, "start") {
-                Ok(_) => {
-                    log_message("Nginx started successfully".to_string());
-                    self.update_status("Running");
+        let interval = Duration::from_secs(self.config.health.interval_secs.max(1));
+        let threshold = self.config.health.failure_threshold.max(1);
+        let mut failures = 0;
+        loop {
+            std::thread::sleep(interval);
+            if self.state() != ServiceState::Running || !self.is_current_process(pid) {
+                return;
+            }
+
+            match probe::check(&self.config.readiness) {
+                Ok(()) => {
+                    if failures >= threshold {
+                        self.log(format!("{} is healthy again", self.name));
+                    }
+                    failures = 0;
+                    self.set_health(Health::Healthy);
                 }
                 Err(e) => {
-                    log_message(format!("Failed to start Nginx: {e}"));
-                    self.update_status("Error");
+                    failures += 1;
+                    if failures == threshold {
+                        self.log(format!(
+                            "{} is unhealthy after {failures} failed health checks: {e}",
+                            self.name
+                        ));
+                    }
+                    if failures >= threshold {
+                        self.set_health(Health::Unhealthy { failures, last_error: e });
+                    }
                 }
             }
-        } else if self.name == "MariaDB" {
-            let mariadb_dir = std::path::Path::new("./resource/mariadb");
-            let data_dir = mariadb_dir.join("data");
+        }
+    }
 
-            let _data_dir_created = false;
-            if !data_dir.exists() {
-                log_message("MariaDB data directory not found. Initializing...".to_string());
+    /// The daemon's latest snapshot, when this service is remote.
+    fn mirrored<T>(&self, read: impl FnOnce(&ServiceSnapshot) -> T) -> Option<Option<T>> {
+        let remote = self.remote.as_ref()?;
+        Some(remote.snapshot.lock().ok().and_then(|snapshot| snapshot.as_ref().map(read)))
+    }
 
-                if let Err(e) = std::fs::create_dir_all(&data_dir) {
-                    log_message(format!("Failed to create MariaDB data directory: {e}"));
-                    self.update_status("Error");
-                    return;
-                }
+    pub fn last_exit(&self) -> Option<ExitInfo> {
+        if let Some(mirrored) = self.mirrored(|snapshot| snapshot.last_exit.clone()) {
+            return mirrored.flatten();
+        }
+        self.supervisor.lock().ok().and_then(|supervisor| supervisor.last_exit())
+    }
 
-                let mut init_command = Command::new(mariadb_dir.join("bin/mariadb-install-db.exe"));
-                init_command
-                    .arg("--datadir=./data")
-                    .current_dir(mariadb_dir);
+    pub fn stderr_tail(&self) -> Vec<String> {
+        if let Some(mirrored) = self.mirrored(|snapshot| snapshot.stderr_tail.clone()) {
+            return mirrored.unwrap_or_default();
+        }
+        self.supervisor
+            .lock()
+            .map(|supervisor| supervisor.stderr_tail())
+            .unwrap_or_default()
+    }
 
-                match self.run_command_with_output_capture(init_command, "init") {
-                    Ok(_) => {
-                        log_message("MariaDB initialized successfully".to_string());
-                    }
-                    Err(e) => {
-                        log_message(format!("MariaDB initialization failed: {e}"));
-                        if let Err(e) = std::fs::remove_dir_all(&data_dir) {
-                            log_message(format!(
-                                "Failed to rollback MariaDB data directory: {e}"
-                            ));
-                        } else {
-                            log_message("Rolled back MariaDB data directory.".to_string());
-                        }
-                        self.update_status("Error");
-                        return;
+    pub fn config_error(&self) -> Option<ConfigError> {
+        if let Some(mirrored) = self.mirrored(|snapshot| snapshot.config_error.clone()) {
+            return mirrored.flatten();
+        }
+        self.config_error.lock().ok().and_then(|error| error.clone())
+    }
+
+    fn set_config_error(&self, error: Option<ConfigError>) {
+        if let Ok(mut config_error) = self.config_error.lock() {
+            *config_error = error;
+        }
+    }
+
+    /// Notes that `settings` changed on disk and only apply once the running
+    /// process is restarted. Has no effect while the service is not running.
+    pub fn require_restart(&self, settings: Vec<String>) {
+        let Some(pid) = self.process_id() else {
+            return;
+        };
+        let Ok(mut pending) = self.pending_restart.lock() else {
+            return;
+        };
+        match pending.as_mut() {
+            Some(pending) if pending.pid == pid => {
+                for setting in settings {
+                    if !pending.settings.contains(&setting) {
+                        pending.settings.push(setting);
                     }
                 }
             }
+            _ => *pending = Some(PendingRestart { pid, settings }),
+        }
+    }
 
-            let is_data_dir_empty = match std::fs::read_dir(&data_dir) {
-                Ok(mut dir) => dir.next().is_none(),
-                Err(e) => {
-                    log_message(format!("Failed to read MariaDB data directory: {e}"));
-                    true
-                }
-            };
+    /// Settings waiting for a restart; empty once the process that was
+    /// running when they changed has gone.
+    pub fn restart_required(&self) -> Vec<String> {
+        let pid = self.process_id();
+        self.pending_restart
+            .lock()
+            .ok()
+            .and_then(|pending| pending.as_ref().filter(|pending| Some(pending.pid) == pid).map(|pending| pending.settings.clone()))
+            .unwrap_or_default()
+    }
 
-            if !data_dir.exists() || is_data_dir_empty {
-                log_message(
-                    "MariaDB data directory is missing or empty. Cannot start service.".to_string(),
-                );
-                self.update_status("Error");
-                return;
+    pub fn restarts_in_window(&self) -> usize {
+        if let Some(mirrored) = self.mirrored(|snapshot| snapshot.restarts_in_window) {
+            return mirrored.unwrap_or_default();
+        }
+        self.supervisor
+            .lock()
+            .map(|supervisor| supervisor.restarts_in_window())
+            .unwrap_or_default()
+    }
+
+    /// Waits for the service process to exit, then applies the service's
+    /// restart policy if the exit was not requested.
+    fn supervise(&self, mut child: std::process::Child) {
+        let pid = child.id();
+        let exit = match child.wait() {
+            Ok(exit_status) => {
+                self.log(format!(
+                    "{} process exited with status: {exit_status}",
+                    self.name
+                ));
+                ExitInfo {
+                    code: exit_status.code(),
+                    description: exit_status.to_string(),
+                }
+            }
+            Err(e) => {
+                self.log(format!("Error waiting for {} process: {e}", self.name));
+                ExitInfo {
+                    code: None,
+                    description: e.to_string(),
+                }
             }
+        };
+        self.handle_exit(pid, exit);
+    }
 
-            log_message("Starting MariaDB service...".to_string());
-            let mut command = Command::new(mariadb_dir.join("bin/mariadbd.exe")); // Fixed: use mariadbd.exe instead of mysqld.exe
-            command
-                .current_dir(mariadb_dir)
-                .arg("--defaults-file=my.ini");
+    /// Polls a process adopted from an earlier session, which cannot be
+    /// waited on, and handles its exit like a child's.
+    fn watch_adopted(&self, pid: u32) {
+        while self.is_current_process(pid) && platform::is_alive(pid) {
+            std::thread::sleep(Duration::from_secs(1));
+        }
+        if !self.is_current_process(pid) {
+            return;
+        }
 
-            match self.run_command_with_output_capture(command, "start") {
-                Ok(Some(mut child)) => {
-                    let pid = child.id();
-                    match self.process_id.lock() {
-                        Ok(mut process_id_guard) => {
-                            *process_id_guard = Some(pid);
-                        }
-                        Err(e) => {
-                            log_message(format!("Failed to acquire process_id lock: {e}"));
-                        }
-                    }
+        self.log(format!("Adopted {} process (PID {pid}) exited", self.name));
+        self.handle_exit(
+            pid,
+            ExitInfo {
+                code: None,
+                description: "adopted process exited, status unknown".to_string(),
+            },
+        );
+    }
 
-                    let service_name = self.name.clone();
-                    let status_arc = Arc::clone(&self.status);
-                    std::thread::spawn(move || {
-                        match child.wait() {
-                            Ok(exit_status) => {
-                                log_message(format!(
-                                    "{service_name} process exited with status: {exit_status}"
-                                ));
-                            }
-                            Err(e) => {
-                                log_message(format!(
-                                    "Error waiting for {service_name} process: {e}"
-                                ));
-                            }
-                        }
-                        ServiceInfo::update_status_static(status_arc, "Stopped");
-                        log_message(format!(
-                            "{service_name} status set to Stopped after process exit."
-                        ));
-                    });
+    /// Records an exit and applies the restart policy unless the exit was
+    /// requested.
+    fn handle_exit(&self, pid: u32, exit: ExitInfo) {
+        // A process ended after failing, e.g. its readiness check, did not
+        // exit cleanly whatever its status.
+        let exited_cleanly = exit.code == Some(0) && !matches!(self.state(), ServiceState::Error(_));
+
+        if self.is_current_process(pid) {
+            self.set_process_id(None);
+        }
+        if let Ok(mut supervisor) = self.supervisor.lock() {
+            supervisor.record_exit(exit);
+        }
 
-                    std::thread::sleep(Duration::from_millis(500));
-                    log_message(format!("MariaDB started successfully with PID: {pid}"));
-                    self.update_status("Running");
+        let next = match self.state() {
+            ServiceState::Stopping | ServiceState::Stopped => {
+                self.transition(ServiceState::Stopped);
+                self.log(format!("{} status set to Stopped after process exit.", self.name));
+                return;
+            }
+            _ if exited_cleanly => ServiceState::Stopped,
+            _ => ServiceState::Crashed,
+        };
+        self.transition(next.clone());
+        self.log(format!("{} status set to {next} after process exit.", self.name));
+
+        let decision = match self.supervisor.lock() {
+            Ok(mut supervisor) => supervisor.decide(&self.config.restart, exited_cleanly, Instant::now()),
+            Err(e) => {
+                self.log(format!("Failed to acquire supervisor lock: {e}"));
+                return;
+            }
+        };
+
+        match decision {
+            RestartDecision::None => {}
+            RestartDecision::Recycle => {
+                self.log(format!("{} finished its run, starting a fresh process", self.name));
+                let still_wanted = self
+                    .supervisor
+                    .lock()
+                    .map(|mut supervisor| supervisor.take_pending_restart())
+                    .unwrap_or(false);
+                if still_wanted && self.state() == next {
+                    self.start();
                 }
-                Ok(None) => {
-                    log_message("MariaDB command completed but process not running".to_string());
-                    self.update_status("Stopped");
+            }
+            RestartDecision::Restart { delay, attempt } => {
+                self.log(format!(
+                    "Restarting {} in {:.1}s (attempt {attempt} of {})",
+                    self.name,
+                    delay.as_secs_f32(),
+                    self.config.restart.max_restarts
+                ));
+                std::thread::sleep(delay);
+
+                let still_wanted = self
+                    .supervisor
+                    .lock()
+                    .map(|mut supervisor| supervisor.take_pending_restart())
+                    .unwrap_or(false);
+                if still_wanted && self.state() == next {
+                    self.start();
                 }
-                Err(e) => {
-                    log_message(format!("Failed to start MariaDB: {e}"));
-                    self.update_status("Error");
+            }
+            RestartDecision::CrashLoop => {
+                self.log(format!(
+                    "{} exited {} times within {}s, giving up. Start it manually once the problem is fixed.",
+                    self.name,
+                    self.config.restart.max_restarts,
+                    self.config.restart.window_secs
+                ));
+                self.transition(ServiceState::CrashLoop);
+            }
+        }
+    }
+
+    fn set_process_id(&self, pid: Option<u32>) {
+        match pid {
+            Some(pid) => {
+                if let Err(e) = runfiles::write_pid(&self.name, pid) {
+                    self.log(e);
                 }
             }
-        } else {
-            let mut command = Command::new(&self.file_path);
-            command.arg("-s").arg("start");
+            None => runfiles::remove_pid(&self.name),
+        }
+
+        match self.process_id.lock() {
+            Ok(mut process_id_guard) => {
+                *process_id_guard = pid;
+            }
+            Err(e) => {
+                self.log(format!("Failed to acquire process_id lock: {e}"));
+            }
+        }
+    }
 
-            match self.run_command_with_output_capture(command, "start") {
+    /// Writes the files the service's configuration generates.
+    fn write_files(&self) -> Result<(), String> {
+        for (path, contents) in &self.config.files {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+            }
+            std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Runs the service's init command if the directory it creates is
+    /// missing. Returns `false` when the service cannot be started.
+    fn run_init(&self, init: &InitCommand) -> bool {
+        let target = Path::new(&init.creates);
+
+        if !target.exists() {
+            self.log(format!(
+                "{} is not initialized ({} not found). Initializing...",
+                self.name, init.creates
+            ));
+
+            if let Err(e) = std::fs::create_dir_all(target) {
+                self.log(format!("Failed to create {}: {e}", init.creates));
+                return false;
+            }
+
+            let init_command = self.build_command(&init.program, &init.args);
+            match self.run_command_with_output_capture(init_command, "init") {
                 Ok(_) => {
-                    log_message(format!("{} started successfully", self.name));
-                    self.update_status("Running");
+                    self.log(format!("{} initialized successfully", self.name));
                 }
                 Err(e) => {
-                    log_message(format!("Failed to start {}: {}", self.name, e));
-                    self.update_status("Error");
+                    self.log(format!("{} initialization failed: {e}", self.name));
+                    if let Err(e) = std::fs::remove_dir_all(target) {
+                        self.log(format!("Failed to rollback {}: {e}", init.creates));
+                    } else {
+                        self.log(format!("Rolled back {}.", init.creates));
+                    }
+                    return false;
                 }
             }
         }
+
+        let is_target_empty = match std::fs::read_dir(target) {
+            Ok(mut dir) => dir.next().is_none(),
+            Err(e) => {
+                self.log(format!("Failed to read {}: {e}", init.creates));
+                true
+            }
+        };
+
+        if is_target_empty {
+            self.log(format!(
+                "{} is missing or empty. Cannot start {}.",
+                init.creates, self.name
+            ));
+            return false;
+        }
+
+        true
     }
 
-    fn stop(&self) {
-        log_message(format!("Stopping {} service...", self.name));
+    /// Forcefully terminates the service process, used when the configured
+    /// stop command is missing or fails. Without a known PID, every process
+    /// matching the service's process names is terminated.
+    fn kill_process(&self) -> Result<(), String> {
+        let pids = match self.process_id() {
+            Some(pid) => vec![pid],
+            None if self.owns_process_names() => platform::find_pids_by_name(&self.process_names()),
+            // Other services run processes of the same name; don't kill theirs.
+            None => Vec::new(),
+        };
 
-        if self.is_stopped() {
-            log_message(format!("{} is already stopped", self.name));
-            match self.process_id.lock() {
-                Ok(mut process_id_guard) => {
-                    *process_id_guard = None;
+        if pids.is_empty() {
+            return Err(format!("No running {} process found", self.name));
+        }
+
+        let grace = Duration::from_secs(self.config.stop_timeout_secs);
+        for pid in pids {
+            self.log(format!("Terminating process {pid}"));
+            platform::terminate(pid, grace)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the service's process with its stop command, or by terminating
+    /// it when there is none or it fails. The state is left to the caller.
+    fn shut_down(&self) -> Result<(), String> {
+        if let Some(stop) = &self.config.stop {
+            let mut command = self.build_command(&stop.program, &stop.args);
+            if let Some(password) = self.root_password().filter(|_| self.config.stops_as("root")) {
+                command.env("MYSQL_PWD", password);
+            }
+            match self.run_command_with_output_capture(command, "stop") {
+                Ok(_) => {
+                    self.log(format!("{} stopped successfully", self.name));
+                    return Ok(());
                 }
-                Err(e) => {
-                    log_message(format!("Failed to acquire process_id lock: {e}"));
+                Err(_) => {
+                    self.log(format!(
+                        "Failed to stop {} with its stop command, trying alternative methods...",
+                        self.name
+                    ));
                 }
             }
+        }
+
+        self.kill_process()?;
+        self.log(format!("{} stopped successfully by killing the process", self.name));
+        Ok(())
+    }
+
+    /// Runs the service's configuration test, logging its output.
+    fn check_config(&self, check: &CommandSpec) -> Result<(), ConfigError> {
+        let mut command = self.build_command(&check.program, &check.args);
+        self.hide_window(&mut command);
+        self.log(format!("Running: {:?}", command));
+
+        let output = command.stdin(Stdio::null()).output().map_err(|e| ConfigError {
+            file: None,
+            line: None,
+            message: format!("Failed to run {}: {e}", check.program),
+        })?;
+        // Nginx and Apache both report on stderr.
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout)
+        );
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            self.log(line.to_string());
+        }
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(ConfigError::parse(&text))
+        }
+    }
+
+    /// Reloads the service's configuration, after its configuration test
+    /// passes when it declares one.
+    pub fn reload(&self) {
+        if let Some(remote) = &self.remote {
+            self.forward(remote, Request::Reload { service: self.name.clone() });
             return;
         }
 
-        if self.name == "Nginx" {
-            let nginx_dir = std::path::Path::new("./resource/nginx");
+        let Some(reload) = &self.config.reload else {
+            self.log(format!("{} does not declare a reload command", self.name));
+            return;
+        };
+
+        if self.state() != ServiceState::Running {
+            self.log(format!("{} is not running, nothing to reload", self.name));
+            return;
+        }
 
-            let pid_file = nginx_dir.join("logs/nginx.pid");
-            if !pid_file.exists() {
-                log_message("Nginx PID file not found, assuming Nginx is not running. Setting status to Stopped.".to_string());
-                self.update_status("Stopped");
-                *self.process_id.lock().unwrap() = None;
+        if let Some(check) = &self.config.check {
+            if let Err(error) = self.check_config(check) {
+                self.log(format!("{} configuration test failed, not reloading: {error}", self.name));
+                self.set_config_error(Some(error));
                 return;
             }
+        }
+        self.set_config_error(None);
 
-            let mut command = Command::new(&self.file_path);
-            command.current_dir(nginx_dir).arg("-s").arg("stop");
+        self.log(format!("Reloading {} service...", self.name));
+        let command = self.build_command(&reload.program, &reload.args);
+        match self.run_command_with_output_capture(command, "reload") {
+            Ok(_) => self.log(format!("{} reloaded successfully", self.name)),
+            Err(e) => self.log(format!("Failed to reload {}: {e}", self.name)),
+        }
+    }
+}
 
-            match self.run_command_with_output_capture(command, "stop") {
-                Ok(_) => {
-                    log_message("Nginx stopped successfully".to_string());
-                    self.update_status("Stopped");
-                }
-                Err(e) => {
-                    log_message(format!("Failed to stop Nginx: {e}"));
-                    self.update_status("Error");
-                }
+impl Service for ServiceInfo {
+    fn start(&self) {
+        if let Some(remote) = &self.remote {
+            self.forward(remote, Request::Start { service: Some(self.name.clone()) });
+            return;
+        }
+
+        self.log(format!("Starting {} service...", self.name));
+
+        match self.state() {
+            ServiceState::Running | ServiceState::Starting => {
+                self.log(format!("{} is already running", self.name));
+                return;
             }
-            match self.process_id.lock() {
-                Ok(mut process_id_guard) => {
-                    *process_id_guard = None;
-                }
-                Err(e) => {
-                    log_message(format!("Failed to acquire process_id lock: {e}"));
-                }
+            ServiceState::Stopping => {
+                self.log(format!("{} is still stopping, try again shortly", self.name));
+                return;
             }
-        } else if self.name == "MariaDB" {
-            let mariadb_dir = std::path::Path::new("./resource/mariadb");
+            _ => {}
+        }
 
-            log_message("Attempting to stop MariaDB with mysqladmin...".to_string());
-            let mut mysqladmin_cmd = Command::new(mariadb_dir.join("bin/mysqladmin.exe"));
-            mysqladmin_cmd
-                .current_dir(mariadb_dir)
-                .arg("-u")
-                .arg("root")
-                .arg("shutdown");
+        if self.state() == ServiceState::CrashLoop {
+            if let Ok(mut supervisor) = self.supervisor.lock() {
+                supervisor.reset();
+            }
+        }
 
-            match self.run_command_with_output_capture(mysqladmin_cmd, "stop") {
-                Ok(_) => {
-                    log_message("MariaDB stopped successfully with mysqladmin".to_string());
-                    self.update_status("Stopped");
-                    match self.process_id.lock() {
-                        Ok(mut process_id_guard) => {
-                            *process_id_guard = None;
-                        }
-                        Err(e) => {
-                            log_message(format!("Failed to acquire process_id lock: {e}"));
-                        }
-                    }
-                    return;
-                }
-                Err(_) => {
-                    log_message(
-                        "Failed to stop MariaDB with mysqladmin, trying alternative methods..."
-                            .to_string(),
-                    );
-                }
+        if !self.transition(ServiceState::Starting) {
+            return;
+        }
+
+        if let Some(init) = &self.config.init {
+            if !self.run_init(init) {
+                self.transition(ServiceState::Error("initialization failed".to_string()));
+                return;
             }
+        }
 
-            let mut kill_mariadbd = Command::new("taskkill");
-            kill_mariadbd
-                .arg("/F")
-                .arg("/IM")
-                .arg("mariadbd.exe");
-            
-            match self.run_command_with_output_capture(kill_mariadbd, "stop") {
-                Ok(_) => {
-                    log_message(
-                        "MariaDB stopped successfully with taskkill (mariadbd)".to_string(),
-                    );
-                    self.update_status("Stopped");
-                    match self.process_id.lock() {
-                        Ok(mut process_id_guard) => {
-                            *process_id_guard = None;
-                        }
-                        Err(e) => {
-                            log_message(format!("Failed to acquire process_id lock: {e}"));
-                        }
-                    }
-                    return;
-                }
-                Err(_) => {
-                    log_message("Failed to kill mariadbd process".to_string());
-                }
+        if let Err(e) = self.write_files() {
+            self.log(format!("Failed to start {}: {e}", self.name));
+            self.transition(ServiceState::Error(e));
+            return;
+        }
+
+        let command = self.build_command(&self.config.executable, &self.config.args);
+
+        match self.run_command_with_output_capture(command, "start") {
+            Ok(Some(child)) => {
+                let pid = child.id();
+                self.set_process_id(Some(pid));
+
+                let service = self.clone();
+                std::thread::spawn(move || service.supervise(child));
+
+                let service = self.clone();
+                std::thread::spawn(move || service.watch_readiness(pid));
             }
-            
-            match self.process_id.lock() {
-                Ok(mut process_id_guard) => {
-                    *process_id_guard = None;
-                }
-                Err(e) => {
-                    log_message(format!("Failed to acquire process_id lock: {e}"));
-                }
+            Ok(None) => {
+                self.log(format!("{} command completed but process not running", self.name));
+                self.transition(ServiceState::Stopped);
             }
-        } else {
-            let mut command = Command::new(&self.file_path);
-            command.arg("-s").arg("stop");
+            Err(e) => {
+                self.log(format!("Failed to start {}: {}", self.name, e));
+                self.transition(ServiceState::Error(e));
+            }
+        }
+    }
 
-            match self.run_command_with_output_capture(command, "stop") {
-                Ok(_) => {
-                    log_message(format!("{} stopped successfully", self.name));
-                    self.update_status("Stopped");
-                }
-                Err(e) => {
-                    log_message(format!("Failed to stop {}: {}", self.name, e));
-                    self.update_status("Error");
+    fn stop(&self) {
+        if let Some(remote) = &self.remote {
+            self.forward(remote, Request::Stop { service: Some(self.name.clone()) });
+            return;
+        }
+
+        self.log(format!("Stopping {} service...", self.name));
+
+        match self.state() {
+            ServiceState::Stopped => {
+                if let Ok(mut supervisor) = self.supervisor.lock() {
+                    supervisor.cancel_restart();
                 }
+                self.log(format!("{} is already stopped", self.name));
+                self.set_process_id(None);
+                return;
             }
-            match self.process_id.lock() {
-                Ok(mut process_id_guard) => {
-                    *process_id_guard = None;
-                }
-                Err(e) => {
-                    log_message(format!("Failed to acquire process_id lock: {e}"));
+            ServiceState::Crashed | ServiceState::CrashLoop => {
+                if let Ok(mut supervisor) = self.supervisor.lock() {
+                    supervisor.cancel_restart();
                 }
+                self.transition(ServiceState::Stopped);
+                self.set_process_id(None);
+                return;
+            }
+            ServiceState::Stopping => {
+                self.log(format!("{} is already stopping", self.name));
+                return;
+            }
+            _ => {}
+        }
+
+        if let Some(pid_file) = &self.config.pid_file {
+            if self.process_id().is_none() && !Path::new(pid_file).exists() {
+                self.log(format!(
+                    "{} PID file not found, assuming {} is not running. Setting status to Stopped.",
+                    self.name, self.name
+                ));
+                self.transition(ServiceState::Stopped);
+                self.set_process_id(None);
+                return;
             }
         }
-    }
 
-    fn status(&self) -> String {
-        match self.status.lock() {
-            Ok(status_guard) => status_guard.clone(),
+        if !self.transition(ServiceState::Stopping) {
+            return;
+        }
+
+        match self.shut_down() {
+            Ok(()) => {
+                self.transition(ServiceState::Stopped);
+            }
             Err(e) => {
-                log_message(format!("Failed to acquire status lock: {e}"));
-                "Error".to_string()
+                self.log(format!("Failed to stop {}: {}", self.name, e));
+                self.transition(ServiceState::Error(e));
             }
         }
+        self.set_process_id(None);
+    }
+
+    fn state(&self) -> ServiceState {
+        self.state.current()
     }
 }
@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use sysinfo::{ProcessRefreshKind, RefreshKind, System};
+
+use crate::config::absolute_path;
+
+/// Resolves a configured program to something that can be spawned on this
+/// platform. `./resource/nginx/nginx` becomes `nginx.exe` on Windows, a
+/// Windows-only `mysqladmin.exe` loses its extension elsewhere, and programs
+/// that are not bundled under `resource/` are looked up on `PATH`.
+pub fn resolve_executable(program: &str) -> PathBuf {
+    let path = absolute_path(program);
+    if path.is_file() {
+        return path;
+    }
+
+    for candidate in platform_candidates(&path) {
+        if candidate.is_file() {
+            return candidate;
+        }
+    }
+
+    let file_name = platform_file_name(Path::new(program));
+    if let Some(found) = find_in_path(&file_name) {
+        return found;
+    }
+
+    path
+}
+
+fn platform_candidates(path: &Path) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    let suffix = std::env::consts::EXE_SUFFIX;
+
+    if cfg!(windows) {
+        if path.extension().is_none() {
+            let mut with_suffix = path.as_os_str().to_owned();
+            with_suffix.push(suffix);
+            candidates.push(PathBuf::from(with_suffix));
+        }
+    } else if is_windows_executable(path) {
+        candidates.push(path.with_extension(""));
+    }
+
+    candidates
+}
+
+fn platform_file_name(program: &Path) -> String {
+    let path = if !cfg!(windows) && is_windows_executable(program) {
+        program.with_extension("")
+    } else {
+        program.to_path_buf()
+    };
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if cfg!(windows) && path.extension().is_none() {
+        format!("{name}{}", std::env::consts::EXE_SUFFIX)
+    } else {
+        name
+    }
+}
+
+fn is_windows_executable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
+
+fn find_in_path(file_name: &str) -> Option<PathBuf> {
+    if file_name.is_empty() {
+        return None;
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Finds running processes whose name matches one of `names`, ignoring case
+/// and a trailing `.exe`.
+pub fn find_pids_by_name(names: &[String]) -> Vec<u32> {
+    let sys = System::new_with_specifics(
+        RefreshKind::new().with_processes(ProcessRefreshKind::new()),
+    );
+
+    let wanted: Vec<String> = names.iter().map(|name| normalize_name(name)).collect();
+    sys.processes()
+        .iter()
+        .filter(|(_, process)| wanted.contains(&normalize_name(process.name())))
+        .map(|(pid, _)| pid.as_u32())
+        .collect()
+}
+
+/// Sums CPU usage (percent) and resident memory (bytes) of the processes in
+/// `sys` whose name matches one of `names`. CPU usage is only meaningful once
+/// `sys` has been refreshed twice.
+pub fn process_usage(sys: &System, names: &[String]) -> (f32, u64) {
+    usage_of(sys, &processes_named(sys, names))
+}
+
+/// Sums CPU usage and resident memory of process `pid` and its descendants,
+/// such as the workers of a php-cgi or Nginx master. `None` when `pid` is
+/// not in `sys`.
+pub fn process_tree_usage(sys: &System, pid: u32) -> Option<(f32, u64)> {
+    process_tree(sys, pid).map(|pids| usage_of(sys, &pids))
+}
+
+/// The processes in `sys` whose name matches one of `names`.
+pub fn processes_named(sys: &System, names: &[String]) -> Vec<sysinfo::Pid> {
+    let wanted: Vec<String> = names.iter().map(|name| normalize_name(name)).collect();
+    sys.processes()
+        .values()
+        .filter(|process| wanted.contains(&normalize_name(process.name())))
+        .map(|process| process.pid())
+        .collect()
+}
+
+/// Process `pid` and its descendants. `None` when `pid` is not in `sys`.
+pub fn process_tree(sys: &System, pid: u32) -> Option<Vec<sysinfo::Pid>> {
+    let root = sysinfo::Pid::from_u32(pid);
+    sys.process(root)?;
+
+    Some(
+        sys.processes()
+            .values()
+            .filter(|process| descends_from(sys, process, root))
+            .map(|process| process.pid())
+            .collect(),
+    )
+}
+
+/// Sums CPU usage and resident memory of `pids`.
+pub fn usage_of(sys: &System, pids: &[sysinfo::Pid]) -> (f32, u64) {
+    pids.iter()
+        .filter_map(|pid| sys.process(*pid))
+        .fold((0.0, 0), |(cpu, memory), process| (cpu + process.cpu_usage(), memory + process.memory()))
+}
+
+fn descends_from(sys: &System, process: &sysinfo::Process, root: sysinfo::Pid) -> bool {
+    let mut current = Some(process);
+    // Bounded, in case reused PIDs make the parent links loop.
+    for _ in 0..64 {
+        match current {
+            Some(process) if process.pid() == root => return true,
+            Some(process) => current = process.parent().and_then(|parent| sys.process(parent)),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Name of the running process with `pid`, or `None` if there is none.
+pub fn process_name(pid: u32) -> Option<String> {
+    let mut sys = System::new();
+    let pid = sysinfo::Pid::from_u32(pid);
+    if !sys.refresh_process(pid) {
+        return None;
+    }
+    sys.process(pid).map(|process| process.name().to_string())
+}
+
+/// Whether a process called `actual` is one of `names`. Linux reports at
+/// most 15 characters of a process name, so a truncated name also matches.
+pub fn name_matches(actual: &str, names: &[String]) -> bool {
+    const LINUX_COMM_LEN: usize = 15;
+
+    let actual = normalize_name(actual);
+    names.iter().map(|name| normalize_name(name)).any(|name| {
+        name == actual || (actual.len() == LINUX_COMM_LEN && name.starts_with(&actual))
+    })
+}
+
+fn normalize_name(name: &str) -> String {
+    let lower = name.to_lowercase();
+    lower.strip_suffix(".exe").map(str::to_string).unwrap_or(lower)
+}
+
+/// Stops a process, giving it `grace` to exit after SIGTERM before sending
+/// SIGKILL.
+#[cfg(unix)]
+pub fn terminate(pid: u32, grace: Duration) -> Result<(), String> {
+    let pid = pid as libc::pid_t;
+
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(());
+        }
+        return Err(format!("Failed to send SIGTERM to {pid}: {error}"));
+    }
+
+    let start_time = Instant::now();
+    while start_time.elapsed() < grace {
+        if !is_alive(pid as u32) {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    if unsafe { libc::kill(pid, libc::SIGKILL) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(format!("Failed to send SIGKILL to {pid}: {error}"));
+        }
+    }
+    Ok(())
+}
+
+/// Stops a process with `taskkill`. Windows has no SIGTERM equivalent for
+/// console-less processes, so the grace period only bounds the wait.
+#[cfg(windows)]
+pub fn terminate(pid: u32, grace: Duration) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    let output = Command::new("taskkill")
+        .arg("/F")
+        .arg("/PID")
+        .arg(pid.to_string())
+        .creation_flags(0x08000000)
+        .output()
+        .map_err(|e| format!("Failed to run taskkill: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "taskkill failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let start_time = Instant::now();
+    while start_time.elapsed() < grace && is_alive(pid) {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+/// Interrupts a process started in its own process group, and everything
+/// it started, like Ctrl+C in a terminal. With `force` they are killed.
+#[cfg(unix)]
+pub fn interrupt_group(pid: u32, force: bool) -> Result<(), String> {
+    let signal = if force { libc::SIGKILL } else { libc::SIGINT };
+    if unsafe { libc::kill(-(pid as libc::pid_t), signal) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(format!("Failed to signal process group {pid}: {error}"));
+        }
+    }
+    Ok(())
+}
+
+/// Windows cannot deliver Ctrl+C to a windowless process, so the process
+/// tree is always killed.
+#[cfg(windows)]
+pub fn interrupt_group(pid: u32, _force: bool) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+
+    let output = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .creation_flags(0x08000000)
+        .output()
+        .map_err(|e| format!("Failed to run taskkill: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("taskkill failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn is_alive(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(windows)]
+pub fn is_alive(pid: u32) -> bool {
+    let mut sys = System::new();
+    sys.refresh_process(sysinfo::Pid::from_u32(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("mariadbd.exe"), "mariadbd");
+        assert_eq!(normalize_name("Nginx"), "nginx");
+    }
+
+    #[test]
+    fn test_name_matches() {
+        let names = vec!["mariadbd".to_string(), "mariadb-install-db".to_string()];
+        assert!(name_matches("MariaDBd.exe", &names));
+        assert!(name_matches("mariadb-install", &names));
+        assert!(!name_matches("mariadb", &names));
+        assert!(!name_matches("bash", &names));
+    }
+
+    #[test]
+    fn test_process_name_of_self() {
+        let own = std::env::current_exe().unwrap();
+        let name = process_name(std::process::id()).unwrap();
+        assert!(name_matches(&name, &[own.file_stem().unwrap().to_string_lossy().into_owned()]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_executable_strips_exe_suffix() {
+        let resolved = resolve_executable("./no-such-dir/sh.exe");
+        assert_eq!(resolved.file_name().unwrap(), "sh");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_terminate_child() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        let waiter = std::thread::spawn(move || child.wait());
+        assert!(terminate(pid, Duration::from_secs(2)).is_ok());
+        assert!(!waiter.join().unwrap().unwrap().success());
+    }
+}
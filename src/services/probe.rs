@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::config::Readiness;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Health {
+    /// No probe has run yet, or the service has no probe to run.
+    Unknown,
+    Healthy,
+    Unhealthy { failures: u32, last_error: String },
+}
+
+/// Runs a single readiness probe attempt.
+pub fn check(readiness: &Readiness) -> Result<(), String> {
+    match readiness {
+        Readiness::Spawned | Readiness::Delay { .. } => Ok(()),
+        Readiness::Tcp { host, port } => connect(host, *port).map(|_| ()),
+        Readiness::Http { url, expect_status } => check_http(url, *expect_status),
+        Readiness::Mysql { host, port } => check_mysql(host, *port),
+    }
+}
+
+/// Whether the probe says something about a live service, as opposed to
+/// only waiting after spawn.
+pub fn is_active_probe(readiness: &Readiness) -> bool {
+    !matches!(readiness, Readiness::Spawned | Readiness::Delay { .. })
+}
+
+fn connect(host: &str, port: u16) -> Result<TcpStream, String> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {host}:{port}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("No address found for {host}:{port}"))?;
+
+    let stream = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT)
+        .map_err(|e| format!("TCP connect to {host}:{port} failed: {e}"))?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT)).ok();
+    stream.set_write_timeout(Some(PROBE_TIMEOUT)).ok();
+    Ok(stream)
+}
+
+/// Splits `http://host:port/path` into its parts. Only plain HTTP is
+/// supported, which is all a local readiness probe needs.
+fn parse_http_url(url: &str) -> Result<(String, u16, String), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Only http:// URLs are supported for probes: {url}"))?;
+
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| format!("Invalid port in probe URL: {url}"))?,
+        ),
+        None => (authority, 80),
+    };
+
+    if host.is_empty() {
+        return Err(format!("Missing host in probe URL: {url}"));
+    }
+
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+fn check_http(url: &str, expect_status: u16) -> Result<(), String> {
+    let (host, port, path) = parse_http_url(url)?;
+    let mut stream = connect(&host, port)?;
+
+    let request = format!(
+        "GET {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: xerve-probe\r\nConnection: close\r\n\r\n"
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("Failed to send HTTP probe to {url}: {e}"))?;
+
+    let mut buffer = [0u8; 64];
+    let read = stream
+        .read(&mut buffer)
+        .map_err(|e| format!("Failed to read HTTP probe response from {url}: {e}"))?;
+
+    let status = parse_status_code(&buffer[..read])
+        .ok_or_else(|| format!("Invalid HTTP response from {url}"))?;
+
+    if status == expect_status {
+        Ok(())
+    } else {
+        Err(format!("{url} returned HTTP {status}, expected {expect_status}"))
+    }
+}
+
+fn parse_status_code(response: &[u8]) -> Option<u16> {
+    let line = std::str::from_utf8(response).ok()?.lines().next()?;
+    let mut parts = line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+/// Reads the server greeting a MariaDB/MySQL server sends on connect and
+/// checks that it is a protocol 10 handshake.
+fn check_mysql(host: &str, port: u16) -> Result<(), String> {
+    let mut stream = connect(host, port)?;
+
+    let mut header = [0u8; 4];
+    stream
+        .read_exact(&mut header)
+        .map_err(|e| format!("No MySQL handshake from {host}:{port}: {e}"))?;
+
+    let mut first = [0u8; 1];
+    stream
+        .read_exact(&mut first)
+        .map_err(|e| format!("Truncated MySQL handshake from {host}:{port}: {e}"))?;
+
+    match first[0] {
+        10 => Ok(()),
+        0xff => Err(format!("MySQL server at {host}:{port} refused the connection")),
+        other => Err(format!("Unexpected MySQL protocol version {other} at {host}:{port}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_http_url() {
+        assert_eq!(
+            parse_http_url("http://127.0.0.1:8080/health").unwrap(),
+            ("127.0.0.1".to_string(), 8080, "/health".to_string())
+        );
+        assert_eq!(
+            parse_http_url("http://localhost").unwrap(),
+            ("localhost".to_string(), 80, "/".to_string())
+        );
+        assert!(parse_http_url("https://localhost").is_err());
+    }
+
+    #[test]
+    fn test_parse_status_code() {
+        assert_eq!(parse_status_code(b"HTTP/1.1 204 No Content\r\n"), Some(204));
+        assert_eq!(parse_status_code(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_mysql_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            socket.write_all(&[5, 0, 0, 0, 10, b'1', b'1', b'.', 0]).unwrap();
+        });
+
+        assert!(check_mysql("127.0.0.1", port).is_ok());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_tcp_probe_fails_on_closed_port() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let probe = Readiness::Tcp { host: "127.0.0.1".to_string(), port };
+        assert!(check(&probe).is_err());
+    }
+}
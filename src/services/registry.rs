@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use crate::config::{start_order, XerveConfig};
+use crate::daemon::{DaemonClient, Request, Response};
+use crate::services::{log_message, platform, Service, ServiceInfo, ServiceState};
+
+/// How often a registry attached to a daemon refreshes its services' state.
+const DAEMON_POLL: Duration = Duration::from_millis(500);
+
+/// The set of services declared in `xerve.toml`, in declaration order, along
+/// with the order their dependencies require them to start in.
+#[derive(Clone)]
+pub struct ServiceRegistry {
+    services: Vec<ServiceInfo>,
+    start_order: Vec<usize>,
+    daemon: Option<DaemonClient>,
+}
+
+impl ServiceRegistry {
+    pub fn from_config(config: &XerveConfig) -> Self {
+        // The config has already been validated, so this only fails for
+        // hand-built configs; fall back to declaration order.
+        let start_order =
+            start_order(&config.services).unwrap_or_else(|_| (0..config.services.len()).collect());
+
+        let mut services: Vec<ServiceInfo> = config.services.iter().cloned().map(ServiceInfo::from_config).collect();
+        let names: Vec<Vec<String>> = services.iter().map(ServiceInfo::process_names).collect();
+        for (index, service) in services.iter_mut().enumerate() {
+            service.shared_process_names = names.iter().enumerate().any(|(other, other_names)| {
+                other != index && names[index].iter().any(|name| platform::name_matches(name, other_names))
+            });
+        }
+
+        ServiceRegistry {
+            services,
+            start_order,
+            daemon: None,
+        }
+    }
+
+    /// Adopts service processes left running by an earlier session. Only
+    /// the process that owns the services should do this, not daemon clients.
+    pub fn adopt_running(&self) {
+        for service in &self.services {
+            service.adopt_running();
+        }
+    }
+
+    /// Makes this registry a client of a running daemon: commands are
+    /// forwarded to it, and a background thread mirrors its service states.
+    pub fn attach_daemon(&mut self, client: DaemonClient) {
+        for service in &mut self.services {
+            service.attach_daemon(client.clone());
+        }
+        self.daemon = Some(client.clone());
+
+        let services = self.services.clone();
+        std::thread::spawn(move || {
+            let mut connected = true;
+            loop {
+                match client.request(&Request::Status) {
+                    Ok(Response::Services { services: snapshots }) => {
+                        if !connected {
+                            log_message("Reconnected to the Xerve daemon.".to_string());
+                            connected = true;
+                        }
+                        for snapshot in snapshots {
+                            if let Some(service) = services.iter().find(|s| s.name.eq_ignore_ascii_case(&snapshot.name)) {
+                                service.apply_snapshot(snapshot);
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        if connected {
+                            log_message(format!("Lost connection to the Xerve daemon: {e}"));
+                            connected = false;
+                        }
+                    }
+                }
+                std::thread::sleep(DAEMON_POLL);
+            }
+        });
+    }
+
+    pub fn daemon(&self) -> Option<&DaemonClient> {
+        self.daemon.as_ref()
+    }
+
+    /// Sends a registry-wide request to the daemon.
+    fn forward(daemon: &DaemonClient, request: Request) -> Result<(), String> {
+        let result = match daemon.request(&request) {
+            Ok(Response::Error { message }) => Err(message),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            log_message(e.clone());
+        }
+        result
+    }
+
+    pub fn services(&self) -> &[ServiceInfo] {
+        &self.services
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ServiceInfo> {
+        self.services.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ServiceInfo> {
+        self.services
+            .iter()
+            .find(|service| service.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Services ordered so that dependencies come first.
+    pub fn start_order(&self) -> impl Iterator<Item = &ServiceInfo> {
+        self.start_order.iter().map(|&i| &self.services[i])
+    }
+
+    /// Services ordered so that dependents stop before their dependencies.
+    pub fn stop_order(&self) -> impl Iterator<Item = &ServiceInfo> {
+        self.start_order.iter().rev().map(|&i| &self.services[i])
+    }
+
+    /// Starts every service in dependency order, waiting for each one to
+    /// become ready before starting the next. Stops at the first failure.
+    pub fn start_all(&self) -> Result<(), String> {
+        if let Some(daemon) = &self.daemon {
+            return Self::forward(daemon, Request::Start { service: None });
+        }
+
+        log_message("Starting all services...".to_string());
+
+        for service in self.start_order() {
+            if service.state() != ServiceState::Running {
+                service.start();
+            }
+
+            if let Err(e) = service.wait_until_ready(service.startup_timeout() + Duration::from_secs(5)) {
+                let message = format!("Start all aborted: {} did not become ready: {e}", service.name);
+                log_message(message.clone());
+                return Err(message);
+            }
+        }
+
+        log_message("All services started.".to_string());
+        Ok(())
+    }
+
+    /// Starts `name` after starting and waiting for everything it depends on.
+    pub fn start_with_dependencies(&self, name: &str) -> Result<(), String> {
+        let Some(target) = self.get(name) else {
+            return Err(format!("Unknown service '{name}'"));
+        };
+
+        if let Some(daemon) = &self.daemon {
+            return Self::forward(daemon, Request::Start { service: Some(target.name.clone()) });
+        }
+
+        let mut needed = Vec::new();
+        let mut pending: Vec<&str> = target.depends_on().iter().map(String::as_str).collect();
+        while let Some(dependency) = pending.pop() {
+            if let Some(service) = self.get(dependency) {
+                if !needed.contains(&service.name) {
+                    needed.push(service.name.clone());
+                    pending.extend(service.depends_on().iter().map(String::as_str));
+                }
+            }
+        }
+
+        for service in self.start_order().filter(|service| needed.contains(&service.name)) {
+            if service.state() != ServiceState::Running {
+                log_message(format!("Starting {} first, {} depends on it", service.name, target.name));
+                service.start();
+            }
+
+            if let Err(e) = service.wait_until_ready(service.startup_timeout() + Duration::from_secs(5)) {
+                let message = format!(
+                    "Not starting {}: dependency {} did not become ready: {e}",
+                    target.name, service.name
+                );
+                log_message(message.clone());
+                return Err(message);
+            }
+        }
+
+        target.start();
+        Ok(())
+    }
+
+    /// Stops the service called `name` if it is running, then starts it
+    /// again with its dependencies.
+    pub fn restart(&self, name: &str) -> Result<(), String> {
+        let Some(service) = self.get(name) else {
+            return Err(format!("Unknown service '{name}'"));
+        };
+        if service.state() != ServiceState::Stopped {
+            service.stop();
+        }
+        self.start_with_dependencies(name)
+    }
+
+    /// Stops every service, dependents first.
+    pub fn stop_all(&self) {
+        if let Some(daemon) = &self.daemon {
+            let _ = Self::forward(daemon, Request::Stop { service: None });
+            return;
+        }
+
+        log_message("Stopping all services...".to_string());
+
+        for service in self.stop_order() {
+            if service.state().is_active() {
+                service.stop();
+            }
+        }
+
+        log_message("All services stopped.".to_string());
+    }
+}
+
+impl<'a> IntoIterator for &'a ServiceRegistry {
+    type Item = &'a ServiceInfo;
+    type IntoIter = std::slice::Iter<'a, ServiceInfo>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_process_names() {
+        let config = XerveConfig::parse(
+            r#"
+            [[services]]
+            name = "Nginx"
+            executable = "nginx"
+
+            [[services]]
+            name = "PHP 8.3"
+            executable = "./resource/php-8.3.0/php-cgi"
+
+            [[services]]
+            name = "PHP 8.4"
+            executable = "./resource/php-8.4.11/php-cgi"
+            "#,
+        )
+        .unwrap();
+        let registry = ServiceRegistry::from_config(&config);
+
+        let owns: Vec<bool> = registry.iter().map(ServiceInfo::owns_process_names).collect();
+        assert_eq!(owns, [true, false, false]);
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory for state Xerve keeps between runs: PID records of the
+/// processes it starts, the daemon socket, the API token, the prompt and
+/// SQL console history, generated PHP-FPM pools and root passwords set from
+/// the Databases tab.
+const RUN_DIR: &str = "run";
+
+/// File-system friendly form of a service name, e.g. `Nginx` -> `nginx`.
+pub fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+pub fn pid_path(name: &str) -> PathBuf {
+    PathBuf::from(RUN_DIR).join(format!("{}.pid", slug(name)))
+}
+
+pub fn write_pid(name: &str, pid: u32) -> Result<(), String> {
+    std::fs::create_dir_all(RUN_DIR).map_err(|e| format!("Failed to create {RUN_DIR}: {e}"))?;
+    std::fs::write(pid_path(name), pid.to_string())
+        .map_err(|e| format!("Failed to write PID file for {name}: {e}"))
+}
+
+pub fn remove_pid(name: &str) {
+    let _ = std::fs::remove_file(pid_path(name));
+}
+
+/// Control socket of the Xerve daemon.
+pub fn socket_path() -> PathBuf {
+    PathBuf::from(RUN_DIR).join("xerve.sock")
+}
+
+/// Token for the HTTP management API when none is configured.
+pub fn api_token_path() -> PathBuf {
+    PathBuf::from(RUN_DIR).join("api-token")
+}
+
+/// Commands entered in the terminal prompt, oldest first.
+pub fn command_history_path() -> PathBuf {
+    PathBuf::from(RUN_DIR).join("command-history")
+}
+
+/// Working directory of the terminal prompt.
+pub fn command_dir_path() -> PathBuf {
+    PathBuf::from(RUN_DIR).join("command-dir")
+}
+
+/// Scripts run in the SQL console, as a JSON list.
+pub fn sql_history_path() -> PathBuf {
+    PathBuf::from(RUN_DIR).join("sql-history.json")
+}
+
+/// Pool configuration generated for a PHP-FPM service.
+pub fn php_fpm_config_path(service: &str) -> PathBuf {
+    PathBuf::from(RUN_DIR).join(format!("{}-fpm.conf", slug(service)))
+}
+
+/// Root password set from the Databases tab. Xerve signs in with it instead
+/// of the password in xerve.toml.
+pub fn root_password_path(service: &str) -> PathBuf {
+    PathBuf::from(RUN_DIR).join(format!("{}-root-password", slug(service)))
+}
+
+/// Writes a secret that only the current user may read.
+pub fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(path)
+        .and_then(|mut file| {
+            // `mode` only applies to new files; tighten an existing one
+            // before the secret goes in.
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+            }
+            file.write_all(contents.as_bytes())
+        })
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_tightens_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("site.key");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, "secret").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+static SUBSCRIBERS: Mutex<Vec<Sender<ServiceEvent>>> = Mutex::new(Vec::new());
+
+/// Returns a receiver that gets every state transition of every service.
+pub fn subscribe() -> Receiver<ServiceEvent> {
+    let (sender, receiver) = mpsc::channel();
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.push(sender);
+    }
+    receiver
+}
+
+fn publish(event: ServiceEvent) {
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum ServiceState {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+    Crashed,
+    CrashLoop,
+    Error(String),
+}
+
+impl ServiceState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServiceState::Stopped => "Stopped",
+            ServiceState::Starting => "Starting...",
+            ServiceState::Running => "Running",
+            ServiceState::Stopping => "Stopping...",
+            ServiceState::Crashed => "Crashed",
+            ServiceState::CrashLoop => "Crash loop",
+            ServiceState::Error(_) => "Error",
+        }
+    }
+
+    /// Whether a service process may be alive in this state.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            ServiceState::Starting | ServiceState::Running | ServiceState::Stopping
+        )
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        matches!(self, ServiceState::Starting | ServiceState::Stopping)
+    }
+
+    pub fn can_transition_to(&self, next: &ServiceState) -> bool {
+        use ServiceState::*;
+
+        matches!(
+            (self, next),
+            (Stopped, Starting | CrashLoop)
+                | (Starting, Running | Stopping | Stopped | Crashed | Error(_))
+                | (Running, Stopping | Stopped | Crashed | Error(_))
+                | (Stopping, Stopped | Crashed | Error(_))
+                | (Crashed, Starting | Stopped | CrashLoop)
+                | (CrashLoop, Starting | Stopped)
+                | (Error(_), Starting | Stopping | Stopped | Crashed)
+        )
+    }
+}
+
+impl fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceState::Error(reason) => write!(f, "Error: {reason}"),
+            state => f.write_str(state.label().trim_end_matches("...")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceEvent {
+    pub service: String,
+    pub from: ServiceState,
+    pub to: ServiceState,
+    pub at: SystemTime,
+}
+
+impl fmt::Display for ServiceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = chrono::DateTime::<chrono::Local>::from(self.at);
+        write!(
+            f,
+            "[{}] {}: {} -> {}",
+            at.format("%H:%M:%S"),
+            self.service,
+            self.from,
+            self.to
+        )
+    }
+}
+
+struct Current {
+    state: ServiceState,
+    since: Instant,
+}
+
+/// Holds a service's state and only lets it change along valid transitions,
+/// publishing an event for each change.
+pub struct StateMachine {
+    service: String,
+    current: Mutex<Current>,
+}
+
+impl StateMachine {
+    pub fn new(service: &str, initial: ServiceState) -> Self {
+        StateMachine {
+            service: service.to_string(),
+            current: Mutex::new(Current {
+                state: initial,
+                since: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn current(&self) -> ServiceState {
+        match self.current.lock() {
+            Ok(current) => current.state.clone(),
+            Err(e) => ServiceState::Error(format!("Failed to acquire state lock: {e}")),
+        }
+    }
+
+    pub fn since(&self) -> Option<Instant> {
+        self.current.lock().ok().map(|current| current.since)
+    }
+
+    /// Moves to `next`. Transitioning to the current state is a no-op.
+    pub fn transition(&self, next: ServiceState) -> Result<(), String> {
+        let mut current = self
+            .current
+            .lock()
+            .map_err(|e| format!("Failed to acquire state lock: {e}"))?;
+
+        if current.state == next {
+            return Ok(());
+        }
+
+        if !current.state.can_transition_to(&next) {
+            return Err(format!(
+                "{}: invalid state transition from {} to {}",
+                self.service, current.state, next
+            ));
+        }
+
+        let from = std::mem::replace(&mut current.state, next.clone());
+        current.since = Instant::now();
+        drop(current);
+
+        publish(ServiceEvent {
+            service: self.service.clone(),
+            from,
+            to: next,
+            at: SystemTime::now(),
+        });
+        Ok(())
+    }
+
+    /// Sets a state that was observed rather than driven: a snapshot from the
+    /// daemon that owns the service, or a process adopted at startup. Such
+    /// changes skip intermediate states, so the transition is not validated.
+    pub fn observe(&self, next: ServiceState) {
+        let Ok(mut current) = self.current.lock() else {
+            return;
+        };
+        if current.state == next {
+            return;
+        }
+
+        let from = std::mem::replace(&mut current.state, next.clone());
+        current.since = Instant::now();
+        drop(current);
+
+        publish(ServiceEvent {
+            service: self.service.clone(),
+            from,
+            to: next,
+            at: SystemTime::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_lifecycle() {
+        let machine = StateMachine::new("Test", ServiceState::Stopped);
+        assert!(machine.transition(ServiceState::Starting).is_ok());
+        assert!(machine.transition(ServiceState::Running).is_ok());
+        assert!(machine.transition(ServiceState::Stopping).is_ok());
+        assert!(machine.transition(ServiceState::Stopped).is_ok());
+        assert_eq!(machine.current(), ServiceState::Stopped);
+    }
+
+    #[test]
+    fn test_invalid_transition_rejected() {
+        let machine = StateMachine::new("Test", ServiceState::Stopped);
+        assert!(machine.transition(ServiceState::Running).is_err());
+        assert!(machine.transition(ServiceState::Crashed).is_err());
+        assert_eq!(machine.current(), ServiceState::Stopped);
+    }
+
+    #[test]
+    fn test_clean_exit_can_end_in_crash_loop() {
+        let machine = StateMachine::new("Test", ServiceState::Stopped);
+        assert!(machine.transition(ServiceState::CrashLoop).is_ok());
+        assert!(machine.transition(ServiceState::Starting).is_ok());
+    }
+
+    #[test]
+    fn test_events_published() {
+        let events = subscribe();
+        let machine = StateMachine::new("EventTest", ServiceState::Stopped);
+        machine.transition(ServiceState::Starting).unwrap();
+        machine.transition(ServiceState::Error("boom".to_string())).unwrap();
+
+        let received: Vec<ServiceEvent> = events
+            .try_iter()
+            .filter(|event| event.service == "EventTest")
+            .collect();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].from, ServiceState::Stopped);
+        assert_eq!(received[1].to, ServiceState::Error("boom".to_string()));
+    }
+}
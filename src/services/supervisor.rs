@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{RestartConfig, RestartPolicy};
+
+const STDERR_TAIL_LINES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExitInfo {
+    pub code: Option<i32>,
+    pub description: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RestartDecision {
+    /// Leave the service in its current state.
+    None,
+    /// Start the service again after the given delay.
+    Restart { delay: Duration, attempt: usize },
+    /// The process finished its planned run; start a fresh one right away.
+    Recycle,
+    /// Too many restarts within the window; give up.
+    CrashLoop,
+}
+
+/// Per-service bookkeeping for unexpected exits: recent restarts, the last
+/// exit status and the tail of the process's stderr.
+#[derive(Default)]
+pub struct Supervisor {
+    restarts: VecDeque<Instant>,
+    last_exit: Option<ExitInfo>,
+    stderr_tail: VecDeque<String>,
+    restart_pending: bool,
+}
+
+impl Supervisor {
+    pub fn record_stderr(&mut self, line: String) {
+        self.stderr_tail.push_back(line);
+        while self.stderr_tail.len() > STDERR_TAIL_LINES {
+            self.stderr_tail.pop_front();
+        }
+    }
+
+    pub fn record_exit(&mut self, exit: ExitInfo) {
+        self.last_exit = Some(exit);
+    }
+
+    pub fn last_exit(&self) -> Option<ExitInfo> {
+        self.last_exit.clone()
+    }
+
+    pub fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.iter().cloned().collect()
+    }
+
+    pub fn restarts_in_window(&self) -> usize {
+        self.restarts.len()
+    }
+
+    /// Forgets restart history, e.g. when the user starts the service by hand.
+    pub fn reset(&mut self) {
+        self.restarts.clear();
+        self.stderr_tail.clear();
+        self.restart_pending = false;
+    }
+
+    pub fn cancel_restart(&mut self) {
+        self.restart_pending = false;
+    }
+
+    /// Returns whether a scheduled restart is still wanted and clears it.
+    pub fn take_pending_restart(&mut self) -> bool {
+        std::mem::take(&mut self.restart_pending)
+    }
+
+    /// Decides what to do after the process exited on its own.
+    pub fn decide(&mut self, config: &RestartConfig, exited_cleanly: bool, now: Instant) -> RestartDecision {
+        if config.policy == RestartPolicy::Recycle && exited_cleanly {
+            self.restart_pending = true;
+            return RestartDecision::Recycle;
+        }
+
+        let wanted = match config.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure | RestartPolicy::Recycle => !exited_cleanly,
+            RestartPolicy::Always => true,
+        };
+        if !wanted {
+            return RestartDecision::None;
+        }
+
+        let window = Duration::from_secs(config.window_secs);
+        while self
+            .restarts
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > window)
+        {
+            self.restarts.pop_front();
+        }
+
+        if self.restarts.len() >= config.max_restarts {
+            self.restart_pending = false;
+            return RestartDecision::CrashLoop;
+        }
+
+        self.restarts.push_back(now);
+        self.restart_pending = true;
+
+        let attempt = self.restarts.len();
+        let factor = 1u64 << (attempt - 1).min(16);
+        let delay = Duration::from_millis(
+            config.backoff_ms.saturating_mul(factor).min(config.max_backoff_ms),
+        );
+        RestartDecision::Restart { delay, attempt }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on_failure() -> RestartConfig {
+        RestartConfig {
+            policy: RestartPolicy::OnFailure,
+            max_restarts: 3,
+            window_secs: 60,
+            backoff_ms: 100,
+            max_backoff_ms: 250,
+        }
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let mut supervisor = Supervisor::default();
+        let now = Instant::now();
+        let delays: Vec<RestartDecision> = (0..3).map(|_| supervisor.decide(&on_failure(), false, now)).collect();
+        assert_eq!(delays[0], RestartDecision::Restart { delay: Duration::from_millis(100), attempt: 1 });
+        assert_eq!(delays[1], RestartDecision::Restart { delay: Duration::from_millis(200), attempt: 2 });
+        assert_eq!(delays[2], RestartDecision::Restart { delay: Duration::from_millis(250), attempt: 3 });
+        assert_eq!(supervisor.decide(&on_failure(), false, now), RestartDecision::CrashLoop);
+    }
+
+    #[test]
+    fn test_window_expires_old_restarts() {
+        let mut supervisor = Supervisor::default();
+        let start = Instant::now();
+        for _ in 0..3 {
+            supervisor.decide(&on_failure(), false, start);
+        }
+        let later = start + Duration::from_secs(61);
+        assert!(matches!(supervisor.decide(&on_failure(), false, later), RestartDecision::Restart { attempt: 1, .. }));
+    }
+
+    #[test]
+    fn test_policy_respects_clean_exit() {
+        let mut supervisor = Supervisor::default();
+        assert_eq!(supervisor.decide(&on_failure(), true, Instant::now()), RestartDecision::None);
+
+        let never = RestartConfig { policy: RestartPolicy::Never, ..on_failure() };
+        assert_eq!(supervisor.decide(&never, false, Instant::now()), RestartDecision::None);
+
+        let always = RestartConfig { policy: RestartPolicy::Always, ..on_failure() };
+        assert!(matches!(supervisor.decide(&always, true, Instant::now()), RestartDecision::Restart { .. }));
+    }
+
+    #[test]
+    fn test_always_policy_gives_up_on_clean_exits() {
+        let mut supervisor = Supervisor::default();
+        let always = RestartConfig { policy: RestartPolicy::Always, ..on_failure() };
+        let now = Instant::now();
+        for attempt in 1..=3 {
+            assert!(matches!(supervisor.decide(&always, true, now), RestartDecision::Restart { attempt: a, .. } if a == attempt));
+        }
+        assert_eq!(supervisor.decide(&always, true, now), RestartDecision::CrashLoop);
+        assert!(!supervisor.take_pending_restart());
+    }
+
+    #[test]
+    fn test_recycle_skips_backoff_for_clean_exits() {
+        let mut supervisor = Supervisor::default();
+        let recycle = RestartConfig { policy: RestartPolicy::Recycle, ..on_failure() };
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(supervisor.decide(&recycle, true, now), RestartDecision::Recycle);
+        }
+        assert_eq!(supervisor.restarts_in_window(), 0);
+        assert_eq!(
+            supervisor.decide(&recycle, false, now),
+            RestartDecision::Restart { delay: Duration::from_millis(100), attempt: 1 }
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Ad-hoc commands from the terminal prompt, such as `php artisan migrate`
+//! or `composer install`. They run through the system shell in a project
+//! directory, with the default PHP install and the directories of the
+//! service executables put first on `PATH`, and their output goes to the log
+//! like service output does.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::config::absolute_path;
+use crate::logs::LogEntry;
+use crate::php::PhpVersions;
+use crate::services::{self, platform, runfiles, ServiceRegistry};
+
+/// Name under which commands and their output appear in the log.
+pub const SOURCE: &str = "Shell";
+
+const MAX_HISTORY: usize = 500;
+
+/// Where commands run until another directory is chosen.
+const DEFAULT_DIR: &str = "./resource/nginx/htdocs";
+
+/// Previously entered commands with up/down navigation, saved to a file
+/// after every command.
+pub struct History {
+    entries: Vec<String>,
+    path: PathBuf,
+    /// Entry shown while navigating, or `None` when editing a new line.
+    cursor: Option<usize>,
+    /// What was typed before navigating away from the new line.
+    draft: String,
+}
+
+impl History {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = std::fs::read_to_string(&path)
+            .map(|contents| contents.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        History {
+            entries,
+            path,
+            cursor: None,
+            draft: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: &str) {
+        self.cursor = None;
+        self.draft.clear();
+        if self.entries.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+        let excess = self.entries.len().saturating_sub(MAX_HISTORY);
+        self.entries.drain(..excess);
+
+        if let Some(parent) = self.path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(&self.path, self.entries.join("\n") + "\n");
+    }
+
+    /// The entry before the one shown, remembering `current` as the draft
+    /// when leaving the new line.
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let index = match self.cursor {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(index) => index.saturating_sub(1),
+        };
+        self.cursor = Some(index);
+        Some(self.entries[index].clone())
+    }
+
+    /// The entry after the one shown, or the draft past the newest one.
+    pub fn next(&mut self) -> Option<String> {
+        let index = self.cursor?;
+        if index + 1 < self.entries.len() {
+            self.cursor = Some(index + 1);
+            Some(self.entries[index + 1].clone())
+        } else {
+            self.cursor = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+}
+
+/// The running command, if any.
+#[derive(Default)]
+struct Running {
+    pid: Option<u32>,
+    interrupted: bool,
+}
+
+pub struct Shell {
+    dir: PathBuf,
+    tool_dirs: Vec<PathBuf>,
+    pub history: History,
+    running: Arc<Mutex<Running>>,
+}
+
+impl Shell {
+    pub fn new(registry: &ServiceRegistry, php: &PhpVersions) -> Self {
+        let dir = std::fs::read_to_string(runfiles::command_dir_path())
+            .ok()
+            .map(|dir| PathBuf::from(dir.trim()))
+            .filter(|dir| dir.is_dir())
+            .unwrap_or_else(default_dir);
+
+        // Only the default PHP version answers to `php`; the other installs'
+        // services would otherwise shadow it with whichever came first.
+        let is_php = |name: &str| php.all().iter().any(|version| version.service_name().eq_ignore_ascii_case(name));
+        let mut tool_dirs: Vec<PathBuf> = php.default_version().map(|version| version.dir.clone()).into_iter().collect();
+        for candidate in registry
+            .iter()
+            .filter(|service| !is_php(&service.name))
+            .filter_map(|service| service.executable_dir())
+        {
+            if !tool_dirs.contains(&candidate) {
+                tool_dirs.push(candidate);
+            }
+        }
+
+        Shell {
+            dir,
+            tool_dirs,
+            history: History::load(runfiles::command_history_path()),
+            running: Arc::new(Mutex::new(Running::default())),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.lock().map(|running| running.pid.is_some()).unwrap_or(false)
+    }
+
+    /// Runs `line`, or changes directory for `cd <dir>`. Output is logged as
+    /// it arrives; this returns once the command has started.
+    pub fn run(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        self.history.push(line);
+        services::log_entry(LogEntry::service(SOURCE, format!("{} $ {line}", self.dir.display())));
+
+        if line == "cd" || line.starts_with("cd ") {
+            return self.change_dir(line[2..].trim());
+        }
+
+        if self.is_running() {
+            return Err("A command is already running; press Ctrl+C to cancel it".to_string());
+        }
+
+        let mut child = self
+            .command(line)
+            .spawn()
+            .map_err(|e| format!("Failed to run {line}: {e}"))?;
+        let pid = child.id();
+        *self.running.lock().unwrap() = Running { pid: Some(pid), interrupted: false };
+        let readers = services::capture_output(&mut child, SOURCE, None, None);
+
+        let running = Arc::clone(&self.running);
+        std::thread::spawn(move || {
+            let status = child.wait();
+            for reader in readers {
+                let _ = reader.join();
+            }
+            let interrupted = std::mem::take(&mut *running.lock().unwrap()).interrupted;
+            let message = match status {
+                _ if interrupted => "Cancelled".to_string(),
+                Ok(status) if status.success() => "Done".to_string(),
+                Ok(status) => format!("Exited with {status}"),
+                Err(e) => format!("Failed to wait for the command: {e}"),
+            };
+            services::log_entry(LogEntry::service(SOURCE, message));
+        });
+        Ok(())
+    }
+
+    /// Interrupts the running command; a second call kills it.
+    pub fn cancel(&self) -> Result<(), String> {
+        let mut running = self.running.lock().unwrap();
+        let Some(pid) = running.pid else {
+            return Ok(());
+        };
+        let force = running.interrupted;
+        running.interrupted = true;
+        platform::interrupt_group(pid, force)
+    }
+
+    fn change_dir(&mut self, target: &str) -> Result<(), String> {
+        let dir = match target {
+            "" => default_dir(),
+            target => self.dir.join(target),
+        };
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("cd: {}: {e}", dir.display()))?;
+        if !dir.is_dir() {
+            return Err(format!("cd: {} is not a directory", dir.display()));
+        }
+
+        self.dir = dir;
+        let path = runfiles::command_dir_path();
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(path, self.dir.to_string_lossy().as_bytes());
+        Ok(())
+    }
+
+    fn command(&self, line: &str) -> Command {
+        #[cfg(windows)]
+        let mut command = {
+            use std::os::windows::process::CommandExt;
+            let mut command = Command::new("cmd");
+            command.arg("/C").raw_arg(line);
+            // CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP
+            command.creation_flags(0x08000000 | 0x00000200);
+            command
+        };
+        #[cfg(not(windows))]
+        let mut command = {
+            use std::os::unix::process::CommandExt;
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(line).process_group(0);
+            command
+        };
+
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let dirs = self.tool_dirs.iter().cloned().chain(std::env::split_paths(&path));
+        if let Ok(path) = std::env::join_paths(dirs) {
+            command.env("PATH", path);
+        }
+
+        command
+            .current_dir(&self.dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+}
+
+fn default_dir() -> PathBuf {
+    let dir = absolute_path(DEFAULT_DIR);
+    if dir.is_dir() {
+        dir
+    } else {
+        std::env::current_dir().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_navigation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = History::load(dir.path().join("history"));
+        assert_eq!(history.previous("typed"), None);
+
+        history.push("composer install");
+        history.push("php artisan migrate");
+        history.push("php artisan migrate");
+
+        assert_eq!(history.previous("php art").as_deref(), Some("php artisan migrate"));
+        assert_eq!(history.previous("").as_deref(), Some("composer install"));
+        assert_eq!(history.previous("").as_deref(), Some("composer install"));
+        assert_eq!(history.next().as_deref(), Some("php artisan migrate"));
+        assert_eq!(history.next().as_deref(), Some("php art"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn test_default_php_comes_first_on_path() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in ["php-8.2.29", "php-8.4.11"] {
+            std::fs::create_dir_all(dir.path().join(version)).unwrap();
+            std::fs::write(dir.path().join(version).join(format!("php-cgi{}", std::env::consts::EXE_SUFFIX)), "").unwrap();
+        }
+        let contents = format!("[php]\ndir = {:?}\n", dir.path().to_string_lossy());
+        let config = crate::config::XerveConfig::parse_installed(&contents).unwrap();
+        let php = PhpVersions::discover(&config.php);
+        let shell = Shell::new(&ServiceRegistry::from_config(&config), &php);
+
+        let command = shell.command("php -v");
+        let path = command.get_envs().find(|(key, _)| *key == "PATH").and_then(|(_, value)| value).unwrap();
+        let dirs: Vec<PathBuf> = std::env::split_paths(path).collect();
+        assert_eq!(dirs[0], php.find("8.4").unwrap().dir);
+        assert!(!dirs.contains(&php.find("8.2").unwrap().dir));
+    }
+
+    #[test]
+    fn test_history_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run").join("history");
+        History::load(path.clone()).push("npm run dev");
+
+        let mut reloaded = History::load(path);
+        assert_eq!(reloaded.previous("").as_deref(), Some("npm run dev"));
+    }
+}
@@ -0,0 +1,182 @@
+//! A development certificate authority for HTTPS sites. The CA is created
+//! once in `certs_dir` and signs a certificate per site; trusting it in the
+//! browser or OS store is left to the user.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use rcgen::{
+    date_time_ymd, BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    Issuer, KeyPair, KeyUsagePurpose,
+};
+
+use crate::services::runfiles;
+
+const CA_NAME: &str = "Xerve Development CA";
+const CA_FILE: &str = "xerve-ca.pem";
+const CA_KEY_FILE: &str = "xerve-ca.key";
+const CA_VALID_DAYS: i64 = 3650;
+/// Browsers refuse server certificates valid for longer than about 13 months.
+const SITE_VALID_DAYS: i64 = 397;
+
+/// Where a site's certificate and key are written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+pub struct CertificateAuthority {
+    dir: PathBuf,
+}
+
+impl CertificateAuthority {
+    pub fn new(dir: PathBuf) -> Self {
+        CertificateAuthority { dir }
+    }
+
+    /// The CA certificate to import into trust stores.
+    pub fn cert_path(&self) -> PathBuf {
+        self.dir.join(CA_FILE)
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join(CA_KEY_FILE)
+    }
+
+    pub fn site_files(&self, id: &str) -> CertFiles {
+        CertFiles {
+            cert: self.dir.join(format!("{id}.pem")),
+            key: self.dir.join(format!("{id}.key")),
+        }
+    }
+
+    /// Loads the CA, creating it on first use.
+    fn issuer(&self) -> Result<Issuer<'static, KeyPair>, String> {
+        let (cert_path, key_path) = (self.cert_path(), self.key_path());
+        if cert_path.exists() && key_path.exists() {
+            let cert = read(&cert_path)?;
+            let key = KeyPair::from_pem(&read(&key_path)?)
+                .map_err(|e| format!("Invalid CA key {}: {e}", key_path.display()))?;
+            return Issuer::from_ca_cert_pem(&cert, key)
+                .map_err(|e| format!("Invalid CA certificate {}: {e}", cert_path.display()));
+        }
+
+        let mut params = CertificateParams::default();
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, CA_NAME);
+        name.push(DnType::OrganizationName, "Xerve");
+        params.distinguished_name = name;
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+        set_validity(&mut params, CA_VALID_DAYS);
+
+        let key = KeyPair::generate().map_err(|e| format!("Failed to generate the CA key: {e}"))?;
+        let cert = params.self_signed(&key).map_err(|e| format!("Failed to create the CA: {e}"))?;
+        write(&cert_path, &cert.pem(), false)?;
+        write(&key_path, &key.serialize_pem(), true)?;
+        Ok(Issuer::new(params, key))
+    }
+
+    /// Issues a certificate for `id` covering `hosts`, which may include
+    /// wildcards such as `*.shop.test`, replacing any earlier one.
+    pub fn issue(&self, id: &str, hosts: &[String]) -> Result<CertFiles, String> {
+        let issuer = self.issuer()?;
+
+        let mut params = CertificateParams::new(hosts.to_vec()).map_err(|e| format!("Invalid host name: {e}"))?;
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, hosts.first().map(String::as_str).unwrap_or(id));
+        params.distinguished_name = name;
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature, KeyUsagePurpose::KeyEncipherment];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+        set_validity(&mut params, SITE_VALID_DAYS);
+
+        let key = KeyPair::generate().map_err(|e| format!("Failed to generate a key for {id}: {e}"))?;
+        let cert = params
+            .signed_by(&key, &issuer)
+            .map_err(|e| format!("Failed to sign the certificate for {id}: {e}"))?;
+
+        let files = self.site_files(id);
+        write(&files.cert, &cert.pem(), false)?;
+        write(&files.key, &key.serialize_pem(), true)?;
+        Ok(files)
+    }
+}
+
+/// Valid from yesterday, to allow for clock skew, for `days` days.
+fn set_validity(params: &mut CertificateParams, days: i64) {
+    let date = |at: DateTime<Utc>| date_time_ymd(at.year(), at.month() as u8, at.day() as u8);
+    let now = Utc::now();
+    params.not_before = date(now - Duration::days(1));
+    params.not_after = date(now + Duration::days(days));
+}
+
+/// When the PEM certificate at `path` expires.
+pub fn expires_at(path: &Path) -> Result<DateTime<Local>, String> {
+    let pem = std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let (_, pem) = x509_parser::pem::parse_x509_pem(&pem).map_err(|e| format!("Invalid certificate {}: {e}", path.display()))?;
+    let cert = pem.parse_x509().map_err(|e| format!("Invalid certificate {}: {e}", path.display()))?;
+    DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .map(|at| at.with_timezone(&Local))
+        .ok_or_else(|| format!("Invalid expiry date in {}", path.display()))
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+}
+
+/// Writes `contents` to `path`; private keys are readable by the owner only.
+fn write(path: &Path, contents: &str, private: bool) -> Result<(), String> {
+    if private {
+        return runfiles::write_private(path, contents);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+    }
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x509_parser::extensions::GeneralName;
+
+    #[test]
+    fn test_issue_site_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = CertificateAuthority::new(dir.path().to_path_buf());
+        let hosts = vec!["shop.localhost".to_string(), "*.shop.test".to_string()];
+        let files = ca.issue("shop", &hosts).unwrap();
+
+        let ca_pem = std::fs::read(ca.cert_path()).unwrap();
+        let site_pem = std::fs::read(&files.cert).unwrap();
+        let (_, ca_pem_block) = x509_parser::pem::parse_x509_pem(&ca_pem).unwrap();
+        let (_, site_pem_block) = x509_parser::pem::parse_x509_pem(&site_pem).unwrap();
+        let ca_cert = ca_pem_block.parse_x509().unwrap();
+        let site_cert = site_pem_block.parse_x509().unwrap();
+
+        assert!(ca_cert.is_ca());
+        assert_eq!(site_cert.issuer(), ca_cert.subject());
+        let names: Vec<String> = site_cert
+            .subject_alternative_name()
+            .unwrap()
+            .unwrap()
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, hosts);
+
+        let days = (expires_at(&files.cert).unwrap() - Local::now()).num_days();
+        assert!((SITE_VALID_DAYS - 2..=SITE_VALID_DAYS).contains(&days), "{days}");
+
+        // The CA is created once and reused.
+        ca.issue("blog", &["blog.localhost".to_string()]).unwrap();
+        assert_eq!(std::fs::read(ca.cert_path()).unwrap(), ca_pem);
+    }
+}
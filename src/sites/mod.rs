@@ -0,0 +1,560 @@
+//! Nginx virtual hosts. Sites are kept in `sites.toml`; each one becomes a
+//! server block in `<conf_dir>/sites/<site>.conf`, generated from the
+//! templates next to this file, and `nginx.conf` includes them all. HTTPS
+//! sites get a certificate from the development CA in [`certs`].
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{absolute_path, SitesConfig};
+use crate::php::PhpVersions;
+use crate::services::runfiles::slug;
+
+pub mod certs;
+
+pub use certs::CertFiles;
+use certs::CertificateAuthority;
+
+/// The site list, next to `xerve.toml`.
+pub const SITES_FILE: &str = "sites.toml";
+
+const STATIC_TEMPLATE: &str = include_str!("templates/static.conf");
+const PHP_TEMPLATE: &str = include_str!("templates/php.conf");
+
+/// Line added to the `http` block of `nginx.conf`.
+const INCLUDE: &str = "include sites/*.conf;";
+
+/// HTTPS sites listen here as well as on their own port, unless it is this one.
+pub const HTTPS_PORT: u16 = 443;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Site {
+    pub name: String,
+    /// Document root, relative to Xerve's working directory or absolute.
+    pub root: String,
+    #[serde(default)]
+    pub php: bool,
+    /// PHP version such as `8.2`; the default version when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub php_version: Option<String>,
+    pub port: u16,
+    #[serde(default)]
+    pub https: bool,
+    /// Extra host names, such as `shop.test` or `*.shop.test`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl Site {
+    /// `blog` for a site named `My Blog!`: the part of host names and file
+    /// names that stands for the site.
+    pub fn id(&self) -> String {
+        slug(&self.name).trim_matches('-').to_string()
+    }
+
+    pub fn server_name(&self) -> String {
+        format!("{}.localhost", self.id())
+    }
+
+    /// Every name the site answers to, starting with its `.localhost` one.
+    pub fn hosts(&self) -> Vec<String> {
+        std::iter::once(self.server_name()).chain(self.aliases.iter().cloned()).collect()
+    }
+
+    pub fn url(&self) -> String {
+        match (self.https, self.port) {
+            (true, _) => format!("https://{}/", self.server_name()),
+            (false, 80) => format!("http://{}/", self.server_name()),
+            (false, port) => format!("http://{}:{port}/", self.server_name()),
+        }
+    }
+
+    fn conf_name(&self) -> String {
+        format!("{}.conf", self.id())
+    }
+
+    /// The site's server block; `cert` is used when the site is HTTPS.
+    pub fn render(&self, php_fastcgi: &str, cert: Option<&CertFiles>) -> String {
+        let template = if self.php { PHP_TEMPLATE } else { STATIC_TEMPLATE };
+        let listen = match cert.filter(|_| self.https) {
+            Some(cert) => {
+                // nginx rejects a second listen on the same port.
+                let plain = match self.port {
+                    HTTPS_PORT => String::new(),
+                    port => format!("listen {port};\n    "),
+                };
+                format!(
+                    "{plain}listen {HTTPS_PORT} ssl;\n    ssl_certificate \"{}\";\n    ssl_certificate_key \"{}\";",
+                    nginx_path(&cert.cert),
+                    nginx_path(&cert.key)
+                )
+            }
+            None => format!("listen {};", self.port),
+        };
+        template
+            .replace("{{name}}", &self.name)
+            .replace("{{listen}}", &listen)
+            .replace("{{server_name}}", &self.hosts().join(" "))
+            .replace("{{root}}", &nginx_path(&absolute_path(&self.root)))
+            .replace("{{fastcgi}}", php_fastcgi)
+    }
+}
+
+/// Nginx reads forward slashes on every platform.
+fn nginx_path(path: &std::path::Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// A certificate that needs attention.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertWarning {
+    /// The site, or `None` for the CA itself.
+    pub site: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for CertWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.site {
+            Some(site) => write!(f, "{site}: {}", self.message),
+            None => write!(f, "Development CA: {}", self.message),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SitesFile {
+    #[serde(default)]
+    sites: Vec<Site>,
+}
+
+/// The configured sites and where their files go.
+pub struct Sites {
+    file: PathBuf,
+    config: SitesConfig,
+    php: PhpVersions,
+    sites: Vec<Site>,
+}
+
+impl Sites {
+    pub fn load(config: &SitesConfig, php: PhpVersions) -> Result<Self, String> {
+        Self::load_from(PathBuf::from(SITES_FILE), config, php)
+    }
+
+    pub fn load_from(file: PathBuf, config: &SitesConfig, php: PhpVersions) -> Result<Self, String> {
+        let sites = match std::fs::read_to_string(&file) {
+            Ok(contents) => {
+                toml::from_str::<SitesFile>(&contents)
+                    .map_err(|e| format!("Invalid {}: {e}", file.display()))?
+                    .sites
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {e}", file.display())),
+        };
+        Ok(Sites {
+            file,
+            config: config.clone(),
+            php,
+            sites,
+        })
+    }
+
+    pub fn sites(&self) -> &[Site] {
+        &self.sites
+    }
+
+    /// The PHP versions sites can choose from.
+    pub fn php(&self) -> &PhpVersions {
+        &self.php
+    }
+
+    /// Name of the service that serves the sites.
+    pub fn service(&self) -> &str {
+        &self.config.service
+    }
+
+    /// Document root suggested for a new site called `name`.
+    pub fn default_root(&self, name: &str) -> String {
+        let id = slug(name).trim_matches('-').to_string();
+        format!("{}/{id}", self.config.htdocs.trim_end_matches(['/', '\\']))
+    }
+
+    fn conf_dir(&self) -> PathBuf {
+        absolute_path(&self.config.conf_dir)
+    }
+
+    fn sites_dir(&self) -> PathBuf {
+        self.conf_dir().join("sites")
+    }
+
+    fn authority(&self) -> CertificateAuthority {
+        CertificateAuthority::new(absolute_path(&self.config.certs_dir))
+    }
+
+    /// The CA certificate to trust in browsers, once it exists.
+    pub fn ca_path(&self) -> Option<PathBuf> {
+        Some(self.authority().cert_path()).filter(|path| path.exists())
+    }
+
+    /// Adds `site`, writes its server block and makes sure `nginx.conf`
+    /// includes it. The document root is created when it does not exist.
+    pub fn add(&mut self, mut site: Site) -> Result<(), String> {
+        site.name = site.name.trim().to_string();
+        site.root = site.root.trim().to_string();
+        if site.id().is_empty() {
+            return Err("Site names need at least one letter or digit".to_string());
+        }
+        if self.sites.iter().any(|existing| existing.id() == site.id()) {
+            return Err(format!("A site called {} already exists", site.name));
+        }
+        if site.port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        if site.root.is_empty() {
+            site.root = self.default_root(&site.name);
+        }
+        if site.root.contains('"') {
+            return Err("Document roots can't contain quotes".to_string());
+        }
+        if site.php {
+            self.php.fastcgi(site.php_version.as_deref())?;
+        }
+        site.aliases.retain(|alias| !alias.trim().is_empty());
+        for alias in &mut site.aliases {
+            *alias = alias.trim().to_ascii_lowercase();
+            if !alias.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '*')) {
+                return Err(format!("'{alias}' is not a valid host name"));
+            }
+        }
+
+        let root = absolute_path(&site.root);
+        std::fs::create_dir_all(&root).map_err(|e| format!("Failed to create {}: {e}", root.display()))?;
+        self.include_sites()?;
+        if site.https {
+            self.authority().issue(&site.id(), &site.hosts())?;
+        }
+        self.write_site(&site)?;
+        self.sites.push(site);
+        self.save()
+    }
+
+    /// Removes the site called `name` and its server block. The document
+    /// root is left alone.
+    pub fn remove(&mut self, name: &str) -> Result<(), String> {
+        let Some(index) = self.sites.iter().position(|site| site.name == name) else {
+            return Ok(());
+        };
+        let site = &self.sites[index];
+        let cert = self.authority().site_files(&site.id());
+        for path in [self.sites_dir().join(site.conf_name()), cert.cert, cert.key] {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to remove {}: {e}", path.display())),
+            }
+        }
+        self.sites.remove(index);
+        self.save()
+    }
+
+    /// Issues a fresh certificate for the HTTPS site called `name`.
+    pub fn renew(&self, name: &str) -> Result<(), String> {
+        let site = self
+            .sites
+            .iter()
+            .find(|site| site.name == name && site.https)
+            .ok_or_else(|| format!("{name} is not an HTTPS site"))?;
+        self.authority().issue(&site.id(), &site.hosts())?;
+        self.write_site(site)
+    }
+
+    /// Certificates that are missing, expired or expire within
+    /// `expiry_warning_days`.
+    pub fn certificate_warnings(&self) -> Vec<CertWarning> {
+        let authority = self.authority();
+        let warn_before = chrono::Duration::days(self.config.expiry_warning_days.into());
+        let now = chrono::Local::now();
+        let check = |site: Option<&Site>, path: PathBuf| {
+            let message = match certs::expires_at(&path) {
+                Err(_) if !path.exists() => "certificate is missing".to_string(),
+                Err(e) => e,
+                Ok(expires) if expires <= now => format!("certificate expired on {}", expires.format("%Y-%m-%d")),
+                Ok(expires) if expires - now <= warn_before => {
+                    format!("certificate expires in {} days", (expires - now).num_days())
+                }
+                Ok(_) => return None,
+            };
+            Some(CertWarning { site: site.map(|site| site.name.clone()), message })
+        };
+
+        let mut warnings = Vec::new();
+        if authority.cert_path().exists() {
+            warnings.extend(check(None, authority.cert_path()));
+        }
+        for site in self.sites.iter().filter(|site| site.https) {
+            warnings.extend(check(Some(site), authority.site_files(&site.id()).cert));
+        }
+        warnings
+    }
+
+    /// Rewrites server blocks that no longer match their site, e.g. after
+    /// the default PHP version changed. Returns how many were rewritten.
+    pub fn sync(&self) -> Result<usize, String> {
+        let mut rewritten = 0;
+        for site in &self.sites {
+            let conf = self.render(site)?;
+            let path = self.sites_dir().join(site.conf_name());
+            if std::fs::read_to_string(&path).ok().as_ref() != Some(&conf) {
+                self.write_site(site)?;
+                rewritten += 1;
+            }
+        }
+        Ok(rewritten)
+    }
+
+    fn render(&self, site: &Site) -> Result<String, String> {
+        let fastcgi = match site.php {
+            true => self.php.fastcgi(site.php_version.as_deref()).map_err(|e| format!("{}: {e}", site.name))?,
+            false => String::new(),
+        };
+        let cert = self.authority().site_files(&site.id());
+        Ok(site.render(&fastcgi, Some(&cert)))
+    }
+
+    fn write_site(&self, site: &Site) -> Result<(), String> {
+        let dir = self.sites_dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        let path = dir.join(site.conf_name());
+        std::fs::write(&path, self.render(site)?).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+
+    fn include_sites(&self) -> Result<(), String> {
+        let path = self.conf_dir().join("nginx.conf");
+        let main = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        if let Some(updated) = with_include(&main)? {
+            std::fs::write(&path, updated).map_err(|e| format!("Failed to update {}: {e}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let file = SitesFile { sites: self.sites.clone() };
+        let contents = toml::to_string(&file).map_err(|e| format!("Failed to save sites: {e}"))?;
+        let header = "# Sites managed from the Sites tab of Xerve.\n\n";
+        if let Some(parent) = self.file.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+        }
+        std::fs::write(&self.file, format!("{header}{contents}"))
+            .map_err(|e| format!("Failed to write {}: {e}", self.file.display()))
+    }
+}
+
+/// `main` with the sites included at the end of its `http` block, or `None`
+/// when they already are.
+fn with_include(main: &str) -> Result<Option<String>, String> {
+    if main.lines().any(|line| line.trim_start().starts_with("include") && line.contains("sites/")) {
+        return Ok(None);
+    }
+
+    let start = find_block(main, "http").ok_or("nginx.conf has no http block")?;
+    let mut depth = 0;
+    for (offset, c) in main[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let end = start + offset;
+                    let line_start = main[..end].rfind('\n').map(|i| i + 1).unwrap_or(end);
+                    let indent = &main[line_start..end];
+                    let indent = if indent.trim().is_empty() { indent } else { "" };
+                    return Ok(Some(format!("{}{indent}    {INCLUDE}\n{}", &main[..line_start], &main[line_start..])));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("The http block of nginx.conf is not closed".to_string())
+}
+
+/// Offset of the `{` that opens the first `name` block outside comments.
+fn find_block(conf: &str, name: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in conf.split_inclusive('\n') {
+        let code = line.split('#').next().unwrap_or_default();
+        let trimmed = code.trim_start();
+        if let Some(rest) = trimmed.strip_prefix(name) {
+            if rest.trim_start().starts_with('{') {
+                return Some(offset + code.len() - rest.trim_start().len());
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PhpConfig;
+    use std::path::Path;
+
+    fn config(dir: &Path) -> SitesConfig {
+        SitesConfig {
+            conf_dir: dir.join("conf").to_string_lossy().into_owned(),
+            htdocs: dir.join("htdocs").to_string_lossy().into_owned(),
+            certs_dir: dir.join("certs").to_string_lossy().into_owned(),
+            ..SitesConfig::default()
+        }
+    }
+
+    /// PHP 8.2 and 8.4 installed under `dir/resource`.
+    fn php(dir: &Path) -> PhpVersions {
+        for version in ["php-8.2.29", "php-8.4.11"] {
+            std::fs::create_dir_all(dir.join("resource").join(version)).unwrap();
+        }
+        PhpVersions::discover(&PhpConfig {
+            dir: dir.join("resource").to_string_lossy().into_owned(),
+            ..PhpConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_site_names_and_urls() {
+        let site = Site { name: "My Blog!".to_string(), root: "/srv/blog".to_string(), port: 80, ..Site::default() };
+        assert_eq!(site.id(), "my-blog");
+        assert_eq!(site.url(), "http://my-blog.localhost/");
+        assert_eq!(Site { port: 8080, ..site.clone() }.url(), "http://my-blog.localhost:8080/");
+        assert_eq!(Site { port: 8080, https: true, ..site }.url(), "https://my-blog.localhost/");
+    }
+
+    #[test]
+    fn test_render_php_site() {
+        let site = Site {
+            name: "Shop".to_string(),
+            root: "/srv/shop/public".to_string(),
+            php: true,
+            port: 8080,
+            aliases: vec!["*.shop.test".to_string()],
+            ..Site::default()
+        };
+        let cert = CertFiles { cert: PathBuf::from("/certs/shop.pem"), key: PathBuf::from("/certs/shop.key") };
+        let conf = site.render("127.0.0.1:9000", Some(&cert));
+        assert!(conf.contains("listen 8080;\n    server_name"));
+        assert!(conf.contains("server_name shop.localhost *.shop.test;"));
+        assert!(conf.contains("root \"/srv/shop/public\";"));
+        assert!(conf.contains("fastcgi_pass 127.0.0.1:9000;"));
+        assert!(!conf.contains("{{"));
+        assert!(!Site { php: false, ..site.clone() }.render("127.0.0.1:9000", None).contains("fastcgi"));
+
+        let conf = Site { https: true, ..site.clone() }.render("127.0.0.1:9000", Some(&cert));
+        assert!(conf.contains("listen 8080;\n    listen 443 ssl;\n    ssl_certificate \"/certs/shop.pem\";\n    ssl_certificate_key \"/certs/shop.key\";\n"));
+
+        let conf = Site { https: true, port: HTTPS_PORT, ..site }.render("127.0.0.1:9000", Some(&cert));
+        assert_eq!(conf.matches("listen").count(), 1);
+        assert!(conf.contains("    listen 443 ssl;\n    ssl_certificate \"/certs/shop.pem\";"));
+    }
+
+    #[test]
+    fn test_include_is_added_once() {
+        let main = "worker_processes 1;\n# http { in a comment\nhttp {\n    include mime.types;\n    server {\n        listen 80;\n    }\n}\n";
+        let updated = with_include(main).unwrap().unwrap();
+        assert_eq!(
+            updated,
+            "worker_processes 1;\n# http { in a comment\nhttp {\n    include mime.types;\n    server {\n        listen 80;\n    }\n    include sites/*.conf;\n}\n"
+        );
+        assert_eq!(with_include(&updated).unwrap(), None);
+        assert!(with_include("events {}\n").is_err());
+    }
+
+    #[test]
+    fn test_add_and_remove_sites() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("conf")).unwrap();
+        std::fs::write(dir.path().join("conf").join("nginx.conf"), "http {\n}\n").unwrap();
+        let file = dir.path().join("sites.toml");
+
+        let mut sites = Sites::load_from(file.clone(), &config(dir.path()), php(dir.path())).unwrap();
+        sites.add(Site { name: "Blog".to_string(), php: true, port: 8081, ..Site::default() }).unwrap();
+        assert!(sites.add(Site { name: "blog".to_string(), port: 80, ..Site::default() }).is_err());
+
+        let conf = dir.path().join("conf").join("sites").join("blog.conf");
+        assert!(std::fs::read_to_string(&conf).unwrap().contains("listen 8081;"));
+        assert!(dir.path().join("htdocs").join("blog").is_dir());
+        assert!(std::fs::read_to_string(dir.path().join("conf").join("nginx.conf")).unwrap().contains(INCLUDE));
+
+        let mut reloaded = Sites::load_from(file.clone(), &config(dir.path()), php(dir.path())).unwrap();
+        assert_eq!(reloaded.sites()[0].root, sites.default_root("Blog"));
+        reloaded.remove("Blog").unwrap();
+        assert!(!conf.exists());
+        assert!(Sites::load_from(file, &config(dir.path()), php(dir.path())).unwrap().sites().is_empty());
+    }
+
+    #[test]
+    fn test_php_version_per_site() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("conf")).unwrap();
+        std::fs::write(dir.path().join("conf").join("nginx.conf"), "http {\n}\n").unwrap();
+        let file = dir.path().join("sites.toml");
+        let conf = |id: &str| std::fs::read_to_string(dir.path().join("conf").join("sites").join(format!("{id}.conf"))).unwrap();
+
+        let mut sites = Sites::load_from(file.clone(), &config(dir.path()), php(dir.path())).unwrap();
+        let legacy = Site { name: "Legacy".to_string(), php: true, php_version: Some("8.2".to_string()), port: 80, ..Site::default() };
+        sites.add(legacy.clone()).unwrap();
+        sites.add(Site { name: "App".to_string(), php: true, port: 80, ..Site::default() }).unwrap();
+        let missing = Site { name: "Old".to_string(), php_version: Some("7.4".to_string()), ..legacy };
+        assert_eq!(sites.add(missing).unwrap_err(), "PHP 7.4 is not installed");
+        assert!(conf("legacy").contains("fastcgi_pass 127.0.0.1:9082;"));
+        assert!(conf("app").contains("fastcgi_pass 127.0.0.1:9000;"));
+        assert_eq!(sites.sync().unwrap(), 0);
+
+        let php = PhpVersions::discover(&PhpConfig { default_version: Some("8.2".to_string()), ..php(dir.path()).config().clone() });
+        let sites = Sites::load_from(file, &config(dir.path()), php).unwrap();
+        // 8.2 moves to the default port; App follows the default there.
+        assert_eq!(sites.sync().unwrap(), 1);
+        assert!(conf("legacy").contains("fastcgi_pass 127.0.0.1:9000;"));
+        assert!(conf("app").contains("fastcgi_pass 127.0.0.1:9000;"));
+    }
+
+    #[test]
+    fn test_https_site_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("conf")).unwrap();
+        std::fs::write(dir.path().join("conf").join("nginx.conf"), "http {\n}\n").unwrap();
+        let file = dir.path().join("sites.toml");
+
+        let mut sites = Sites::load_from(file.clone(), &config(dir.path()), php(dir.path())).unwrap();
+        assert_eq!(sites.ca_path(), None);
+        let site = Site {
+            name: "Shop".to_string(),
+            port: 80,
+            https: true,
+            aliases: vec![" *.Shop.test ".to_string(), String::new()],
+            ..Site::default()
+        };
+        assert!(sites.add(Site { aliases: vec!["shop test".to_string()], ..site.clone() }).is_err());
+        sites.add(site).unwrap();
+
+        let cert = dir.path().join("certs").join("shop.pem");
+        let conf = std::fs::read_to_string(dir.path().join("conf").join("sites").join("shop.conf")).unwrap();
+        assert!(conf.contains("server_name shop.localhost *.shop.test;"));
+        assert!(conf.contains(&nginx_path(&cert)));
+        assert!(sites.ca_path().is_some());
+        assert!(sites.certificate_warnings().is_empty());
+
+        let strict = SitesConfig { expiry_warning_days: 400, ..config(dir.path()) };
+        let warnings = Sites::load_from(file.clone(), &strict, php(dir.path())).unwrap().certificate_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].site.as_deref(), Some("Shop"));
+        assert!(warnings[0].message.starts_with("certificate expires in"));
+
+        std::fs::remove_file(&cert).unwrap();
+        assert_eq!(sites.certificate_warnings()[0].message, "certificate is missing");
+        sites.renew("Shop").unwrap();
+        assert!(cert.exists());
+
+        sites.remove("Shop").unwrap();
+        assert!(!cert.exists());
+    }
+}
@@ -0,0 +1,108 @@
+use eframe::egui;
+use std::path::Path;
+use crate::logs::LogEntry;
+use crate::php::PhpVersions;
+use crate::services::{self, ServiceRegistry};
+use crate::shell::{self, Shell};
+use crate::ui::theme;
+
+/// The input line under the terminal output.
+pub struct CommandPrompt {
+    input: String,
+    shell: Shell,
+}
+
+impl CommandPrompt {
+    pub fn new(registry: &ServiceRegistry, php: &PhpVersions) -> Self {
+        CommandPrompt {
+            input: String::new(),
+            shell: Shell::new(registry, php),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.shell.dir()
+    }
+
+    fn report(result: Result<(), String>) {
+        if let Err(e) = result {
+            services::log_entry(LogEntry::service(shell::SOURCE, e));
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui) {
+        let id = egui::Id::new("terminal_prompt");
+        let running = self.shell.is_running();
+
+        // Handle keys before the text field sees them, so up and down do not
+        // move the cursor and Ctrl+C (which egui reports as Copy) cancels.
+        if ui.memory(|memory| memory.has_focus(id)) {
+            let mut recalled = None;
+            if ui.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp)) {
+                recalled = self.shell.history.previous(&self.input);
+            } else if ui.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown)) {
+                recalled = self.shell.history.next();
+            }
+            if let Some(line) = recalled {
+                self.input = line;
+                if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), id) {
+                    let end = egui::text::CCursor::new(self.input.chars().count());
+                    state.cursor.set_char_range(Some(egui::text::CCursorRange::one(end)));
+                    state.store(ui.ctx(), id);
+                }
+            }
+
+            let interrupt = ui.input(|input| {
+                input.events.iter().any(|event| matches!(event, egui::Event::Copy))
+                    || (input.modifiers.command && input.key_pressed(egui::Key::C))
+            });
+            if running && interrupt {
+                Self::report(self.shell.cancel());
+            }
+        }
+
+        let mut submitted = false;
+        ui.horizontal(|ui| {
+            let dir = self.shell.dir();
+            let name = dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| dir.display().to_string());
+            ui.label(
+                egui::RichText::new(format!("{name} $"))
+                    .size(12.0)
+                    .monospace()
+                    .color(theme::ACCENT),
+            )
+            .on_hover_text(dir.display().to_string());
+
+            let button_width = 64.0;
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.input)
+                    .id(id)
+                    .font(egui::TextStyle::Monospace)
+                    .hint_text(if running {
+                        "Running... Ctrl+C to cancel"
+                    } else {
+                        "Run a command, e.g. php artisan migrate, or cd <dir>"
+                    })
+                    .desired_width(ui.available_width() - button_width - 8.0),
+            );
+            submitted = response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+            if running {
+                if ui.add_sized([button_width, 20.0], egui::Button::new("Cancel")).clicked() {
+                    Self::report(self.shell.cancel());
+                }
+            } else if ui.add_sized([button_width, 20.0], egui::Button::new("Run")).clicked() {
+                submitted = true;
+            }
+
+            if submitted {
+                response.request_focus();
+            }
+        });
+
+        if submitted {
+            let line = std::mem::take(&mut self.input);
+            Self::report(self.shell.run(&line));
+        }
+    }
+}
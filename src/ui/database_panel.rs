@@ -0,0 +1,604 @@
+use eframe::egui;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+use crate::config::BackupConfig;
+use crate::database::admin::{self, Account, Action, Overview, Privileges};
+use crate::database::backup::{BackupFile, Backups};
+use crate::services::ServiceInfo;
+use crate::ui::{theme, DatabaseTarget, ServerConfigEditor};
+
+/// How often the backup list is re-read while the tab is open.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Form for a new database.
+struct NewDatabase {
+    name: String,
+    charset: String,
+    collation: String,
+}
+
+/// Form for a new user.
+struct NewUser {
+    user: String,
+    host: String,
+    password: String,
+    privileges: Privileges,
+    database: Option<String>,
+}
+
+/// Something that needs confirming before it runs.
+enum Request {
+    Admin(Action),
+    Restore(BackupFile),
+}
+
+/// A request waiting for the user to confirm it.
+struct Confirm {
+    request: Request,
+    /// What was typed into the confirmation field, for dropping a database.
+    typed: String,
+}
+
+/// Schemas and accounts of a database service, with forms to change them.
+pub struct DatabasePanel {
+    target: DatabaseTarget,
+    overview: Option<Overview>,
+    loading: Option<Receiver<Result<Overview, String>>>,
+    stale: bool,
+    applying: Option<(Action, Receiver<Result<(), String>>)>,
+    /// Outcome of the last action or refresh.
+    status: Option<Result<String, String>>,
+    new_database: NewDatabase,
+    new_user: NewUser,
+    root_password: String,
+    root_password_again: String,
+    /// Service a root password reset runs on, and the new password once it
+    /// has been set, for Xerve to sign in with.
+    root_reset_on: Option<String>,
+    new_root_password: Option<(String, String)>,
+    confirm: Option<Confirm>,
+    backup_config: BackupConfig,
+    backups: Option<Backups>,
+    backup_files: Vec<BackupFile>,
+    scanned: Option<Instant>,
+    /// A running dump or restore, reporting what it did.
+    backup_job: Option<Receiver<Result<String, String>>>,
+    server_config: ServerConfigEditor,
+}
+
+impl DatabasePanel {
+    pub fn new(backup_config: BackupConfig) -> Self {
+        DatabasePanel {
+            target: DatabaseTarget::default(),
+            overview: None,
+            loading: None,
+            stale: true,
+            applying: None,
+            status: None,
+            new_database: NewDatabase {
+                name: String::new(),
+                charset: "utf8mb4".to_string(),
+                collation: String::new(),
+            },
+            new_user: NewUser {
+                user: String::new(),
+                host: "localhost".to_string(),
+                password: String::new(),
+                privileges: Privileges::All,
+                database: None,
+            },
+            root_password: String::new(),
+            root_password_again: String::new(),
+            root_reset_on: None,
+            new_root_password: None,
+            confirm: None,
+            backup_config,
+            backups: None,
+            backup_files: Vec::new(),
+            scanned: None,
+            backup_job: None,
+            server_config: ServerConfigEditor::default(),
+        }
+    }
+
+    fn busy(&self) -> bool {
+        self.loading.is_some() || self.applying.is_some()
+    }
+
+    /// Runs `action`, asking first when it is destructive.
+    fn request(&mut self, action: Action) {
+        if action.is_destructive() {
+            self.confirm = Some(Confirm { request: Request::Admin(action), typed: String::new() });
+        } else {
+            self.apply(action);
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        let Some(session) = self.target.session() else {
+            return;
+        };
+        if matches!(action, Action::ResetRootPassword(_)) {
+            self.root_reset_on = self.target.selected().map(str::to_string);
+        }
+        let work = action.clone();
+        self.applying = Some((action, session.call(move |conn| work.apply(conn))));
+    }
+
+    /// Runs a dump or restore on a background thread.
+    fn start_backup_job(&mut self, job: impl FnOnce(&Backups) -> Result<String, String> + Send + 'static) {
+        let Some(backups) = self.backups.clone() else {
+            return;
+        };
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(job(&backups));
+        });
+        self.backup_job = Some(receiver);
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(job) = &self.backup_job {
+            match job.try_recv() {
+                Ok(result) => {
+                    self.status = Some(result);
+                    self.backup_job = None;
+                    self.scanned = None;
+                    self.stale = true;
+                }
+                Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(250)),
+                Err(TryRecvError::Disconnected) => self.backup_job = None,
+            }
+        }
+        if self.scanned.is_none_or(|scanned| scanned.elapsed() >= RESCAN_INTERVAL) {
+            self.backup_files = self.backups.as_ref().map(Backups::files).unwrap_or_default();
+            self.scanned = Some(Instant::now());
+        }
+
+        if self.stale && !self.busy() {
+            if let Some(session) = self.target.session() {
+                self.loading = Some(session.call(admin::overview));
+            }
+            self.stale = false;
+        }
+
+        if let Some(loading) = &self.loading {
+            match loading.try_recv() {
+                Ok(Ok(overview)) => {
+                    self.overview = Some(overview);
+                    self.loading = None;
+                }
+                Ok(Err(e)) => {
+                    self.status = Some(Err(e));
+                    self.loading = None;
+                }
+                Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+                Err(TryRecvError::Disconnected) => self.loading = None,
+            }
+        }
+
+        if let Some((action, applying)) = &self.applying {
+            match applying.try_recv() {
+                Ok(result) => {
+                    if let (Ok(()), Action::ResetRootPassword(password)) = (&result, action) {
+                        self.new_root_password = self.root_reset_on.take().map(|service| (service, password.clone()));
+                    }
+                    self.status = Some(result.map(|()| action.done()));
+                    self.applying = None;
+                    self.stale = true;
+                    ctx.request_repaint();
+                }
+                Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+                Err(TryRecvError::Disconnected) => self.applying = None,
+            }
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, services: &[ServiceInfo]) {
+        let databases = self.target.update(services);
+        let backups = self.target.service(&databases).and_then(|service| Backups::for_service(service, &self.backup_config));
+        if backups.as_ref().map(Backups::dir) != self.backups.as_ref().map(Backups::dir) {
+            self.scanned = None;
+        }
+        self.backups = backups;
+        self.poll(ui.ctx());
+
+        if let Some((name, password)) = self.new_root_password.take() {
+            let service = databases.iter().find(|service| service.name == name && service.signs_in_as("root"));
+            if let Some(Err(e)) = service.map(|service| service.remember_root_password(&password)) {
+                self.status = Some(Err(format!("Changed the root password, but Xerve could not save it and may be locked out: {e}")));
+            }
+        }
+
+        theme::card_frame(ui.style()).show(ui, |ui| {
+            ui.set_min_width(420.0);
+            ui.add_space(6.0);
+
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Databases").size(22.0).strong());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if let Some(session) = self.target.session() {
+                        theme::subtle_label(ui, session.address(), 12.0);
+                    }
+                });
+            });
+
+            ui.add_space(10.0);
+
+            if databases.is_empty() {
+                DatabaseTarget::empty_hint(ui);
+                return;
+            }
+
+            ui.horizontal(|ui| {
+                if self.target.combo(ui, "database_panel_service", &databases) {
+                    self.overview = None;
+                    self.status = None;
+                    self.stale = true;
+                }
+                if ui.add_enabled(!self.busy(), egui::Button::new("Refresh")).clicked() {
+                    self.stale = true;
+                }
+                let backing_up = self.backup_job.is_some();
+                if ui
+                    .add_enabled(!backing_up, egui::Button::new("Backup all"))
+                    .on_hover_text("Dump every database to a .sql.gz file")
+                    .clicked()
+                {
+                    self.start_backup_job(|backups| backups.create(None).map(|path| format!("Saved {}", path.display())));
+                }
+                if self.busy() || backing_up {
+                    ui.spinner();
+                }
+            });
+
+            match &self.status {
+                Some(Ok(message)) => theme::subtle_label(ui, message, 12.0),
+                Some(Err(e)) => {
+                    ui.label(egui::RichText::new(e).size(12.0).color(theme::RED));
+                }
+                None => {}
+            }
+
+            if self.overview.is_some() {
+                ui.add_space(8.0);
+                self.render_schemas(ui);
+            }
+        });
+
+        if self.overview.is_some() {
+            let uses_root = self.target.service(&databases).is_some_and(|service| service.signs_in_as("root"));
+            ui.add_space(16.0);
+            theme::card_frame(ui.style()).show(ui, |ui| {
+                ui.set_min_width(420.0);
+                self.render_accounts(ui, uses_root);
+            });
+        }
+
+        if self.backups.is_some() {
+            ui.add_space(16.0);
+            theme::card_frame(ui.style()).show(ui, |ui| {
+                ui.set_min_width(420.0);
+                self.render_backups(ui);
+            });
+        }
+
+        if let Some(service) = self.target.service(&databases).filter(|service| ServerConfigEditor::applies_to(service)) {
+            ui.add_space(16.0);
+            theme::card_frame(ui.style()).show(ui, |ui| {
+                ui.set_min_width(420.0);
+                self.server_config.render(ui, service, self.target.session());
+            });
+        }
+
+        self.render_confirm(ui.ctx());
+    }
+
+    fn render_schemas(&mut self, ui: &mut egui::Ui) {
+        let Some(overview) = &self.overview else {
+            return;
+        };
+
+        let mut requested = None;
+        let mut backup_requested = None;
+        egui::ScrollArea::vertical()
+            .id_salt("database_panel_schemas")
+            .max_height(220.0)
+            .show(ui, |ui| {
+                egui::Grid::new("database_panel_schema_grid")
+                    .striped(true)
+                    .num_columns(5)
+                    .spacing(egui::vec2(24.0, 6.0))
+                    .show(ui, |ui| {
+                        for schema in &overview.schemas {
+                            ui.label(egui::RichText::new(&schema.name).size(14.0).strong());
+                            theme::subtle_label(ui, theme::format_size(schema.size), 12.0);
+                            theme::subtle_label(ui, format!("{} tables", schema.tables), 12.0);
+                            let drop = ui
+                                .add_enabled(!schema.is_system(), egui::Button::new("Drop"))
+                                .on_disabled_hover_text("System databases can't be dropped");
+                            if drop.clicked() {
+                                requested = Some(Action::DropDatabase(schema.name.clone()));
+                            }
+                            let backup = ui
+                                .add_enabled(!schema.is_system() && self.backup_job.is_none(), egui::Button::new("Backup"))
+                                .on_hover_text(format!("Dump {} to a .sql.gz file", schema.name));
+                            if backup.clicked() {
+                                backup_requested = Some(schema.name.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("New database").size(16.0).strong());
+        let form = &mut self.new_database;
+        if form.collation.is_empty() || overview.collations_of(&form.charset).all(|collation| collation.name != form.collation) {
+            form.collation = overview.default_collation(&form.charset).unwrap_or_default().to_string();
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut form.name).hint_text("Name").desired_width(160.0));
+            egui::ComboBox::from_id_salt("database_panel_charset")
+                .selected_text(&form.charset)
+                .show_ui(ui, |ui| {
+                    for charset in overview.charsets() {
+                        ui.selectable_value(&mut form.charset, charset.to_string(), charset);
+                    }
+                });
+            egui::ComboBox::from_id_salt("database_panel_collation")
+                .selected_text(&form.collation)
+                .width(200.0)
+                .show_ui(ui, |ui| {
+                    for collation in overview.collations_of(&form.charset) {
+                        ui.selectable_value(&mut form.collation, collation.name.clone(), &collation.name);
+                    }
+                });
+            if ui.add_enabled(!form.name.trim().is_empty(), egui::Button::new("Create")).clicked() {
+                requested = Some(Action::CreateDatabase {
+                    name: form.name.trim().to_string(),
+                    charset: form.charset.clone(),
+                    collation: form.collation.clone(),
+                });
+                form.name.clear();
+            }
+        });
+
+        if let Some(action) = requested {
+            self.request(action);
+        }
+        if let Some(schema) = backup_requested {
+            self.start_backup_job(move |backups| {
+                backups.create(Some(&schema)).map(|path| format!("Saved {}", path.display()))
+            });
+        }
+    }
+
+    /// `uses_root` is set when Xerve itself signs in as root, so a new root
+    /// password is kept for its own connections.
+    fn render_accounts(&mut self, ui: &mut egui::Ui, uses_root: bool) {
+        let Some(overview) = &self.overview else {
+            return;
+        };
+
+        ui.label(egui::RichText::new("Users").size(22.0).strong());
+        ui.add_space(8.0);
+
+        let mut requested = None;
+        egui::ScrollArea::vertical()
+            .id_salt("database_panel_accounts")
+            .max_height(180.0)
+            .show(ui, |ui| {
+                egui::Grid::new("database_panel_account_grid")
+                    .striped(true)
+                    .num_columns(2)
+                    .spacing(egui::vec2(24.0, 6.0))
+                    .show(ui, |ui| {
+                        for account in &overview.accounts {
+                            ui.label(egui::RichText::new(format!("{}@{}", account.user, account.host)).size(14.0).monospace());
+                            if ui.button("Drop").clicked() {
+                                requested = Some(Action::DropUser(account.clone()));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("New user").size(16.0).strong());
+        let form = &mut self.new_user;
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut form.user).hint_text("User").desired_width(120.0));
+            ui.label("@");
+            ui.add(egui::TextEdit::singleline(&mut form.host).hint_text("Host").desired_width(100.0))
+                .on_hover_text("localhost, 127.0.0.1, % for any host, or a pattern such as 192.168.1.%");
+            ui.add(egui::TextEdit::singleline(&mut form.password).hint_text("Password").password(true).desired_width(120.0));
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("database_panel_privileges")
+                .selected_text(form.privileges.label())
+                .show_ui(ui, |ui| {
+                    for privileges in Privileges::ALL {
+                        ui.selectable_value(&mut form.privileges, privileges, privileges.label());
+                    }
+                });
+            ui.label("on");
+            egui::ComboBox::from_id_salt("database_panel_grant_database")
+                .selected_text(form.database.as_deref().unwrap_or("all databases"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut form.database, None, "all databases");
+                    for schema in overview.schemas.iter().filter(|schema| !schema.is_system()) {
+                        ui.selectable_value(&mut form.database, Some(schema.name.clone()), &schema.name);
+                    }
+                });
+            let ready = !form.user.trim().is_empty() && !form.host.trim().is_empty();
+            if ui.add_enabled(ready, egui::Button::new("Create")).clicked() {
+                requested = Some(Action::CreateUser {
+                    account: Account {
+                        user: form.user.trim().to_string(),
+                        host: form.host.trim().to_string(),
+                    },
+                    password: std::mem::take(&mut form.password),
+                    privileges: form.privileges,
+                    database: form.database.clone(),
+                });
+                form.user.clear();
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Root password").size(16.0).strong());
+        if uses_root {
+            theme::subtle_label(ui, "Xerve signs in as root and keeps using the new password.", 12.0);
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.root_password).hint_text("New password").password(true).desired_width(140.0));
+            ui.add(egui::TextEdit::singleline(&mut self.root_password_again).hint_text("Repeat").password(true).desired_width(140.0));
+            let matching = self.root_password == self.root_password_again;
+            if ui.add_enabled(matching, egui::Button::new("Reset")).clicked() {
+                requested = Some(Action::ResetRootPassword(self.root_password.clone()));
+            }
+            if !matching {
+                ui.label(egui::RichText::new("Passwords differ").size(11.0).color(theme::RED));
+            }
+        });
+
+        if let Some(action) = requested {
+            self.request(action);
+        }
+    }
+
+    fn render_backups(&mut self, ui: &mut egui::Ui) {
+        let Some(backups) = &self.backups else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new("Backups").size(22.0).strong());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Open folder").clicked() {
+                    let _ = std::fs::create_dir_all(backups.dir());
+                    if let Err(e) = open::that(backups.dir()) {
+                        self.status = Some(Err(format!("Failed to open {}: {e}", backups.dir().display())));
+                    }
+                }
+                let schedule = match self.backup_config.nightly_at() {
+                    Some(at) => format!("nightly at {}, newest {} kept", at.format("%H:%M"), self.backup_config.keep),
+                    None => format!("newest {} kept", self.backup_config.keep),
+                };
+                theme::subtle_label(ui, schedule, 12.0);
+            });
+        });
+        ui.add_space(8.0);
+
+        if self.backup_files.is_empty() {
+            ui.label(
+                egui::RichText::new(format!("No backups yet. Dumps are saved to {}.", backups.dir().display()))
+                    .size(12.0)
+                    .italics(),
+            );
+            return;
+        }
+
+        let mut restore = None;
+        egui::ScrollArea::vertical()
+            .id_salt("database_panel_backups")
+            .max_height(180.0)
+            .show(ui, |ui| {
+                for file in &self.backup_files {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(file.name()).size(13.0).monospace());
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.add_enabled(self.backup_job.is_none(), egui::Button::new("Restore")).clicked() {
+                                restore = Some(file.clone());
+                            }
+                            theme::subtle_label(ui, theme::format_size(file.size), 11.0);
+                            ui.add_space(8.0);
+                            theme::subtle_label(ui, file.modified.format("%Y-%m-%d %H:%M").to_string(), 11.0);
+                        });
+                    });
+                }
+            });
+
+        if let Some(file) = restore {
+            self.confirm = Some(Confirm { request: Request::Restore(file), typed: String::new() });
+        }
+    }
+
+    fn render_confirm(&mut self, ctx: &egui::Context) {
+        let Some(confirm) = &mut self.confirm else {
+            return;
+        };
+
+        let mut decision = None;
+        let modal = egui::Modal::new(egui::Id::new("database_panel_confirm")).show(ctx, |ui| {
+            ui.set_width(360.0);
+            let (title, message) = match &confirm.request {
+                Request::Admin(Action::DropDatabase(name)) => (
+                    "Drop database",
+                    format!("This deletes {name} and every table in it. It can't be undone. Type the name to confirm."),
+                ),
+                Request::Admin(Action::DropUser(account)) => (
+                    "Drop user",
+                    format!("{}@{} will no longer be able to log in.", account.user, account.host),
+                ),
+                Request::Admin(Action::ResetRootPassword(password)) if password.is_empty() => (
+                    "Reset root password",
+                    "This removes the password from every root account.".to_string(),
+                ),
+                Request::Admin(_) => (
+                    "Reset root password",
+                    "This changes the password of every root account.".to_string(),
+                ),
+                Request::Restore(file) => (
+                    "Restore",
+                    format!("Every database in {} is replaced by its state in the dump. Changes made since then are lost.", file.name()),
+                ),
+            };
+            ui.label(egui::RichText::new(title).size(18.0).strong());
+            ui.add_space(6.0);
+            ui.label(message);
+
+            let mut confirmed = true;
+            if let Request::Admin(Action::DropDatabase(name)) = &confirm.request {
+                ui.add_space(6.0);
+                ui.add(egui::TextEdit::singleline(&mut confirm.typed).hint_text(name.as_str()));
+                confirmed = &confirm.typed == name;
+            }
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                let button = egui::Button::new(egui::RichText::new(title).color(egui::Color32::WHITE)).fill(theme::RED);
+                if ui.add_enabled(confirmed, button).clicked() {
+                    decision = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    decision = Some(false);
+                }
+            });
+        });
+        if modal.should_close() {
+            decision.get_or_insert(false);
+        }
+
+        match decision {
+            Some(true) => match self.confirm.take().map(|confirm| confirm.request) {
+                Some(Request::Admin(action)) => {
+                    if matches!(action, Action::ResetRootPassword(_)) {
+                        self.root_password.clear();
+                        self.root_password_again.clear();
+                    }
+                    self.apply(action);
+                }
+                Some(Request::Restore(file)) => {
+                    self.start_backup_job(move |backups| {
+                        backups.restore(&file).map(|()| format!("Restored {}", file.name()))
+                    });
+                }
+                None => {}
+            },
+            Some(false) => self.confirm = None,
+            None => {}
+        }
+    }
+}
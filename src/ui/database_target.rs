@@ -0,0 +1,65 @@
+use eframe::egui;
+use crate::database::Session;
+use crate::services::ServiceInfo;
+
+/// The database service a tab works on, with a session that follows its
+/// configuration.
+#[derive(Default)]
+pub struct DatabaseTarget {
+    service: Option<String>,
+    session: Option<Session>,
+}
+
+impl DatabaseTarget {
+    /// Keeps the selection and session in line with `services` and returns
+    /// the services that have database settings.
+    pub fn update<'a>(&mut self, services: &'a [ServiceInfo]) -> Vec<&'a ServiceInfo> {
+        let databases: Vec<&ServiceInfo> = services.iter().filter(|service| service.database().is_some()).collect();
+        if self.service.as_ref().is_none_or(|name| !databases.iter().any(|service| &service.name == name)) {
+            self.service = databases.first().map(|service| service.name.clone());
+        }
+        let settings = databases
+            .iter()
+            .find(|service| Some(&service.name) == self.service.as_ref())
+            .and_then(|service| service.database());
+        if self.session.as_ref().map(Session::settings) != settings.as_ref() {
+            self.session = settings.map(Session::new);
+        }
+        databases
+    }
+
+    /// The selected service among `databases`.
+    pub fn service<'a>(&self, databases: &[&'a ServiceInfo]) -> Option<&'a ServiceInfo> {
+        databases.iter().copied().find(|service| Some(&service.name) == self.service.as_ref())
+    }
+
+    /// Name of the selected service.
+    pub fn selected(&self) -> Option<&str> {
+        self.service.as_deref()
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// Picker for the service; returns whether the selection changed.
+    pub fn combo(&mut self, ui: &mut egui::Ui, id: &str, databases: &[&ServiceInfo]) -> bool {
+        let previous = self.service.clone();
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(self.service.as_deref().unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for service in databases {
+                    ui.selectable_value(&mut self.service, Some(service.name.clone()), &service.name);
+                }
+            });
+        self.service != previous
+    }
+
+    pub fn empty_hint(ui: &mut egui::Ui) {
+        ui.label(
+            egui::RichText::new("No database service configured. Add a `database` table or a mysql readiness probe to a service in xerve.toml.")
+                .size(12.0)
+                .italics(),
+        );
+    }
+}
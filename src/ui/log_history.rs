@@ -0,0 +1,223 @@
+use eframe::egui;
+use std::time::{Duration, Instant};
+use crate::logs::store::{self, LogFile};
+use crate::logs::{self, LogEntry, LogQuery, LogStream};
+use crate::services::ServiceInfo;
+use crate::ui::theme;
+
+/// How often the file list is re-read while the tab is open.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Browser for the log files under `logs/<service>/`.
+#[derive(Default)]
+pub struct LogHistory {
+    service: Option<String>,
+    files: Vec<LogFile>,
+    scanned: Option<Instant>,
+    open: Option<LogFile>,
+    entries: Vec<LogEntry>,
+    error: Option<String>,
+    search: String,
+    from: String,
+    to: String,
+    /// Indices into `entries` that match `matched_query`.
+    matches: Vec<usize>,
+    matched_query: Option<LogQuery>,
+}
+
+impl LogHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn rescan(&mut self) {
+        self.files = self.service.as_deref().map(store::files).unwrap_or_default();
+        self.scanned = Some(Instant::now());
+    }
+
+    fn load(&mut self, file: LogFile) {
+        let service = self.service.clone().unwrap_or_default();
+        match store::read(&service, &file) {
+            Ok(entries) => {
+                self.entries = entries;
+                self.error = None;
+            }
+            Err(e) => {
+                self.entries.clear();
+                self.error = Some(e);
+            }
+        }
+        self.open = Some(file);
+        self.matched_query = None;
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, services: &[ServiceInfo]) {
+        if self.service.is_none() {
+            self.service = services.first().map(|service| service.name.clone());
+        }
+        if self.scanned.is_none_or(|scanned| scanned.elapsed() >= RESCAN_INTERVAL) {
+            self.rescan();
+        }
+
+        theme::card_frame(ui.style()).show(ui, |ui| {
+            ui.set_min_width(420.0);
+            ui.add_space(6.0);
+
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Log History").size(22.0).strong());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if let Some(service) = &self.service {
+                        theme::subtle_label(ui, store::service_dir(service).display().to_string(), 12.0);
+                    }
+                });
+            });
+
+            ui.add_space(10.0);
+
+            let previous = self.service.clone();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("log_history_service")
+                    .selected_text(self.service.as_deref().unwrap_or("No services"))
+                    .show_ui(ui, |ui| {
+                        for service in services {
+                            ui.selectable_value(&mut self.service, Some(service.name.clone()), &service.name);
+                        }
+                    });
+                if ui.button("Refresh").clicked() {
+                    self.rescan();
+                }
+            });
+            if self.service != previous {
+                self.open = None;
+                self.entries.clear();
+                self.rescan();
+            }
+
+            ui.add_space(8.0);
+            self.render_files(ui);
+        });
+
+        if self.open.is_some() {
+            ui.add_space(16.0);
+            theme::card_frame(ui.style()).show(ui, |ui| {
+                ui.set_min_width(420.0);
+                self.render_entries(ui);
+            });
+        }
+    }
+
+    fn render_files(&mut self, ui: &mut egui::Ui) {
+        if self.files.is_empty() {
+            ui.label(
+                egui::RichText::new("No log files yet. Output is saved once the service has been started.")
+                    .size(12.0)
+                    .italics(),
+            );
+            return;
+        }
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_salt("log_history_files")
+            .max_height(160.0)
+            .show(ui, |ui| {
+                for file in &self.files {
+                    let selected = self.open.as_ref().is_some_and(|open| open.path == file.path);
+                    ui.horizontal(|ui| {
+                        if ui.selectable_label(selected, file.name()).clicked() {
+                            clicked = Some(file.clone());
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            theme::subtle_label(ui, theme::format_size(file.size), 11.0);
+                            ui.add_space(8.0);
+                            theme::subtle_label(ui, file.modified.format("last written %Y-%m-%d %H:%M").to_string(), 11.0);
+                        });
+                    });
+                }
+            });
+
+        if let Some(file) = clicked {
+            self.load(file);
+        }
+    }
+
+    fn render_entries(&mut self, ui: &mut egui::Ui) {
+        let title = self.open.as_ref().map(LogFile::name).unwrap_or_default();
+        ui.label(egui::RichText::new(title).size(16.0).strong());
+        ui.add_space(6.0);
+
+        let mut valid_range = true;
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.search).hint_text("Search messages").desired_width(220.0));
+            ui.add_space(8.0);
+            ui.label("From");
+            ui.add(egui::TextEdit::singleline(&mut self.from).hint_text("HH:MM").desired_width(70.0));
+            ui.label("to");
+            ui.add(egui::TextEdit::singleline(&mut self.to).hint_text("HH:MM").desired_width(70.0));
+        });
+
+        let bound = |input: &str, valid: &mut bool| {
+            if input.trim().is_empty() {
+                return None;
+            }
+            let time = logs::parse_time_of_day(input);
+            *valid &= time.is_some();
+            time
+        };
+        let query = LogQuery {
+            text: self.search.trim().to_string(),
+            from: bound(&self.from, &mut valid_range),
+            to: bound(&self.to, &mut valid_range),
+        };
+        if self.matched_query.as_ref() != Some(&query) {
+            self.matches = (0..self.entries.len()).filter(|&i| query.matches(&self.entries[i])).collect();
+            self.matched_query = Some(query);
+        }
+
+        ui.horizontal(|ui| {
+            theme::subtle_label(ui, format!("{} of {} entries", self.matches.len(), self.entries.len()), 11.0);
+            if !valid_range {
+                ui.label(
+                    egui::RichText::new("Times must look like 03:15 or 03:15:30")
+                        .size(11.0)
+                        .color(theme::RED),
+                );
+            }
+        });
+        ui.add_space(4.0);
+
+        if let Some(error) = &self.error {
+            ui.label(egui::RichText::new(error).size(12.0).color(theme::RED));
+            return;
+        }
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + ui.spacing().item_spacing.y;
+        egui::ScrollArea::vertical()
+            .id_salt("log_history_entries")
+            .auto_shrink([false; 2])
+            .max_height(420.0)
+            .show_rows(ui, row_height, self.matches.len(), |ui, rows| {
+                for &index in &self.matches[rows] {
+                    let entry = &self.entries[index];
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new(entry.timestamp.format("%H:%M:%S").to_string())
+                                .size(11.0)
+                                .monospace()
+                                .color(theme::TEXT_MUTED),
+                        )
+                        .on_hover_text(entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+                        if entry.stream != LogStream::Stdout {
+                            ui.label(
+                                egui::RichText::new(entry.stream.label())
+                                    .size(11.0)
+                                    .strong()
+                                    .color(theme::ACCENT),
+                            );
+                        }
+                        ui.label(theme::log_text(entry, &[]));
+                    });
+                }
+            });
+    }
+}
@@ -1,8 +1,24 @@
-mod terminal;
+mod command_prompt;
+mod database_panel;
+mod database_target;
+mod log_history;
+mod php_panel;
+mod resource_monitoring;
+mod server_config;
 mod service_row;
+mod sites_panel;
+mod sql_console;
+mod terminal;
 pub mod theme;
-mod resource_monitoring;
 
+pub use command_prompt::CommandPrompt;
+pub use database_panel::DatabasePanel;
+pub use database_target::DatabaseTarget;
+pub use log_history::LogHistory;
+pub use php_panel::PhpPanel;
+pub use resource_monitoring::ResourceMonitoring;
+pub use server_config::ServerConfigEditor;
 pub use service_row::ServiceRow;
+pub use sites_panel::SitesPanel;
+pub use sql_console::SqlConsole;
 pub use terminal::Terminal;
-pub use resource_monitoring::ResourceMonitoring;
\ No newline at end of file
@@ -0,0 +1,247 @@
+use eframe::egui;
+use crate::logs::LogEntry;
+use crate::php::ini::{self, PhpIni};
+use crate::php::{PhpVersion, PhpVersions};
+use crate::services::{self, Service, ServiceRegistry};
+use crate::ui::theme;
+
+/// Size settings, checked with `ini::is_size`.
+const SIZE_SETTINGS: &[(&str, &str)] = &[
+    ("memory_limit", "Memory limit"),
+    ("upload_max_filesize", "Max upload size"),
+    ("post_max_size", "Max POST size"),
+];
+
+/// `php.ini` of one PHP install: common settings, extensions and Xdebug.
+pub struct PhpPanel {
+    php: PhpVersions,
+    /// Short version being edited.
+    version: Option<String>,
+    ini: Result<PhpIni, String>,
+    /// Contents as last loaded or saved, to tell whether there are changes.
+    saved: String,
+    sizes: Vec<String>,
+    max_execution_time: String,
+    display_errors: bool,
+    extensions: Vec<String>,
+    /// Outcome of the last save.
+    status: Option<Result<String, String>>,
+}
+
+impl PhpPanel {
+    pub fn new(php: PhpVersions) -> Self {
+        let version = php.default_version().map(PhpVersion::short);
+        let mut panel = PhpPanel {
+            php,
+            version,
+            ini: Err("No PHP install found".to_string()),
+            saved: String::new(),
+            sizes: Vec::new(),
+            max_execution_time: String::new(),
+            display_errors: false,
+            extensions: Vec::new(),
+            status: None,
+        };
+        panel.load();
+        panel
+    }
+
+    fn selected(&self) -> Option<&PhpVersion> {
+        self.php.find(self.version.as_deref()?)
+    }
+
+    /// Reads the selected version's `php.ini` into the form.
+    fn load(&mut self) {
+        let Some(php) = self.selected() else {
+            return;
+        };
+        let dir = php.dir.clone();
+        self.extensions = ini::available_extensions(&dir);
+        self.ini = PhpIni::load(&dir);
+        let Ok(ini) = &self.ini else {
+            return;
+        };
+        self.saved = ini.contents();
+        self.sizes = SIZE_SETTINGS
+            .iter()
+            .map(|(key, _)| ini.get(key).unwrap_or_default())
+            .collect();
+        self.max_execution_time = ini.get("max_execution_time").unwrap_or_default();
+        self.display_errors = ini.get("display_errors").is_some_and(|value| ini::is_on(&value));
+    }
+
+    /// Form values that PHP would reject.
+    fn errors(&self) -> Vec<String> {
+        let mut errors: Vec<String> = SIZE_SETTINGS
+            .iter()
+            .zip(&self.sizes)
+            .filter(|(_, value)| !value.is_empty() && !ini::is_size(value))
+            .map(|((key, _), value)| format!("{key}: '{value}' is not a size such as 128M or 2G"))
+            .collect();
+        let time = self.max_execution_time.trim();
+        if !time.is_empty() && time.parse::<u32>().is_err() {
+            errors.push(format!("max_execution_time: '{time}' is not a number of seconds"));
+        }
+        errors
+    }
+
+    /// Writes the form into `php.ini` and restarts the version's service
+    /// when it is running.
+    fn save(&mut self, registry: &ServiceRegistry) {
+        let Some(service) = self.selected().map(PhpVersion::service_name) else {
+            return;
+        };
+        let Ok(ini) = &mut self.ini else {
+            return;
+        };
+        // Only the settings that were edited, so the rest of the file stays
+        // as PHP's defaults or the user left it.
+        let fields = SIZE_SETTINGS.iter().map(|(key, _)| *key).zip(&self.sizes);
+        for (key, value) in fields.chain([("max_execution_time", &self.max_execution_time)]) {
+            let value = value.trim();
+            if !value.is_empty() && ini.get(key).as_deref() != Some(value) {
+                ini.set(key, value);
+            }
+        }
+        if ini.get("display_errors").is_some_and(|value| ini::is_on(&value)) != self.display_errors {
+            ini.set("display_errors", if self.display_errors { "On" } else { "Off" });
+        }
+
+        if ini.contents() == self.saved {
+            self.status = Some(Ok("No changes to save".to_string()));
+            return;
+        }
+        if let Err(e) = ini.save() {
+            self.status = Some(Err(e));
+            return;
+        }
+        self.saved = ini.contents();
+        let message = format!("Saved {}", ini.path().display());
+        services::log_entry(LogEntry::service("PHP", message.clone()));
+
+        let running = registry.get(&service).is_some_and(|service| service.state().is_active());
+        self.status = Some(Ok(if running {
+            let registry = registry.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = registry.restart(&service) {
+                    services::log_entry(LogEntry::service("PHP", e));
+                }
+            });
+            format!("{message}; restarting")
+        } else {
+            format!("{message}; applies when {service} starts")
+        }));
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, registry: &ServiceRegistry) {
+        theme::card_frame(ui.style()).show(ui, |ui| {
+            ui.set_min_width(420.0);
+            ui.add_space(6.0);
+
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("PHP").size(22.0).strong());
+                let previous = self.version.clone();
+                egui::ComboBox::from_id_salt("php_ini_version")
+                    .selected_text(self.version.clone().unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for version in self.php.all() {
+                            ui.selectable_value(&mut self.version, Some(version.short()), version.short())
+                                .on_hover_text(version.dir.display().to_string());
+                        }
+                    });
+                if self.version != previous {
+                    self.status = None;
+                    self.load();
+                }
+                if let Ok(ini) = &self.ini {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        theme::subtle_label(ui, ini.path().display().to_string(), 12.0);
+                    });
+                }
+            });
+
+            ui.add_space(10.0);
+
+            let ini = match &mut self.ini {
+                Ok(ini) => ini,
+                Err(e) => {
+                    ui.label(egui::RichText::new(e.as_str()).size(12.0).color(theme::RED));
+                    return;
+                }
+            };
+
+            egui::Grid::new("php_ini_settings").num_columns(2).spacing([12.0, 6.0]).show(ui, |ui| {
+                for ((key, label), value) in SIZE_SETTINGS.iter().zip(&mut self.sizes) {
+                    ui.label(*label).on_hover_text(*key);
+                    ui.add(egui::TextEdit::singleline(value).hint_text("128M").desired_width(100.0));
+                    ui.end_row();
+                }
+                ui.label("Max execution time").on_hover_text("max_execution_time, in seconds");
+                ui.add(egui::TextEdit::singleline(&mut self.max_execution_time).hint_text("30").desired_width(100.0));
+                ui.end_row();
+                ui.label("Display errors").on_hover_text("display_errors");
+                ui.checkbox(&mut self.display_errors, "");
+                ui.end_row();
+            });
+
+            ui.add_space(10.0);
+            let xdebug = self.extensions.iter().find(|name| name.starts_with("xdebug")).cloned();
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Xdebug").size(16.0).strong());
+                match &xdebug {
+                    Some(name) => {
+                        let mut enabled = ini.extension_enabled(name);
+                        let label = if enabled { "On" } else { "Off" };
+                        if ui.checkbox(&mut enabled, label).changed() {
+                            ini.set_extension(name, enabled);
+                            if enabled && ini.get("xdebug.mode").is_none() {
+                                ini.set("xdebug.mode", "debug,develop");
+                            }
+                        }
+                    }
+                    None => theme::subtle_label(ui, "Not installed; put the Xdebug library in ext/", 12.0),
+                }
+            });
+
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("Extensions").size(16.0).strong());
+            if self.extensions.is_empty() {
+                theme::subtle_label(ui, "No extensions found in ext/", 12.0);
+            }
+            ui.horizontal_wrapped(|ui| {
+                for name in self.extensions.iter().filter(|name| Some(*name) != xdebug.as_ref()) {
+                    let mut enabled = ini.extension_enabled(name);
+                    if ui.checkbox(&mut enabled, name).changed() {
+                        ini.set_extension(name, enabled);
+                    }
+                }
+            });
+
+            ui.add_space(10.0);
+            let errors = self.errors();
+            for error in &errors {
+                ui.label(egui::RichText::new(error).size(12.0).color(theme::RED));
+            }
+            ui.horizontal(|ui| {
+                if ui.add_enabled(errors.is_empty(), egui::Button::new("Save")).clicked() {
+                    self.save(registry);
+                }
+                if ui.button("Revert").on_hover_text("Reload php.ini from disk").clicked() {
+                    self.status = None;
+                    self.load();
+                }
+                if matches!(&self.ini, Ok(ini) if ini.contents() != self.saved) {
+                    theme::subtle_label(ui, "Unsaved extension changes", 12.0);
+                }
+            });
+
+            match &self.status {
+                Some(Ok(message)) => theme::subtle_label(ui, message, 12.0),
+                Some(Err(e)) => {
+                    ui.label(egui::RichText::new(e).size(12.0).color(theme::RED));
+                }
+                None => {}
+            }
+        });
+    }
+}
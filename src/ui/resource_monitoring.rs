@@ -1,5 +1,5 @@
 use eframe::egui;
-use crate::services::{Service, ServiceInfo};
+use crate::services::{platform, Service, ServiceInfo};
 use crate::ui::theme;
 use sysinfo::{System, ProcessRefreshKind, RefreshKind, MemoryRefreshKind, CpuRefreshKind, Pid};
 use std::time::Instant;
@@ -45,7 +45,7 @@ impl ResourceMonitoring {
         }
     }
 
-    pub fn render(&mut self, ui: &mut egui::Ui, services: &Vec<ServiceInfo>) {
+    pub fn render(&mut self, ui: &mut egui::Ui, services: &[ServiceInfo]) {
         if self.last_update.elapsed().as_millis() >= 1000 {
             self.update_data(services);
             self.last_update = Instant::now();
@@ -97,7 +97,7 @@ impl ResourceMonitoring {
         }
     }
 
-    fn update_data(&mut self, services: &Vec<ServiceInfo>) {
+    fn update_data(&mut self, services: &[ServiceInfo]) {
         self.sys.refresh_all();
         
         self.system_cpu = self.sys.global_cpu_info().cpu_usage();
@@ -107,8 +107,8 @@ impl ResourceMonitoring {
         for service in services {
             let service_name = &service.name;
             
-            let (cpu_usage, memory_usage) = if service.status() == "Running" {
-                self.get_service_usage(service_name)
+            let (cpu_usage, memory_usage) = if service.state().is_active() {
+                self.get_service_usage(service)
             } else {
                 self.service_pids.remove(service_name);
                 (0.0, 0)
@@ -119,7 +119,7 @@ impl ResourceMonitoring {
                 memory_usage,
             };
             
-            let data_vec = self.service_data.entry(service_name.clone()).or_insert_with(Vec::new);
+            let data_vec = self.service_data.entry(service_name.clone()).or_default();
             data_vec.push(data_point);
             
             if data_vec.len() > 60 {
@@ -128,50 +128,24 @@ impl ResourceMonitoring {
         }
     }
 
-    fn get_service_usage(&mut self, service_name: &str) -> (f32, u64) {
-        let process_names = self.get_process_names_for_service(service_name);
-        
-        let mut total_cpu = 0.0;
-        let mut total_memory = 0u64;
-        let mut found_pids = Vec::new();
-
-        for (pid, process) in self.sys.processes() {
-            let process_name = process.name();
-            
-            if !process_names.is_empty() && process_names.iter().any(|name| {
-                process_name.to_lowercase().contains(&name.to_lowercase()) ||
-                name.to_lowercase().contains(&process_name.to_lowercase())
-            }) {
-                total_cpu += process.cpu_usage();
-                total_memory += process.memory();
-                found_pids.push(*pid);
-            }
-        }
-
-        if !found_pids.is_empty() {
-            self.service_pids.insert(service_name.to_string(), found_pids);
-        }
-
-        if total_cpu == 0.0 && total_memory == 0 {
-            if let Some(pids) = self.service_pids.get(service_name) {
-                for pid in pids {
-                    if let Some(process) = self.sys.process(*pid) {
-                        total_cpu += process.cpu_usage();
-                        total_memory += process.memory();
-                    }
-                }
-            }
-        }
-
-        (total_cpu, total_memory)
-    }
-
-    fn get_process_names_for_service(&self, service_name: &str) -> Vec<&'static str> {
-        match service_name {
-            "Nginx" => vec!["nginx", "nginx.exe"],
-            "MariaDB" => vec!["mariadbd", "mariadbd.exe", "mysqld", "mysqld.exe"],
-            _ => vec![], // Only Nginx and MariaDB are supported
-        }
+    /// Usage of the service's process tree where its PID is known, as the
+    /// daemon's metrics do, so services sharing an executable, like several
+    /// PHP versions, are told apart. Only services that own their process
+    /// names fall back to matching by name.
+    fn get_service_usage(&mut self, service: &ServiceInfo) -> (f32, u64) {
+        let pids = service
+            .process_id()
+            .and_then(|pid| platform::process_tree(&self.sys, pid))
+            .or_else(|| {
+                service
+                    .owns_process_names()
+                    .then(|| platform::processes_named(&self.sys, &service.process_names()))
+            })
+            .unwrap_or_default();
+
+        let usage = platform::usage_of(&self.sys, &pids);
+        self.service_pids.insert(service.name.clone(), pids);
+        usage
     }
 
     fn render_system_overview(&self, ui: &mut egui::Ui) {
@@ -237,8 +211,8 @@ impl ResourceMonitoring {
     }
 
     fn render_service_card(&mut self, ui: &mut egui::Ui, service: &ServiceInfo) {
-        let status = service.status();
-        let is_running = status == "Running";
+        let state = service.state();
+        let is_running = state.is_active();
 
         theme::card_frame(ui.style()).show(ui, |ui| {
             ui.set_min_height(280.0);
@@ -249,15 +223,16 @@ impl ResourceMonitoring {
                         .size(20.0)
                         .strong(),
                 );
+                theme::health_badge(ui, &service.health());
                 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    let (fg, bg) = theme::status_colors(&status);
+                    let (fg, bg) = theme::status_colors(&state);
                     let status_response = ui.allocate_response(egui::vec2(80.0, 24.0), egui::Sense::hover());
                     ui.painter().rect_filled(status_response.rect, 12.0, bg);
                     ui.painter().text(
                         status_response.rect.center(),
                         egui::Align2::CENTER_CENTER,
-                        &status,
+                        state.label(),
                         egui::FontId::proportional(12.0),
                         fg,
                     );
@@ -349,7 +324,7 @@ impl ResourceMonitoring {
                 );
                 ui.add_space(4.0);
                 ui.label(
-                    egui::RichText::new("Monitoring Nginx & MariaDB processes")
+                    egui::RichText::new(format!("Monitoring {service_name} processes"))
                         .size(12.0)
                         .color(theme::TEXT_MUTED),
                 );
@@ -368,7 +343,7 @@ impl ResourceMonitoring {
             theme::BG_DARK,
         );
         
-        let percentage = (value / max).min(1.0).max(0.0);
+        let percentage = (value / max).clamp(0.0, 1.0);
         if percentage > 0.0 {
             let progress_width = percentage * (width - 4.0);
             let progress_rect = egui::Rect::from_min_size(
@@ -0,0 +1,268 @@
+use eframe::egui;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+use crate::database::server_config::{self, OptionFile, SETTINGS};
+use crate::database::Session;
+use crate::logs::LogEntry;
+use crate::services::{self, Service, ServiceInfo};
+use crate::ui::theme;
+
+/// Dynamic settings being applied to the running server.
+struct Applying {
+    keys: Vec<String>,
+    /// The settings the server refused.
+    refused: Receiver<Result<Vec<String>, String>>,
+}
+
+/// Editor for a database service's option file: a form for the common
+/// settings, or the whole file as text.
+#[derive(Default)]
+pub struct ServerConfigEditor {
+    path: Option<PathBuf>,
+    file: Option<Result<OptionFile, String>>,
+    /// Form values, in `SETTINGS` order.
+    values: Vec<String>,
+    raw: bool,
+    text: String,
+    applying: Option<Applying>,
+    status: Option<Result<String, String>>,
+}
+
+impl ServerConfigEditor {
+    /// Reads the option file of `service` when it differs from the one shown.
+    fn follow(&mut self, service: &ServiceInfo) {
+        let path = service.defaults_file();
+        if path != self.path {
+            self.path = path;
+            self.status = None;
+            self.load();
+        }
+    }
+
+    fn load(&mut self) {
+        self.file = self.path.as_deref().map(OptionFile::load);
+        if let Some(Ok(file)) = &self.file {
+            self.fill(&file.clone());
+        }
+    }
+
+    /// Puts `file` into the form and the raw text.
+    fn fill(&mut self, file: &OptionFile) {
+        self.values = SETTINGS
+            .iter()
+            .map(|setting| file.get(setting.key).unwrap_or_default())
+            .collect();
+        self.text = file.contents();
+    }
+
+    /// The file as edited: the raw text, plus the form values when the form
+    /// is showing.
+    fn edited(&self) -> Option<OptionFile> {
+        let Some(Ok(file)) = &self.file else {
+            return None;
+        };
+        let mut edited = OptionFile::parse(file.path().to_path_buf(), &self.text);
+        if self.raw {
+            return Some(edited);
+        }
+        for (setting, value) in SETTINGS.iter().zip(&self.values) {
+            let value = value.trim();
+            if value.is_empty() {
+                // An emptied field hands the setting back to the server's default.
+                if edited.get(setting.key).is_some() {
+                    edited.remove(setting.key);
+                }
+            } else if edited.get(setting.key).as_deref() != Some(value) {
+                edited.set(setting.key, value);
+            }
+        }
+        Some(edited)
+    }
+
+    /// Writes the edited file. Changes to a running server are applied with
+    /// `SET GLOBAL` where possible; the rest mark the service for a restart.
+    fn save(&mut self, service: &ServiceInfo, session: Option<&Session>) {
+        let (Some(Ok(before)), Some(after)) = (self.file.clone(), self.edited()) else {
+            return;
+        };
+        if let Err(e) = after.save() {
+            self.status = Some(Err(e));
+            return;
+        }
+        let changed = server_config::changed(&before, &after);
+        let mut message = format!("Saved {}", after.path().display());
+        services::log_entry(LogEntry::service(&service.name, message.clone()));
+        self.fill(&after);
+        self.file = Some(Ok(after.clone()));
+
+        if !service.state().is_active() || changed.is_empty() {
+            self.status = Some(Ok(message));
+            return;
+        }
+
+        // A removed setting only falls back to the default on a restart.
+        let (dynamic, restart): (Vec<String>, Vec<String>) = changed.into_iter().partition(|key| {
+            after.get(key).is_some() && server_config::setting(key).is_some_and(|setting| setting.dynamic)
+        });
+        if !restart.is_empty() {
+            message.push_str(&format!("; restart {} to apply {}", service.name, restart.join(", ")));
+            service.require_restart(restart);
+        }
+        if !dynamic.is_empty() {
+            match session {
+                Some(session) => {
+                    let values: Vec<(String, String)> = dynamic
+                        .iter()
+                        .map(|key| (key.clone(), after.get(key).unwrap_or_default()))
+                        .collect();
+                    let refused = session.call(move |conn| {
+                        let mut refused = Vec::new();
+                        for (key, value) in values {
+                            if let Err(e) = server_config::apply(conn, &key, &value) {
+                                services::log_entry(LogEntry::service("Databases", e));
+                                refused.push(key);
+                            }
+                        }
+                        Ok(refused)
+                    });
+                    message.push_str(&format!("; applying {}", dynamic.join(", ")));
+                    self.applying = Some(Applying { keys: dynamic, refused });
+                }
+                None => service.require_restart(dynamic),
+            }
+        }
+        self.status = Some(Ok(message));
+    }
+
+    fn poll(&mut self, ctx: &egui::Context, service: &ServiceInfo) {
+        let Some(Applying { keys, refused }) = &self.applying else {
+            return;
+        };
+        match refused.try_recv() {
+            Ok(result) => {
+                let refused = result.unwrap_or_else(|e| {
+                    services::log_entry(LogEntry::service("Databases", e));
+                    keys.clone()
+                });
+                if refused.is_empty() {
+                    self.status = Some(Ok(format!("Applied {} to the running server", keys.join(", "))));
+                } else {
+                    self.status = Some(Err(format!("The server refused {}; restart {} to apply", refused.join(", "), service.name)));
+                    service.require_restart(refused);
+                }
+                self.applying = None;
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+            Err(TryRecvError::Disconnected) => self.applying = None,
+        }
+    }
+
+    /// Whether `service` has an option file to edit.
+    pub fn applies_to(service: &ServiceInfo) -> bool {
+        service.defaults_file().is_some()
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, service: &ServiceInfo, session: Option<&Session>) {
+        self.follow(service);
+        self.poll(ui.ctx(), service);
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new("Server configuration").size(18.0).strong());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if let Some(path) = &self.path {
+                    theme::subtle_label(ui, path.display().to_string(), 12.0);
+                }
+            });
+        });
+        ui.add_space(6.0);
+
+        let file = match &self.file {
+            Some(Ok(file)) => file.clone(),
+            Some(Err(e)) => {
+                ui.label(egui::RichText::new(e.as_str()).size(12.0).color(theme::RED));
+                return;
+            }
+            None => return,
+        };
+
+        let was_raw = self.raw;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.raw, false, "Form");
+            ui.selectable_value(&mut self.raw, true, "Raw");
+        });
+        if self.raw != was_raw {
+            // Carry unsaved edits across.
+            self.raw = was_raw;
+            if let Some(edited) = self.edited() {
+                self.fill(&edited);
+            }
+            self.raw = !was_raw;
+        }
+        ui.add_space(6.0);
+
+        if self.raw {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.text)
+                    .code_editor()
+                    .desired_rows(16)
+                    .desired_width(f32::INFINITY),
+            );
+        } else {
+            egui::Grid::new("server_config_settings").num_columns(3).spacing([12.0, 6.0]).show(ui, |ui| {
+                for (setting, value) in SETTINGS.iter().zip(&mut self.values) {
+                    ui.label(setting.label).on_hover_text(setting.key);
+                    ui.add(egui::TextEdit::singleline(value).hint_text("default").desired_width(160.0));
+                    if !setting.dynamic {
+                        theme::subtle_label(ui, "applies on restart", 11.0);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            let port = self.values.first().and_then(|port| port.trim().parse::<u16>().ok());
+            let connects_on = service.database().and_then(|database| database.port);
+            if let (Some(port), Some(connects_on)) = (port, connects_on) {
+                if port != connects_on {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Xerve connects to {} on port {connects_on}; change the readiness port in xerve.toml to match",
+                            service.name
+                        ))
+                        .size(12.0)
+                        .color(theme::AMBER),
+                    );
+                }
+            }
+        }
+
+        let edited = self.edited();
+        let errors = edited.as_ref().map(server_config::validate).unwrap_or_default();
+        for error in &errors {
+            ui.label(egui::RichText::new(error).size(12.0).color(theme::RED));
+        }
+
+        ui.add_space(6.0);
+        ui.horizontal(|ui| {
+            let changed = edited.is_some_and(|edited| edited.contents() != file.contents());
+            if ui.add_enabled(changed && errors.is_empty(), egui::Button::new("Save")).clicked() {
+                self.save(service, session);
+            }
+            if ui.add_enabled(changed, egui::Button::new("Revert")).clicked() {
+                self.status = None;
+                self.load();
+            }
+            if self.applying.is_some() {
+                ui.spinner();
+            }
+        });
+
+        match &self.status {
+            Some(Ok(message)) => theme::subtle_label(ui, message, 12.0),
+            Some(Err(e)) => {
+                ui.label(egui::RichText::new(e).size(12.0).color(theme::RED));
+            }
+            None => {}
+        }
+    }
+}
@@ -1,18 +1,19 @@
 use eframe::egui;
-use crate::services::{Service, ServiceInfo};
+use crate::services::{ConfigError, Service, ServiceInfo, ServiceRegistry, ServiceState};
 use crate::ui::theme;
 
 pub struct ServiceRow<'a> {
     ui: &'a mut egui::Ui,
+    registry: &'a ServiceRegistry,
 }
 
 impl<'a> ServiceRow<'a> {
-    pub fn new(ui: &'a mut egui::Ui) -> Self {
-        ServiceRow { ui }
+    pub fn new(ui: &'a mut egui::Ui, registry: &'a ServiceRegistry) -> Self {
+        ServiceRow { ui, registry }
     }
 
     pub fn render(&mut self, service: &ServiceInfo) {
-        let status = service.status();
+        let state = service.state();
 
         let response = egui::Frame::new()
             .inner_margin(egui::Margin::symmetric(8i8, 10i8))
@@ -21,14 +22,14 @@ impl<'a> ServiceRow<'a> {
                 ui.horizontal(|ui| {
                     ui.add_space(8.0);
 
-                    // Status dot
-                    let indicator_color = match status.as_str() {
-                        "Running" => theme::GREEN,
-                        "Stopped" => theme::RED,
-                        _ => egui::Color32::GRAY,
-                    };
-                    let (rect, _) = ui.allocate_exact_size(egui::Vec2::new(10.0, 10.0), egui::Sense::hover());
-                    ui.painter().circle_filled(rect.center(), 5.0, indicator_color);
+                    // Status dot, or a spinner while the service is changing state
+                    if state.is_transitioning() {
+                        ui.add(egui::Spinner::new().size(10.0));
+                    } else {
+                        let (_, indicator_color) = theme::status_colors(&state);
+                        let (rect, _) = ui.allocate_exact_size(egui::Vec2::new(10.0, 10.0), egui::Sense::hover());
+                        ui.painter().circle_filled(rect.center(), 5.0, indicator_color);
+                    }
 
                     ui.add_space(10.0);
 
@@ -38,22 +39,30 @@ impl<'a> ServiceRow<'a> {
                             .size(18.0)
                             .strong()
                     );
+                    theme::health_badge(ui, &service.health());
+                    theme::restart_badge(ui, &service.restart_required());
 
                     // Right aligned controls
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         ui.add_space(12.0);
 
                         // Status pill
-                        let (fg, bg) = theme::status_colors(&status);
+                        let (fg, bg) = theme::status_colors(&state);
                         let status_response = ui.allocate_response(egui::vec2(88.0, 26.0), egui::Sense::hover());
                         ui.painter().rect_filled(status_response.rect, 13.0, bg);
                         ui.painter().text(
                             status_response.rect.center(),
                             egui::Align2::CENTER_CENTER,
-                            &status,
+                            state.label(),
                             egui::FontId::proportional(12.0),
                             fg,
                         );
+                        if let Some(since) = service.state_since() {
+                            status_response.on_hover_text(format!(
+                                "{state} for {}s",
+                                since.elapsed().as_secs()
+                            ));
+                        }
 
                         ui.add_space(10.0);
 
@@ -75,11 +84,59 @@ impl<'a> ServiceRow<'a> {
                             .on_hover_text("Stop the service")
                             .clicked()
                         {
-                            service.stop();
+                            let service = service.clone();
+                            std::thread::spawn(move || service.stop());
                         }
 
                         ui.add_space(8.0);
 
+                        // Restart button, while the service is up
+                        if state.is_active() {
+                            if ui
+                                .add(
+                                    egui::Button::new(
+                                        egui::RichText::new("Restart").color(egui::Color32::WHITE).size(13.0),
+                                    )
+                                    .fill(theme::AMBER)
+                                    .min_size(button_size)
+                                    .corner_radius(8.0),
+                                )
+                                .on_hover_text("Stop the service and start it again")
+                                .clicked()
+                            {
+                                let registry = self.registry.clone();
+                                let name = service.name.clone();
+                                std::thread::spawn(move || registry.restart(&name));
+                            }
+
+                            ui.add_space(8.0);
+                        }
+
+                        // Reload button, only for services that declare a reload command
+                        if service.can_reload() {
+                            if ui
+                                .add(
+                                    egui::Button::new(
+                                        egui::RichText::new("Reload").color(egui::Color32::WHITE).size(13.0),
+                                    )
+                                    .fill(theme::BLUE)
+                                    .min_size(button_size)
+                                    .corner_radius(8.0),
+                                )
+                                .on_hover_text(if service.can_check() {
+                                    "Test the configuration, then reload it"
+                                } else {
+                                    "Reload the service configuration"
+                                })
+                                .clicked()
+                            {
+                                let service = service.clone();
+                                std::thread::spawn(move || service.reload());
+                            }
+
+                            ui.add_space(8.0);
+                        }
+
                         // Start button
                         if ui
                             .add(
@@ -90,13 +147,23 @@ impl<'a> ServiceRow<'a> {
                                 .min_size(button_size)
                                 .corner_radius(8.0),
                             )
-                            .on_hover_text("Start the service")
+                            .on_hover_text("Start the service and anything it depends on")
                             .clicked()
                         {
-                            service.start();
+                            let registry = self.registry.clone();
+                            let name = service.name.clone();
+                            std::thread::spawn(move || registry.start_with_dependencies(&name));
                         }
                     });
                 });
+
+                if matches!(state, ServiceState::Crashed | ServiceState::CrashLoop) {
+                    Self::render_crash_details(ui, service);
+                }
+
+                if let Some(error) = service.config_error() {
+                    Self::render_config_error(ui, &error);
+                }
             })
             .response;
 
@@ -109,4 +176,61 @@ impl<'a> ServiceRow<'a> {
         self.ui.separator();
         self.ui.add_space(6.0);
     }
+
+    fn render_config_error(ui: &mut egui::Ui, error: &ConfigError) {
+        ui.horizontal(|ui| {
+            ui.add_space(28.0);
+            ui.label(
+                egui::RichText::new(format!("Configuration test failed: {}", error.message))
+                    .size(12.0)
+                    .color(theme::RED),
+            );
+        });
+        if let Some(location) = error.location() {
+            ui.horizontal(|ui| {
+                ui.add_space(28.0);
+                theme::subtle_label(ui, format!("at {location}"), 12.0);
+            });
+        }
+    }
+
+    fn render_crash_details(ui: &mut egui::Ui, service: &ServiceInfo) {
+        let Some(exit) = service.last_exit() else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.add_space(28.0);
+            theme::subtle_label(
+                ui,
+                format!(
+                    "Last exit: {} · {} restart(s) in the current window",
+                    exit.description,
+                    service.restarts_in_window()
+                ),
+                12.0,
+            );
+        });
+
+        let tail = service.stderr_tail();
+        if tail.is_empty() {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.add_space(28.0);
+            egui::CollapsingHeader::new(egui::RichText::new("Recent stderr output").size(12.0))
+                .id_salt(("stderr_tail", &service.name))
+                .show(ui, |ui| {
+                    for line in &tail {
+                        ui.label(
+                            egui::RichText::new(line)
+                                .monospace()
+                                .size(11.0)
+                                .color(theme::TEXT_MUTED),
+                        );
+                    }
+                });
+        });
+    }
 }
\ No newline at end of file
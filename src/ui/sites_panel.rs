@@ -0,0 +1,224 @@
+use eframe::egui;
+use crate::config::SitesConfig;
+use crate::logs::LogEntry;
+use crate::php::PhpVersions;
+use crate::services::{self, Service, ServiceRegistry, ServiceState};
+use crate::sites::{CertWarning, Site, Sites, SITES_FILE};
+use crate::ui::theme;
+
+/// Nginx virtual hosts, with a form to add one.
+pub struct SitesPanel {
+    sites: Result<Sites, String>,
+    /// Outcome of the last change.
+    status: Option<Result<String, String>>,
+    name: String,
+    root: String,
+    php: bool,
+    /// `None` follows the default PHP version.
+    php_version: Option<String>,
+    port: u16,
+    https: bool,
+    /// Extra host names, separated by spaces or commas.
+    aliases: String,
+    warnings: Vec<CertWarning>,
+}
+
+impl SitesPanel {
+    pub fn new(config: &SitesConfig, php: PhpVersions) -> Self {
+        let sites = Sites::load(config, php);
+        if let Ok(sites) = &sites {
+            // Server blocks follow PHP installs and the default version.
+            match sites.sync() {
+                Ok(0) => {}
+                Ok(count) => services::log_entry(LogEntry::service("Sites", format!("Regenerated {count} site config(s)"))),
+                Err(e) => services::log_entry(LogEntry::service("Sites", e)),
+            }
+        }
+        let warnings = sites.as_ref().map(Sites::certificate_warnings).unwrap_or_default();
+        for warning in &warnings {
+            services::log_entry(LogEntry::service("Sites", warning.to_string()));
+        }
+        SitesPanel {
+            sites,
+            status: None,
+            name: String::new(),
+            root: String::new(),
+            php: true,
+            php_version: None,
+            port: 80,
+            https: false,
+            aliases: String::new(),
+            warnings,
+        }
+    }
+
+    pub fn sites(&self) -> &[Site] {
+        self.sites.as_ref().map(Sites::sites).unwrap_or_default()
+    }
+
+    /// Reloads the web server so it picks up changed sites.
+    fn reload(registry: &ServiceRegistry, service: &str) {
+        let Some(service) = registry.get(service).cloned() else {
+            return;
+        };
+        if service.state() == ServiceState::Running {
+            std::thread::spawn(move || service.reload());
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, registry: &ServiceRegistry) {
+        theme::card_frame(ui.style()).show(ui, |ui| {
+            ui.set_min_width(420.0);
+            ui.add_space(6.0);
+
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Sites").size(22.0).strong());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    theme::subtle_label(ui, format!("Nginx virtual hosts from {SITES_FILE}"), 12.0);
+                });
+            });
+
+            ui.add_space(10.0);
+
+            let sites = match &mut self.sites {
+                Ok(sites) => sites,
+                Err(e) => {
+                    ui.label(egui::RichText::new(e.as_str()).size(12.0).color(theme::RED));
+                    return;
+                }
+            };
+
+            if sites.sites().is_empty() {
+                ui.label(egui::RichText::new("No sites yet. Add one below.").size(12.0).italics());
+            }
+
+            let mut removed = None;
+            let mut renewed = None;
+            for site in sites.sites() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(&site.name).size(15.0).strong());
+                    if site.php {
+                        let version = site.php_version.as_deref().unwrap_or("default");
+                        ui.label(egui::RichText::new(format!("PHP {version}")).size(11.0).strong().color(theme::ACCENT));
+                    }
+                    if site.https {
+                        ui.label(egui::RichText::new("HTTPS").size(11.0).strong().color(theme::GREEN));
+                    }
+                    theme::subtle_label(ui, &site.root, 11.0);
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Remove").on_hover_text("Delete the server block and certificate; files are kept").clicked() {
+                            removed = Some(site.name.clone());
+                        }
+                        if site.https && ui.button("Renew").on_hover_text("Issue a new certificate").clicked() {
+                            renewed = Some(site.name.clone());
+                        }
+                        if ui.button("Open in browser").on_hover_text(site.url()).clicked() {
+                            if let Err(e) = open::that(site.url()) {
+                                self.status = Some(Err(format!("Failed to open {}: {e}", site.url())));
+                            }
+                        }
+                        theme::subtle_label(ui, site.url(), 12.0);
+                    });
+                });
+            }
+
+            if let Some(name) = removed {
+                self.status = Some(sites.remove(&name).map(|()| format!("Removed {name}")));
+                Self::reload(registry, sites.service());
+                self.warnings = sites.certificate_warnings();
+            }
+
+            if let Some(name) = renewed {
+                self.status = Some(sites.renew(&name).map(|()| format!("Renewed the certificate of {name}")));
+                Self::reload(registry, sites.service());
+                self.warnings = sites.certificate_warnings();
+            }
+
+            for warning in &self.warnings {
+                ui.label(egui::RichText::new(warning.to_string()).size(12.0).color(theme::RED));
+            }
+
+            if let Some(ca) = sites.ca_path() {
+                ui.add_space(6.0);
+                ui.horizontal(|ui| {
+                    theme::subtle_label(ui, format!("Trust {} in your browser or OS to avoid certificate warnings", ca.display()), 12.0);
+                    if ui.small_button("Open folder").clicked() {
+                        if let Some(dir) = ca.parent() {
+                            if let Err(e) = open::that(dir) {
+                                self.status = Some(Err(format!("Failed to open {}: {e}", dir.display())));
+                            }
+                        }
+                    }
+                });
+            }
+
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("New site").size(16.0).strong());
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.name).hint_text("Name").desired_width(140.0));
+                let hint = sites.default_root(if self.name.trim().is_empty() { "<name>" } else { &self.name });
+                ui.add(egui::TextEdit::singleline(&mut self.root).hint_text(hint).desired_width(260.0))
+                    .on_hover_text("Document root; left empty, it goes under htdocs");
+                ui.label("Port");
+                ui.add(egui::DragValue::new(&mut self.port).range(1..=65535));
+                ui.checkbox(&mut self.php, "PHP");
+                if self.php {
+                    let php = sites.php();
+                    let default = match php.default_version() {
+                        Some(version) => format!("Default ({})", version.short()),
+                        None => "Default".to_string(),
+                    };
+                    egui::ComboBox::from_id_salt("site_php_version")
+                        .selected_text(self.php_version.clone().unwrap_or(default.clone()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.php_version, None, default);
+                            for version in php.all() {
+                                ui.selectable_value(&mut self.php_version, Some(version.short()), version.short())
+                                    .on_hover_text(version.dir.display().to_string());
+                            }
+                        });
+                }
+                ui.checkbox(&mut self.https, "HTTPS")
+                    .on_hover_text("Serve on port 443 with a certificate from the Xerve development CA");
+            });
+            ui.add(egui::TextEdit::singleline(&mut self.aliases).hint_text("Aliases, e.g. shop.test *.shop.test").desired_width(420.0));
+            ui.add_space(4.0);
+            if ui.add_enabled(!self.name.trim().is_empty(), egui::Button::new("Add site")).clicked() {
+                let site = Site {
+                    name: self.name.clone(),
+                    root: self.root.clone(),
+                    php: self.php,
+                    php_version: self.php_version.clone().filter(|_| self.php),
+                    port: self.port,
+                    https: self.https,
+                    aliases: self.aliases.split([' ', ',']).map(str::to_string).collect(),
+                };
+                let url = site.url();
+                self.status = Some(sites.add(site).map(|()| format!("Added {url}")));
+                if matches!(self.status, Some(Ok(_))) {
+                    self.name.clear();
+                    self.root.clear();
+                    self.aliases.clear();
+                    Self::reload(registry, sites.service());
+                    self.warnings = sites.certificate_warnings();
+                }
+            }
+
+            if let Some(error) = registry.get(sites.service()).and_then(|service| service.config_error()) {
+                ui.label(
+                    egui::RichText::new(format!("{} did not reload: {error}", sites.service()))
+                        .size(12.0)
+                        .color(theme::RED),
+                );
+            }
+
+            match &self.status {
+                Some(Ok(message)) => theme::subtle_label(ui, message, 12.0),
+                Some(Err(e)) => {
+                    ui.label(egui::RichText::new(e).size(12.0).color(theme::RED));
+                }
+                None => {}
+            }
+        });
+    }
+}
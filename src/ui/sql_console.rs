@@ -0,0 +1,305 @@
+use eframe::egui;
+use std::io::Write;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+use crate::database::{QueryHistory, QueryResult, ScriptOutput};
+use crate::services::ServiceInfo;
+use crate::ui::DatabaseTarget;
+use crate::ui::theme;
+
+const PAGE_SIZE: usize = 100;
+
+/// Where exported result sets are written.
+const EXPORT_DIR: &str = "exports";
+
+/// Runs SQL against a database service and shows what it returned.
+pub struct SqlConsole {
+    target: DatabaseTarget,
+    script: String,
+    history: QueryHistory,
+    pending: Option<Receiver<ScriptOutput>>,
+    output: ScriptOutput,
+    /// Index into `output.results` of the result shown in the table.
+    selected: usize,
+    page: usize,
+    /// Outcome of the last export.
+    notice: Option<Result<String, String>>,
+}
+
+impl SqlConsole {
+    pub fn new() -> Self {
+        SqlConsole {
+            target: DatabaseTarget::default(),
+            script: String::new(),
+            history: QueryHistory::load(),
+            pending: None,
+            output: ScriptOutput::default(),
+            selected: 0,
+            page: 0,
+            notice: None,
+        }
+    }
+
+    fn run(&mut self) {
+        let Some(session) = self.target.session() else {
+            return;
+        };
+        if self.script.trim().is_empty() || self.pending.is_some() {
+            return;
+        }
+        self.history.push(&self.script);
+        self.pending = Some(session.execute(&self.script));
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        match pending.try_recv() {
+            Ok(output) => {
+                // Show the last result set, which is usually the one asked for.
+                self.selected = output.results.iter().rposition(QueryResult::has_rows).unwrap_or(0);
+                self.output = output;
+                self.page = 0;
+                self.notice = None;
+                self.pending = None;
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+            Err(TryRecvError::Disconnected) => self.pending = None,
+        }
+    }
+
+    pub fn render(&mut self, ui: &mut egui::Ui, services: &[ServiceInfo]) {
+        self.poll(ui.ctx());
+
+        let databases = self.target.update(services);
+
+        theme::card_frame(ui.style()).show(ui, |ui| {
+            ui.set_min_width(420.0);
+            ui.add_space(6.0);
+
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("SQL Console").size(22.0).strong());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if let Some(session) = self.target.session() {
+                        theme::subtle_label(ui, session.address(), 12.0);
+                    }
+                });
+            });
+
+            ui.add_space(10.0);
+
+            if databases.is_empty() {
+                DatabaseTarget::empty_hint(ui);
+                return;
+            }
+
+            ui.horizontal(|ui| {
+                self.target.combo(ui, "sql_console_service", &databases);
+
+                let mut recalled = None;
+                egui::ComboBox::from_id_salt("sql_console_history")
+                    .selected_text("History")
+                    .width(240.0)
+                    .show_ui(ui, |ui| {
+                        if self.history.entries().is_empty() {
+                            ui.label(egui::RichText::new("Nothing run yet").italics());
+                        }
+                        for script in self.history.entries().iter().rev() {
+                            let title = script.lines().next().unwrap_or_default();
+                            if ui.selectable_label(false, title).on_hover_text(script).clicked() {
+                                recalled = Some(script.clone());
+                            }
+                        }
+                    });
+                if let Some(script) = recalled {
+                    self.script = script;
+                }
+            });
+
+            ui.add_space(8.0);
+            let editor = ui.add(
+                egui::TextEdit::multiline(&mut self.script)
+                    .id(egui::Id::new("sql_console_editor"))
+                    .code_editor()
+                    .desired_rows(6)
+                    .desired_width(f32::INFINITY)
+                    .hint_text("SELECT * FROM mysql.user; -- Ctrl+Enter runs every statement"),
+            );
+            let shortcut = editor.has_focus()
+                && ui.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter));
+
+            ui.add_space(6.0);
+            ui.horizontal(|ui| {
+                let running = self.pending.is_some();
+                let run = ui.add_enabled(
+                    !running,
+                    egui::Button::new(egui::RichText::new("Run").color(egui::Color32::WHITE).size(13.0))
+                        .fill(theme::GREEN)
+                        .corner_radius(8.0),
+                );
+                if run.clicked() || shortcut {
+                    self.run();
+                }
+                if running {
+                    ui.spinner();
+                    theme::subtle_label(ui, "Running...", 12.0);
+                }
+            });
+        });
+
+        if self.output.error.is_some() || !self.output.results.is_empty() {
+            ui.add_space(16.0);
+            theme::card_frame(ui.style()).show(ui, |ui| {
+                ui.set_min_width(420.0);
+                self.render_output(ui);
+            });
+        }
+    }
+
+    fn render_output(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.output.error {
+            ui.label(egui::RichText::new(error).size(12.0).monospace().color(theme::RED));
+            ui.add_space(6.0);
+        }
+
+        if self.output.results.len() > 1 {
+            egui::ScrollArea::vertical()
+                .id_salt("sql_console_results")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for (index, result) in self.output.results.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let title = result.statement.lines().next().unwrap_or_default();
+                            if ui.selectable_label(index == self.selected, title).on_hover_text(&result.statement).clicked() {
+                                self.selected = index;
+                                self.page = 0;
+                            }
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                theme::subtle_label(ui, result.summary(), 11.0);
+                            });
+                        });
+                    }
+                });
+            ui.add_space(8.0);
+        }
+
+        let Some(result) = self.output.results.get(self.selected) else {
+            return;
+        };
+
+        let pages = result.rows.len().div_ceil(PAGE_SIZE).max(1);
+        self.page = self.page.min(pages - 1);
+        ui.horizontal(|ui| {
+            theme::subtle_label(ui, result.summary(), 12.0);
+            if !result.has_rows() {
+                return;
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Export CSV").on_hover_text(format!("Save this result under {EXPORT_DIR}/")).clicked() {
+                    self.notice = Some(export_csv(result));
+                }
+                if ui.add_enabled(self.page + 1 < pages, egui::Button::new("Next")).clicked() {
+                    self.page += 1;
+                }
+                theme::subtle_label(ui, format!("Page {} of {pages}", self.page + 1), 12.0);
+                if ui.add_enabled(self.page > 0, egui::Button::new("Prev")).clicked() {
+                    self.page -= 1;
+                }
+            });
+        });
+
+        match &self.notice {
+            Some(Ok(message)) => theme::subtle_label(ui, message, 11.0),
+            Some(Err(e)) => {
+                ui.label(egui::RichText::new(e).size(11.0).color(theme::RED));
+            }
+            None => {}
+        }
+
+        if !result.has_rows() {
+            return;
+        }
+
+        ui.add_space(4.0);
+        let start = self.page * PAGE_SIZE;
+        let rows = &result.rows[start..(start + PAGE_SIZE).min(result.rows.len())];
+        egui::ScrollArea::both()
+            .id_salt("sql_console_table")
+            .auto_shrink([false, true])
+            .max_height(420.0)
+            .show(ui, |ui| {
+                egui::Grid::new("sql_console_grid")
+                    .striped(true)
+                    .spacing(egui::vec2(16.0, 4.0))
+                    .show(ui, |ui| {
+                        for column in &result.columns {
+                            ui.label(egui::RichText::new(column).size(12.0).strong().color(theme::ACCENT));
+                        }
+                        ui.end_row();
+
+                        for row in rows {
+                            for value in row {
+                                let Some(value) = value else {
+                                    ui.label(egui::RichText::new("NULL").size(12.0).monospace().italics().color(theme::TEXT_MUTED));
+                                    continue;
+                                };
+                                let shown = truncate(value);
+                                let label = ui.add(
+                                    egui::Label::new(egui::RichText::new(&shown).size(12.0).monospace())
+                                        .wrap_mode(egui::TextWrapMode::Extend),
+                                );
+                                if shown != *value {
+                                    label.on_hover_text(value);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
+
+/// Characters shown in a cell before the rest moves to its tooltip.
+const MAX_CELL_CHARS: usize = 80;
+
+fn truncate(value: &str) -> String {
+    let value = value.lines().next().unwrap_or_default();
+    match value.char_indices().nth(MAX_CELL_CHARS) {
+        Some((end, _)) => format!("{}…", &value[..end]),
+        None => value.to_string(),
+    }
+}
+
+/// Writes `result` to a new file under `EXPORT_DIR` and says where it went
+/// and whether rows past `MAX_ROWS` were left out.
+fn export_csv(result: &QueryResult) -> Result<String, String> {
+    std::fs::create_dir_all(EXPORT_DIR).map_err(|e| format!("Failed to create {EXPORT_DIR}: {e}"))?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut attempt = 1;
+    let (mut file, path) = loop {
+        let name = match attempt {
+            1 => format!("query-{stamp}.csv"),
+            n => format!("query-{stamp}-{n}.csv"),
+        };
+        let path = std::path::Path::new(EXPORT_DIR).join(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break (file, path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(format!("Failed to create {}: {e}", path.display())),
+        }
+    };
+    file.write_all(result.to_csv().as_bytes())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+
+    if result.total_rows > result.rows.len() {
+        Ok(format!(
+            "Exported the first {} of {} rows to {}",
+            result.rows.len(),
+            result.total_rows,
+            path.display()
+        ))
+    } else {
+        Ok(format!("Exported to {}", path.display()))
+    }
+}
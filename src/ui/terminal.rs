@@ -1,37 +1,286 @@
 use eframe::egui;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use crate::ui::theme;
+use crate::logs::{self, LogEntry, LogStream, Matcher, SearchOptions};
+use crate::ui::{theme, CommandPrompt};
+
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// The newest entries, numbered in the order they arrived so a view can
+/// tell which ones it has already looked at after older ones are dropped.
+struct LogBuffer {
+    entries: VecDeque<LogEntry>,
+    /// Sequence number of `entries[0]`.
+    first_seq: u64,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    fn end_seq(&self) -> u64 {
+        self.first_seq + self.entries.len() as u64
+    }
+
+    fn get(&self, seq: u64) -> Option<&LogEntry> {
+        seq.checked_sub(self.first_seq).and_then(|index| self.entries.get(index as usize))
+    }
+
+    fn trim(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.capacity);
+        self.entries.drain(..excess);
+        self.first_seq += excess as u64;
+    }
+}
+
+/// One occurrence of the search in the message of entry `seq`.
+#[derive(Debug, Clone, PartialEq)]
+struct SearchMatch {
+    seq: u64,
+    range: Range<usize>,
+}
+
+/// What the terminal panel shows; shared by every clone of the terminal.
+#[derive(Default)]
+struct TerminalView {
+    service: Option<String>,
+    hidden_streams: HashSet<LogStream>,
+    relative_times: bool,
+    search: SearchOptions,
+    only_matching: bool,
+    /// The match to scroll to, by its position rather than its index so it
+    /// survives old entries being dropped.
+    current: Option<(u64, usize)>,
+    scroll_to_current: bool,
+    cache: ViewCache,
+}
+
+/// Rows and matches for the current filters and search. Rebuilt when those
+/// change; otherwise only entries added since the last frame are searched.
+#[derive(Default)]
+struct ViewCache {
+    key: Option<(Option<String>, Vec<LogStream>, SearchOptions, bool)>,
+    matcher: Option<Matcher>,
+    error: Option<String>,
+    scanned_to: u64,
+    rows: VecDeque<u64>,
+    matches: VecDeque<SearchMatch>,
+}
+
+impl TerminalView {
+    fn key(&self) -> (Option<String>, Vec<LogStream>, SearchOptions, bool) {
+        let mut hidden: Vec<LogStream> = self.hidden_streams.iter().copied().collect();
+        hidden.sort_by_key(|stream| stream.label());
+        (self.service.clone(), hidden, self.search.clone(), self.only_matching)
+    }
+
+    fn refresh(&mut self, buffer: &LogBuffer) {
+        let key = self.key();
+        if self.cache.key.as_ref() != Some(&key) {
+            let (matcher, error) = match Matcher::new(&self.search) {
+                Ok(matcher) => (matcher, None),
+                Err(e) => (None, Some(e)),
+            };
+            self.cache = ViewCache {
+                key: Some(key),
+                matcher,
+                error,
+                scanned_to: buffer.first_seq,
+                ..ViewCache::default()
+            };
+            self.current = None;
+        }
+
+        let cache = &mut self.cache;
+        while cache.rows.front().is_some_and(|&seq| seq < buffer.first_seq) {
+            cache.rows.pop_front();
+        }
+        while cache.matches.front().is_some_and(|found| found.seq < buffer.first_seq) {
+            cache.matches.pop_front();
+        }
+
+        for seq in cache.scanned_to.max(buffer.first_seq)..buffer.end_seq() {
+            let Some(entry) = buffer.get(seq) else { continue };
+            if self.hidden_streams.contains(&entry.stream)
+                || (self.service.is_some() && entry.service != self.service)
+            {
+                continue;
+            }
+
+            let found = cache.matcher.as_ref().map(|matcher| matcher.find_all(&entry.message));
+            if let Some(found) = &found {
+                cache.matches.extend(found.iter().map(|range| SearchMatch { seq, range: range.clone() }));
+            }
+            if !self.only_matching || found.is_none_or(|found| !found.is_empty()) {
+                cache.rows.push_back(seq);
+            }
+        }
+        cache.scanned_to = buffer.end_seq();
+    }
+
+    fn current_index(&self) -> Option<usize> {
+        let (seq, start) = self.current?;
+        let index = self
+            .cache
+            .matches
+            .partition_point(|found| (found.seq, found.range.start) < (seq, start));
+        (index < self.cache.matches.len()).then_some(index)
+    }
+
+    /// Moves to the next (or previous) match, wrapping around.
+    fn step(&mut self, forward: bool) {
+        let count = self.cache.matches.len();
+        if count == 0 {
+            return;
+        }
+        let index = match (self.current_index(), forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(index), true) => (index + 1) % count,
+            (Some(index), false) => (index + count - 1) % count,
+        };
+        let found = &self.cache.matches[index];
+        self.current = Some((found.seq, found.range.start));
+        self.scroll_to_current = true;
+    }
+
+    fn matches_of(&self, seq: u64) -> impl Iterator<Item = &SearchMatch> {
+        let matches = &self.cache.matches;
+        let start = matches.partition_point(|found| found.seq < seq);
+        matches.range(start..).take_while(move |found| found.seq == seq)
+    }
+}
 
 #[derive(Clone)]
 pub struct Terminal {
-    logs: Arc<Mutex<Vec<String>>>,
+    entries: Arc<Mutex<LogBuffer>>,
+    subscribers: Arc<Mutex<Vec<Sender<LogEntry>>>>,
+    view: Arc<Mutex<TerminalView>>,
 }
 
 impl Terminal {
     pub fn new() -> Self {
         Self {
-            logs: Arc::new(Mutex::new(Vec::new())),
+            entries: Arc::new(Mutex::new(LogBuffer {
+                entries: VecDeque::new(),
+                first_seq: 0,
+                capacity: DEFAULT_CAPACITY,
+            })),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            view: Arc::new(Mutex::new(TerminalView::default())),
         }
     }
 
+    /// Changes how many entries are kept, dropping the oldest if needed.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut buffer = self.entries.lock().unwrap();
+        buffer.capacity = capacity.max(1);
+        buffer.trim();
+    }
+
+    /// Adds a message from Xerve itself.
     pub fn add_log(&self, log: String) {
-        let mut logs = self.logs.lock().unwrap();
-        logs.push(log);
-        if logs.len() > 1000 {
-            let mut new_logs = Vec::with_capacity(1000);
-            let start_index = logs.len() - 1000;
-            for i in start_index..logs.len() {
-                new_logs.push(logs[i].clone());
-            }
-            *logs = new_logs;
+        self.add_entry(LogEntry::system(log));
+    }
+
+    pub fn add_entry(&self, entry: LogEntry) {
+        // Notify while holding the buffer so `subscribe` never misses an entry.
+        let mut buffer = self.entries.lock().unwrap();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.send(entry.clone()).is_ok());
+        }
+        buffer.entries.push_back(entry);
+        buffer.trim();
+    }
+
+    /// Returns the current buffer and a receiver for every entry added after it.
+    pub fn subscribe(&self) -> (Vec<LogEntry>, Receiver<LogEntry>) {
+        let (sender, receiver) = mpsc::channel();
+        let buffer = self.entries.lock().unwrap();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
         }
+        (buffer.entries.iter().cloned().collect(), receiver)
     }
 
-    pub fn get_logs(&self) -> Vec<String> {
-        self.logs.lock().unwrap().clone()
+    fn render_filters(&self, ui: &mut egui::Ui, view: &mut TerminalView, buffer: &LogBuffer) {
+        let services: BTreeSet<&str> = buffer.entries.iter().filter_map(|entry| entry.service.as_deref()).collect();
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("terminal_service_filter")
+                .selected_text(view.service.as_deref().unwrap_or("All services"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut view.service, None, "All services");
+                    for service in services {
+                        ui.selectable_value(&mut view.service, Some(service.to_string()), service);
+                    }
+                });
+
+            ui.add_space(8.0);
+            for stream in [LogStream::Stdout, LogStream::Stderr, LogStream::System] {
+                let shown = !view.hidden_streams.contains(&stream);
+                if ui.selectable_label(shown, stream.label()).clicked() {
+                    if shown {
+                        view.hidden_streams.insert(stream);
+                    } else {
+                        view.hidden_streams.remove(&stream);
+                    }
+                }
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let label = if view.relative_times { "Relative time" } else { "Clock time" };
+                if ui.selectable_label(view.relative_times, label).clicked() {
+                    view.relative_times = !view.relative_times;
+                }
+            });
+        });
     }
 
-    pub fn render(&self, ui: &mut egui::Ui) {
+    fn render_search(&self, ui: &mut egui::Ui, view: &mut TerminalView) {
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut view.search.query)
+                    .hint_text("Search output")
+                    .desired_width(220.0),
+            );
+            let submitted = response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+            if ui.selectable_label(view.search.case_sensitive, "Aa").on_hover_text("Match case").clicked() {
+                view.search.case_sensitive = !view.search.case_sensitive;
+            }
+            if ui.selectable_label(view.search.regex, ".*").on_hover_text("Regular expression").clicked() {
+                view.search.regex = !view.search.regex;
+            }
+            if ui.selectable_label(view.only_matching, "Only matching").on_hover_text("Hide lines without a match").clicked() {
+                view.only_matching = !view.only_matching;
+            }
+
+            ui.add_space(8.0);
+            if let Some(error) = &view.cache.error {
+                ui.label(egui::RichText::new(error).size(11.0).color(theme::RED));
+            } else if !view.search.query.is_empty() {
+                let count = view.cache.matches.len();
+                let position = view.current_index().map(|index| index + 1).unwrap_or(0);
+                theme::subtle_label(ui, format!("{position} of {count}"), 11.0);
+
+                if ui.small_button("▲").on_hover_text("Previous match (Shift+Enter)").clicked() {
+                    view.step(false);
+                }
+                if ui.small_button("▼").on_hover_text("Next match (Enter)").clicked() {
+                    view.step(true);
+                }
+            }
+
+            if submitted {
+                let backwards = ui.input(|input| input.modifiers.shift);
+                view.step(!backwards);
+                response.request_focus();
+            }
+        });
+    }
+
+    pub fn render(&self, ui: &mut egui::Ui, prompt: &mut CommandPrompt) {
         theme::card_frame(ui.style())
             .show(ui, |ui| {
                 ui.set_min_height(180.0);
@@ -44,55 +293,181 @@ impl Terminal {
                             .strong(),
                     );
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        theme::subtle_label(ui, "READ ONLY", 10.0);
+                        theme::subtle_label(ui, prompt.dir().display().to_string(), 10.0);
                     });
                 });
 
                 ui.add_space(6.0);
 
-                egui::ScrollArea::vertical()
+                let buffer = self.entries.lock().unwrap();
+                let mut view = self.view.lock().unwrap();
+                self.render_filters(ui, &mut view, &buffer);
+                view.refresh(&buffer);
+                self.render_search(ui, &mut view);
+                view.refresh(&buffer);
+                ui.add_space(4.0);
+
+                let row_height = ui.spacing().interact_size.y;
+                let mut scroll_area = egui::ScrollArea::both()
                     .auto_shrink([false; 2])
-                    .stick_to_bottom(true)
-                    .show(ui, |ui| {
-                        ui.set_min_width(ui.available_width() - 10.0);
-
-                        let rect = ui.max_rect();
-                        let grid_color = egui::Color32::from_black_alpha(12);
-                        let step = 16.0;
-                        for x in (rect.left() as i32..rect.right() as i32).step_by(step as usize) {
-                            let x = x as f32;
-                            ui.painter().line_segment([
-                                egui::pos2(x, rect.top()),
-                                egui::pos2(x, rect.bottom()),
-                            ], egui::Stroke::new(1.0, grid_color));
-                        }
-
-                        let logs = self.get_logs();
-                        for log in &logs {
-                            ui.horizontal(|ui| {
-                                theme::subtle_label(ui, ">", 12.0);
-                                ui.add_space(5.0);
-                                ui.label(
-                                    egui::RichText::new(log)
-                                        .size(12.0),
-                                );
-                            });
-                        }
+                    .max_height(320.0)
+                    .stick_to_bottom(!view.scroll_to_current && view.current.is_none());
+                if view.scroll_to_current {
+                    view.scroll_to_current = false;
+                    if let Some((seq, _)) = view.current {
+                        let row = view.cache.rows.partition_point(|&row| row < seq);
+                        let spacing = ui.spacing().item_spacing.y;
+                        scroll_area = scroll_area.vertical_scroll_offset(
+                            ((row as f32 - 3.0) * (row_height + spacing)).max(0.0),
+                        );
+                    }
+                }
+
+                let view = &*view;
+                scroll_area.show_rows(ui, row_height, view.cache.rows.len(), |ui, rows| {
+                    ui.set_min_width(ui.available_width() - 10.0);
+
+                    let rect = ui.max_rect();
+                    let grid_color = egui::Color32::from_black_alpha(12);
+                    let step = 16.0;
+                    for x in (rect.left() as i32..rect.right() as i32).step_by(step as usize) {
+                        let x = x as f32;
+                        ui.painter().line_segment([
+                            egui::pos2(x, rect.top()),
+                            egui::pos2(x, rect.bottom()),
+                        ], egui::Stroke::new(1.0, grid_color));
+                    }
 
-                        if logs.is_empty() {
-                            ui.add_space(10.0);
-                            ui.horizontal(|ui| {
-                                ui.add_space(5.0);
+                    let now = chrono::Local::now();
+                    for &seq in view.cache.rows.range(rows) {
+                        let Some(entry) = buffer.get(seq) else { continue };
+                        let time = if view.relative_times {
+                            logs::format_relative(entry.timestamp, now)
+                        } else {
+                            entry.timestamp.format("%H:%M:%S").to_string()
+                        };
+
+                        ui.horizontal(|ui| {
+                            ui.set_height(row_height);
+                            ui.label(
+                                egui::RichText::new(time)
+                                    .size(11.0)
+                                    .monospace()
+                                    .color(theme::TEXT_MUTED),
+                            )
+                            .on_hover_text(entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string());
+                            if let Some(service) = &entry.service {
                                 ui.label(
-                                    egui::RichText::new("No logs available. Start a service to see output here.")
-                                        .size(12.0)
-                                        .italics(),
+                                    egui::RichText::new(service)
+                                        .size(11.0)
+                                        .strong()
+                                        .color(theme::ACCENT),
                                 );
-                            });
-                        }
+                            }
+                            ui.add_space(2.0);
+                            let highlights: Vec<(Range<usize>, bool)> = view
+                                .matches_of(seq)
+                                .map(|found| (found.range.clone(), view.current == Some((seq, found.range.start))))
+                                .collect();
+                            ui.add(egui::Label::new(theme::log_text(entry, &highlights)).extend());
+                        });
+                    }
 
-                        ui.add_space(5.0);
-                    });
+                    if view.cache.rows.is_empty() {
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            ui.add_space(5.0);
+                            ui.label(
+                                egui::RichText::new(if buffer.entries.is_empty() {
+                                    "No logs available. Start a service to see output here."
+                                } else if view.only_matching && !view.search.query.is_empty() {
+                                    "No lines match the search."
+                                } else {
+                                    "No log entries match the current filters."
+                                })
+                                    .size(12.0)
+                                    .italics(),
+                            );
+                        });
+                    }
+
+                    ui.add_space(5.0);
+                });
+
+                // Running a command logs to this terminal, so let go of it first.
+                drop(buffer);
+                ui.add_space(4.0);
+                prompt.render(ui);
             });
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal_with(capacity: usize, messages: &[&str]) -> Terminal {
+        let terminal = Terminal::new();
+        terminal.set_capacity(capacity);
+        for message in messages {
+            terminal.add_entry(LogEntry::output("Nginx", LogStream::Stdout, *message));
+        }
+        terminal
+    }
+
+    fn refresh(terminal: &Terminal, view: &mut TerminalView) {
+        view.refresh(&terminal.entries.lock().unwrap());
+    }
+
+    #[test]
+    fn test_search_is_incremental_and_follows_trimming() {
+        let terminal = terminal_with(3, &["GET /", "POST /login", "GET /favicon.ico"]);
+        let mut view = TerminalView {
+            search: SearchOptions { query: "get".to_string(), ..SearchOptions::default() },
+            only_matching: true,
+            ..TerminalView::default()
+        };
+
+        refresh(&terminal, &mut view);
+        assert_eq!(view.cache.rows, [0, 2]);
+        assert_eq!(view.cache.matches.len(), 2);
+
+        terminal.add_entry(LogEntry::output("Nginx", LogStream::Stdout, "GET /a GET /b"));
+        refresh(&terminal, &mut view);
+        assert_eq!(view.cache.rows, [2, 3]);
+        assert_eq!(view.matches_of(3).count(), 2);
+        assert_eq!(view.cache.scanned_to, 4);
+    }
+
+    #[test]
+    fn test_step_wraps_around() {
+        let terminal = terminal_with(10, &["error one", "fine", "error two"]);
+        let mut view = TerminalView {
+            search: SearchOptions { query: "error".to_string(), ..SearchOptions::default() },
+            ..TerminalView::default()
+        };
+        refresh(&terminal, &mut view);
+        assert_eq!(view.cache.rows.len(), 3);
+
+        view.step(true);
+        assert_eq!(view.current, Some((0, 0)));
+        view.step(true);
+        assert_eq!(view.current, Some((2, 0)));
+        view.step(true);
+        assert_eq!(view.current, Some((0, 0)));
+        view.step(false);
+        assert_eq!(view.current, Some((2, 0)));
+    }
+
+    #[test]
+    fn test_invalid_regex_shows_every_row() {
+        let terminal = terminal_with(10, &["a", "b"]);
+        let mut view = TerminalView {
+            search: SearchOptions { query: "(".to_string(), regex: true, ..SearchOptions::default() },
+            ..TerminalView::default()
+        };
+        refresh(&terminal, &mut view);
+        assert!(view.cache.error.is_some());
+        assert_eq!(view.cache.rows.len(), 2);
+    }
+}
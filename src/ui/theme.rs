@@ -1,4 +1,8 @@
 use eframe::egui;
+use std::ops::Range;
+use crate::logs::ansi::{AnsiColor, AnsiStyle};
+use crate::logs::{LogEntry, LogLevel, LogStream};
+use crate::services::{Health, ServiceState};
 
 
 pub const ACCENT: egui::Color32 = egui::Color32::from_rgb(80, 180, 255);
@@ -12,6 +16,11 @@ pub const TEXT_MUTED: egui::Color32 = egui::Color32::from_rgb(150, 150, 150);
 pub const GREEN: egui::Color32 = egui::Color32::from_rgb(46, 160, 67);
 pub const RED: egui::Color32 = egui::Color32::from_rgb(220, 53, 69);
 pub const BLUE: egui::Color32 = egui::Color32::from_rgb(0, 123, 255);
+pub const AMBER: egui::Color32 = egui::Color32::from_rgb(230, 145, 30);
+
+/// Backgrounds of search matches in log output.
+pub const MATCH: egui::Color32 = egui::Color32::from_rgb(92, 72, 28);
+pub const MATCH_CURRENT: egui::Color32 = egui::Color32::from_rgb(176, 112, 24);
 
 pub fn apply_theme(ctx: &egui::Context) {
     let mut style = (*ctx.style()).clone();
@@ -51,7 +60,7 @@ pub fn card_frame(style: &egui::Style) -> egui::Frame {
 
 pub fn content_container(ui: &mut egui::Ui, inner: impl FnOnce(&mut egui::Ui)) {
     let avail = ui.available_width();
-    let max = avail.min(980.0).max(340.0);
+    let max = avail.clamp(340.0, 980.0);
     let pad = ((avail - max) / 2.0).max(0.0);
 
     ui.horizontal(|ui| {
@@ -64,14 +73,146 @@ pub fn content_container(ui: &mut egui::Ui, inner: impl FnOnce(&mut egui::Ui)) {
     });
 }
 
-pub fn status_colors(status: &str) -> (egui::Color32, egui::Color32) {
-    match status {
-        "Running" => (egui::Color32::WHITE, GREEN),
-        "Stopped" => (egui::Color32::WHITE, RED),
-        _ => (egui::Color32::WHITE, egui::Color32::GRAY),
+pub fn status_colors(state: &ServiceState) -> (egui::Color32, egui::Color32) {
+    match state {
+        ServiceState::Running => (egui::Color32::WHITE, GREEN),
+        ServiceState::Stopped => (egui::Color32::WHITE, RED),
+        ServiceState::Starting | ServiceState::Stopping => (egui::Color32::WHITE, BLUE),
+        ServiceState::Crashed | ServiceState::Error(_) => (egui::Color32::WHITE, AMBER),
+        ServiceState::CrashLoop => (egui::Color32::WHITE, egui::Color32::from_rgb(150, 30, 45)),
+    }
+}
+
+/// Small amber "Unhealthy" badge, with the last probe error on hover.
+pub fn health_badge(ui: &mut egui::Ui, health: &Health) {
+    if let Health::Unhealthy { failures, last_error } = health {
+        ui.label(
+            egui::RichText::new("Unhealthy")
+                .size(12.0)
+                .strong()
+                .color(AMBER),
+        )
+        .on_hover_text(format!("{failures} failed health checks. Last error: {last_error}"));
+    }
+}
+
+/// Shown while changed settings wait for the service to restart.
+pub fn restart_badge(ui: &mut egui::Ui, settings: &[String]) {
+    if settings.is_empty() {
+        return;
+    }
+    ui.label(
+        egui::RichText::new("Restart required")
+            .size(12.0)
+            .strong()
+            .color(AMBER),
+    )
+    .on_hover_text(format!("Changed since the last start: {}", settings.join(", ")));
+}
+
+/// Text color for a terminal entry: errors red, warnings and stderr amber,
+/// Xerve's own messages muted.
+pub fn log_color(entry: &LogEntry) -> egui::Color32 {
+    match (entry.level, entry.stream) {
+        (LogLevel::Error, _) => RED,
+        (LogLevel::Warn, _) => AMBER,
+        (_, LogStream::Stderr) => egui::Color32::from_rgb(235, 185, 120),
+        (_, LogStream::System) => TEXT_MUTED,
+        (_, LogStream::Stdout) => TEXT,
+    }
+}
+
+/// The 16 standard terminal colours, tuned for the dark background.
+const ANSI_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 49, 49), (13, 188, 121), (229, 229, 16),
+    (36, 114, 200), (188, 63, 188), (17, 168, 205), (229, 229, 229),
+    (102, 102, 102), (241, 76, 76), (35, 209, 139), (245, 245, 67),
+    (59, 142, 234), (214, 112, 214), (41, 184, 219), (255, 255, 255),
+];
+
+pub fn ansi_color(color: AnsiColor) -> egui::Color32 {
+    match color {
+        AnsiColor::Rgb(r, g, b) => egui::Color32::from_rgb(r, g, b),
+        AnsiColor::Indexed(index @ 0..=15) => {
+            let (r, g, b) = ANSI_PALETTE[index as usize];
+            egui::Color32::from_rgb(r, g, b)
+        }
+        AnsiColor::Indexed(index @ 16..=231) => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let index = index - 16;
+            egui::Color32::from_rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        AnsiColor::Indexed(index) => {
+            let grey = 8 + (index - 232) * 10;
+            egui::Color32::from_gray(grey)
+        }
+    }
+}
+
+fn ansi_format(style: AnsiStyle, base: egui::Color32) -> egui::TextFormat {
+    // Bold standard colours are shown bright, as most terminals do.
+    let fg = match style.fg {
+        Some(AnsiColor::Indexed(index @ 0..=7)) if style.bold => ansi_color(AnsiColor::Indexed(index + 8)),
+        Some(color) => ansi_color(color),
+        None => base,
+    };
+    let bg = style.bg.map(ansi_color).unwrap_or(egui::Color32::TRANSPARENT);
+    let (mut fg, bg) = if style.inverse {
+        (if bg == egui::Color32::TRANSPARENT { BG_CARD } else { bg }, fg)
+    } else {
+        (fg, bg)
+    };
+    if style.dim {
+        fg = fg.gamma_multiply(0.6);
+    }
+
+    egui::TextFormat {
+        font_id: egui::FontId::proportional(12.0),
+        color: fg,
+        background: bg,
+        italics: style.italic,
+        underline: if style.underline { egui::Stroke::new(1.0, fg) } else { egui::Stroke::NONE },
+        ..Default::default()
     }
 }
 
+/// The message of a log entry in its ANSI colours, falling back to
+/// `log_color`. Each `highlights` range gets a match background, stronger
+/// when it is flagged as the current match.
+pub fn log_text(entry: &LogEntry, highlights: &[(Range<usize>, bool)]) -> egui::text::LayoutJob {
+    let message = &entry.message;
+    let base = log_color(entry);
+
+    let mut cuts: Vec<usize> = vec![0, message.len()];
+    for span in &entry.spans {
+        cuts.extend([span.range.start, span.range.end]);
+    }
+    for (range, _) in highlights {
+        cuts.extend([range.start, range.end]);
+    }
+    cuts.retain(|&cut| cut <= message.len() && message.is_char_boundary(cut));
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut job = egui::text::LayoutJob::default();
+    for part in cuts.windows(2) {
+        let (start, end) = (part[0], part[1]);
+        let style = entry
+            .spans
+            .iter()
+            .find(|span| span.range.contains(&start))
+            .map(|span| span.style)
+            .unwrap_or_default();
+        let mut format = ansi_format(style, base);
+        if let Some((_, current)) = highlights.iter().find(|(range, _)| range.contains(&start)) {
+            format.color = TEXT;
+            format.background = if *current { MATCH_CURRENT } else { MATCH };
+        }
+        job.append(&message[start..end], 0.0, format);
+    }
+    job
+}
+
 pub fn subtle_label(ui: &mut egui::Ui, text: impl Into<String>, size: f32) {
     ui.label(
         egui::RichText::new(text.into())
@@ -79,3 +220,12 @@ pub fn subtle_label(ui: &mut egui::Ui, text: impl Into<String>, size: f32) {
             .color(TEXT_MUTED),
     );
 }
+
+/// File and database sizes, e.g. `12.4 MB`.
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
@@ -1,12 +1,11 @@
 use std::env;
-use std::path::{Path};
+use std::path::{Path, PathBuf};
 
 pub fn is_in_path(dir: &str) -> bool {
-    let current_path = env::var("PATH").unwrap_or_default();
-    
-    let path_components: Vec<&str> = current_path.split(';').collect();
-    
-    path_components.contains(&dir)
+    let current_path = env::var_os("PATH").unwrap_or_default();
+    let dir = Path::new(dir);
+
+    env::split_paths(&current_path).any(|component| component == dir)
 }
 
 
@@ -21,11 +20,7 @@ pub fn get_absolute_path(relative_path: &str) -> Result<String, String> {
         Ok(abs_path) => {
             match abs_path.to_str() {
                 Some(path_str) => {
-                    let clean_path = if path_str.starts_with(r"\\?\") {
-                        &path_str[4..]
-                    } else {
-                        path_str
-                    };
+                    let clean_path = path_str.strip_prefix(r"\\?\").unwrap_or(path_str);
                     Ok(clean_path.to_string())
                 },
                 None => Err("Failed to convert path to string. This may happen if the path contains invalid UTF-8 characters.".to_string())
@@ -91,19 +86,20 @@ pub fn add_to_path(dir: &str) -> Result<(), String> {
         return Ok(());
     }
     
-    let current_path = env::var("PATH").unwrap_or_default();
+    let current_path = env::var_os("PATH").unwrap_or_default();
     
-    let new_path = if current_path.is_empty() {
-        dir.to_string()
-    } else {
-        format!("{current_path};{dir}")
-    };
+    let mut paths: Vec<PathBuf> = env::split_paths(&current_path).collect();
+    paths.push(PathBuf::from(dir));
+    
+    let new_path = env::join_paths(paths)
+        .map_err(|e| format!("Failed to build PATH with '{dir}': {e}"))?;
     
     env::set_var("PATH", &new_path);
     
     Ok(())
 }
 
+#[cfg(windows)]
 pub fn get_permanent_path_command(dir: &str) -> String {
     format!(
         "powershell -NoProfile -ExecutionPolicy Bypass -Command \"if (([Environment]::GetEnvironmentVariable('PATH', 'User') -split ';') -notcontains '{dir}') {{ $newPath = [Environment]::GetEnvironmentVariable('PATH', 'User') + ';{dir}'; [Environment]::SetEnvironmentVariable('PATH', $newPath, 'User') }}\""
@@ -118,10 +114,20 @@ pub fn get_permanent_path_command(dir: &str) -> String {
 mod tests {
     use super::*;
     
+    #[cfg(windows)]
     #[test]
     fn test_is_in_path() {
         assert!(is_in_path("C:\\WINDOWS\\system32"));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_in_path() {
+        let path = env::var_os("PATH").unwrap_or_default();
+        let first = env::split_paths(&path).next().unwrap();
+        assert!(is_in_path(first.to_str().unwrap()));
+        assert!(!is_in_path("/definitely/not/on/path"));
+    }
     
     #[test]
     fn test_get_absolute_path() {
@@ -136,6 +142,7 @@ mod tests {
         std::fs::create_dir_all(test_dir).unwrap();
         
         assert!(add_to_path(test_dir).is_ok());
+        assert!(is_in_path(test_dir));
         
         assert!(add_to_path(test_dir).is_ok());
         
@@ -0,0 +1,220 @@
+//! Drives `xerve serve` over HTTP against a fake service. The fake service is
+//! this test binary re-run with `XERVE_FAKE_SERVICE_PORT` set, which makes the
+//! `fake_service` test listen on that port instead of returning.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const TOKEN: &str = "test-token";
+
+#[test]
+fn fake_service() {
+    let Ok(port) = std::env::var("XERVE_FAKE_SERVICE_PORT") else {
+        return;
+    };
+    let listener = TcpListener::bind(("127.0.0.1", port.parse::<u16>().unwrap())).unwrap();
+    println!("fake service listening on {port}");
+
+    // Exit on our own eventually in case the test that started us failed.
+    listener.set_nonblocking(true).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(120);
+    while Instant::now() < deadline {
+        let _ = listener.accept();
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// A `xerve serve` process in a scratch directory, killed on drop.
+struct Xerve {
+    child: Child,
+    api_port: u16,
+    service_port: u16,
+    _dir: tempfile::TempDir,
+}
+
+impl Xerve {
+    fn start() -> Self {
+        Self::start_with(|service_port| format!("readiness = {{ kind = \"tcp\", port = {service_port} }}"))
+    }
+
+    /// Starts with the readiness probe and any further service settings
+    /// `settings` returns for the fake service's port.
+    fn start_with(settings: impl Fn(u16) -> String) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let api_port = free_port();
+        let service_port = free_port();
+        let fake = std::env::current_exe().unwrap();
+
+        let config = format!(
+            r#"
+[api]
+port = {api_port}
+token = "{TOKEN}"
+
+[[services]]
+name = "Fake"
+executable = '{}'
+args = ["fake_service", "--exact", "--nocapture", "--test-threads=1"]
+env = {{ XERVE_FAKE_SERVICE_PORT = "{service_port}" }}
+stop_timeout_secs = 2
+{}
+"#,
+            fake.display(),
+            settings(service_port)
+        );
+        std::fs::write(dir.path().join("xerve.toml"), config).unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_xerve"))
+            .arg("serve")
+            .current_dir(dir.path())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let xerve = Xerve { child, api_port, service_port, _dir: dir };
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(("127.0.0.1", api_port)).is_err() {
+            assert!(Instant::now() < deadline, "xerve serve did not start listening");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        xerve
+    }
+
+    fn connect(&self, method: &str, path: &str, token: Option<&str>) -> TcpStream {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.api_port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(30))).unwrap();
+        let auth = token.map(|token| format!("Authorization: Bearer {token}\r\n")).unwrap_or_default();
+        write!(stream, "{method} {path} HTTP/1.0\r\nHost: localhost\r\n{auth}\r\n").unwrap();
+        stream
+    }
+
+    fn request(&self, method: &str, path: &str, token: Option<&str>) -> (u16, String) {
+        let mut response = String::new();
+        self.connect(method, path, token).read_to_string(&mut response).unwrap();
+
+        let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").map(|(_, body)| body.to_string()).unwrap_or_default();
+        (status, body)
+    }
+
+    fn service_is_listening(&self) -> bool {
+        TcpStream::connect(("127.0.0.1", self.service_port)).is_ok()
+    }
+}
+
+impl Drop for Xerve {
+    fn drop(&mut self) {
+        let _ = self.request("POST", "/services/Fake/stop", Some(TOKEN));
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn json(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).unwrap_or_else(|e| panic!("invalid JSON {body:?}: {e}"))
+}
+
+#[test]
+fn test_requests_need_the_token() {
+    let xerve = Xerve::start();
+
+    assert_eq!(xerve.request("GET", "/services", None).0, 401);
+    assert_eq!(xerve.request("GET", "/services", Some("wrong")).0, 401);
+    assert_eq!(xerve.request("GET", "/services", Some(TOKEN)).0, 200);
+    assert_eq!(xerve.request("GET", &format!("/metrics?token={TOKEN}"), None).0, 200);
+}
+
+#[test]
+fn test_service_lifecycle() {
+    let xerve = Xerve::start();
+
+    let (status, body) = xerve.request("GET", "/services", Some(TOKEN));
+    assert_eq!(status, 200);
+    let services = json(&body);
+    assert_eq!(services[0]["name"], "Fake");
+    assert_eq!(services[0]["state"], "stopped");
+
+    assert_eq!(xerve.request("POST", "/services/Nope/start", Some(TOKEN)).0, 404);
+    assert_eq!(xerve.request("GET", "/services/Fake/start", Some(TOKEN)).0, 405);
+
+    let (status, body) = xerve.request("POST", "/services/Fake/start", Some(TOKEN));
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(json(&body)["state"], "running");
+    assert!(xerve.service_is_listening());
+    let first_pid = json(&body)["pid"].clone();
+
+    let (status, body) = xerve.request("GET", "/metrics", Some(TOKEN));
+    assert_eq!(status, 200);
+    assert_eq!(json(&body)[0]["name"], "Fake");
+
+    let (status, body) = xerve.request("POST", "/services/Fake/restart", Some(TOKEN));
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(json(&body)["state"], "running");
+    assert_ne!(json(&body)["pid"], first_pid);
+
+    let (status, body) = xerve.request("POST", "/services/fake/stop", Some(TOKEN));
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(json(&body)["state"], "stopped");
+    assert!(!xerve.service_is_listening());
+}
+
+#[test]
+fn test_logs_stream_as_server_sent_events() {
+    let xerve = Xerve::start();
+    let stream = xerve.connect("GET", "/logs", Some(TOKEN));
+
+    let (status, _) = xerve.request("POST", "/services/Fake/start", Some(TOKEN));
+    assert_eq!(status, 200);
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("HTTP/1.1 200"), "{line}");
+
+    let deadline = Instant::now() + Duration::from_secs(20);
+    loop {
+        assert!(Instant::now() < deadline, "service output never appeared in the event stream");
+        line.clear();
+        if reader.read_line(&mut line).unwrap() == 0 {
+            panic!("event stream closed");
+        }
+        if line.starts_with("data: ") && line.contains("fake service listening") {
+            break;
+        }
+    }
+}
+
+#[test]
+fn test_service_that_never_becomes_ready_is_stopped_and_restarted() {
+    // Probing a port nothing listens on, the fake service never becomes ready.
+    let probe_port = free_port();
+    let xerve = Xerve::start_with(|_| {
+        format!(
+            "readiness = {{ kind = \"tcp\", port = {probe_port} }}\n\
+             health = {{ startup_timeout_secs = 1 }}\n\
+             restart = {{ policy = \"on-failure\", max_restarts = 1, window_secs = 60, backoff_ms = 100 }}"
+        )
+    });
+
+    let (status, body) = xerve.request("POST", "/services/Fake/start", Some(TOKEN));
+    assert_eq!(status, 500, "{body}");
+
+    // Failing once restarts it; failing again puts it in a crash loop.
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        let (_, body) = xerve.request("GET", "/services", Some(TOKEN));
+        if json(&body)[0]["state"] == "crash_loop" {
+            break;
+        }
+        assert!(Instant::now() < deadline, "service never reached a crash loop: {body}");
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    assert!(!xerve.service_is_listening());
+}